@@ -40,6 +40,10 @@ pub struct Model {
     pub created_at: DateTime,
     pub updated_at: DateTime,
     pub artist_id: Option<String>,
+    pub compilation: bool,
+    pub library_id: Option<String>,
+    // Album holding the better copies when every song of this album is a duplicate
+    pub duplicate_of: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
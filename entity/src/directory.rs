@@ -12,6 +12,7 @@ pub struct Model {
     pub mtime: DateTime,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    pub library_id: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter)]
@@ -1,8 +1,21 @@
 pub mod album;
+pub mod album_genre;
+pub mod album_style;
 pub mod artist;
+pub mod artist_credit;
 pub mod cast_devices;
 pub mod directory;
+pub mod genre;
+pub mod library;
+pub mod library_issue;
+pub mod lyrics;
+pub mod play;
 pub mod playlist;
 pub mod playlist_song;
 pub mod setting;
 pub mod song;
+pub mod song_genre;
+pub mod song_style;
+pub mod style;
+pub mod task;
+pub mod user;
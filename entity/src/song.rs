@@ -39,6 +39,8 @@ pub struct Model {
     pub bpm: Option<i32>,
     pub comp: Option<i32>,
     pub mb_track_id: Option<String>,
+    // MUSICBRAINZ_TRACKID. The recording shared by every release the song is on
+    pub mb_recording_id: Option<String>,
     pub mb_album_id: Option<String>,
     pub mb_artist_id: Option<String>,
     pub mb_albumartist_id: Option<String>,
@@ -72,8 +74,9 @@ pub struct Model {
     pub channels: Option<String>,
     pub track: Option<i32>,
     pub disk: Option<i32>,
+    pub disc_total: Option<i32>,
     pub codec: Option<String>,
-    pub length: u32,
+    pub length: i32,
     pub label: Option<String>,
     pub sample_rate: Option<String>,
     pub bits_per_sample: Option<i32>,
@@ -81,6 +84,20 @@ pub struct Model {
     pub updated_at: DateTime,
     pub album_id: Option<String>,
     pub liked: bool,
+    pub library_id: Option<String>,
+    // Best quality copy when this song is a duplicate of another song
+    pub duplicate_of: Option<String>,
+    // ReplayGain 2.0 gain in dB relative to -18 LUFS and linear true peak. Tagged or measured by scan_loudness
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+    // Gapless playback info. Total samples per channel in the stream, samples of encoder priming and padding
+    // to drop, and the exact duration of the remaining samples
+    pub total_samples: Option<i64>,
+    pub encoder_delay: Option<i32>,
+    pub encoder_padding: Option<i32>,
+    pub duration_ms: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,38 +1,151 @@
 pub use sea_orm_migration::prelude::*;
-use sea_orm_migration::sea_orm::{EntityTrait, Schema};
 
 mod m20220101_000001_create_table;
+mod m20261019_000001_create_play_table;
+mod m20261019_000002_create_genre_tables;
+mod m20261019_000003_create_artist_credit_table;
+mod m20261019_000004_add_album_compilation;
+mod m20261019_000005_add_song_disc_total;
+mod m20261019_000006_create_library_table;
+mod m20261019_000007_create_task_table;
+mod m20261019_000008_create_user_table;
+mod m20261019_000009_create_library_issue_table;
+mod m20261019_000010_add_duplicate_columns;
+mod m20261019_000011_add_replaygain_columns;
+mod m20261019_000012_create_lyrics_table;
+mod m20261019_000013_add_gapless_columns;
+mod m20261019_000014_upgrade_baseline_columns;
+mod m20261019_000015_add_queued_task_index;
+mod m20261019_000016_add_username_index;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20261019_000001_create_play_table::Migration),
+            Box::new(m20261019_000002_create_genre_tables::Migration),
+            Box::new(m20261019_000003_create_artist_credit_table::Migration),
+            Box::new(m20261019_000004_add_album_compilation::Migration),
+            Box::new(m20261019_000005_add_song_disc_total::Migration),
+            Box::new(m20261019_000006_create_library_table::Migration),
+            Box::new(m20261019_000007_create_task_table::Migration),
+            Box::new(m20261019_000008_create_user_table::Migration),
+            Box::new(m20261019_000009_create_library_issue_table::Migration),
+            Box::new(m20261019_000010_add_duplicate_columns::Migration),
+            Box::new(m20261019_000011_add_replaygain_columns::Migration),
+            Box::new(m20261019_000012_create_lyrics_table::Migration),
+            Box::new(m20261019_000013_add_gapless_columns::Migration),
+            Box::new(m20261019_000014_upgrade_baseline_columns::Migration),
+            Box::new(m20261019_000015_add_queued_task_index::Migration),
+            Box::new(m20261019_000016_add_username_index::Migration),
+        ]
     }
 }
 
-async fn create_table<E>(db: &SchemaManagerConnection<'_>, schema: &Schema, entity: E)
+// Column definitions shared by the migrations. Changing one changes the schema older migrations create,
+// so add a new helper instead
+fn primary_id<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name)
+        .string()
+        .not_null()
+        .primary_key()
+        .to_owned()
+}
+
+fn text<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).string().not_null().to_owned()
+}
+
+fn text_null<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).string().null().to_owned()
+}
+
+fn integer<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).integer().not_null().to_owned()
+}
+
+fn integer_null<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).integer().null().to_owned()
+}
+
+fn big_integer_null<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).big_integer().null().to_owned()
+}
+
+fn double_null<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).double().null().to_owned()
+}
+
+fn boolean<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).boolean().not_null().to_owned()
+}
+
+fn date_time<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).date_time().not_null().to_owned()
+}
+
+fn date_time_null<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name).date_time().null().to_owned()
+}
+
+// Index named idx-<table>-<column> as used by every migration
+fn index<T, C>(table: T, column: C) -> IndexCreateStatement
 where
-    E: EntityTrait,
+    T: IntoIden,
+    C: IntoIden,
 {
-    let builder = db.get_database_backend();
-    let stmt = builder.build(&schema.create_table_from_entity(entity));
+    let (table, column) = (table.into_iden(), column.into_iden());
+    Index::create()
+        .if_not_exists()
+        .name(format!("idx-{}-{}", table.to_string(), column.to_string()))
+        .table(table)
+        .col(column)
+        .to_owned()
+}
 
-    match db.execute(stmt).await {
-        Ok(_) => println!("Migrated {}", entity.table_name()),
-        Err(e) => println!("Error: {e}"),
+// SQLite only adds or drops one column per statement so every column gets its own ALTER TABLE
+async fn add_columns<T, I>(manager: &SchemaManager<'_>, table: T, columns: I) -> Result<(), DbErr>
+where
+    T: IntoIden,
+    I: IntoIterator<Item = ColumnDef>,
+{
+    let table = table.into_iden();
+    for mut column in columns {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table.clone())
+                    .add_column(&mut column)
+                    .to_owned(),
+            )
+            .await?;
     }
+    Ok(())
 }
 
-pub async fn create_tables(db: &SchemaManagerConnection<'_>) {
-    let schema = Schema::new(db.get_database_backend());
-    create_table(db, &schema, entity::setting::Entity).await;
-    create_table(db, &schema, entity::song::Entity).await;
-    create_table(db, &schema, entity::album::Entity).await;
-    create_table(db, &schema, entity::artist::Entity).await;
-    create_table(db, &schema, entity::directory::Entity).await;
-    create_table(db, &schema, entity::playlist::Entity).await;
-    create_table(db, &schema, entity::playlist_song::Entity).await;
-    create_table(db, &schema, entity::cast_devices::Entity).await;
+async fn drop_columns<T, C, I>(
+    manager: &SchemaManager<'_>,
+    table: T,
+    columns: I,
+) -> Result<(), DbErr>
+where
+    T: IntoIden,
+    C: IntoIden,
+    I: IntoIterator<Item = C>,
+{
+    let table = table.into_iden();
+    for column in columns {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table.clone())
+                    .drop_column(column)
+                    .to_owned(),
+            )
+            .await?;
+    }
+    Ok(())
 }
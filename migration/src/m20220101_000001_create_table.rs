@@ -1,111 +1,460 @@
 use sea_orm_migration::prelude::*;
 
-use crate::create_tables;
+use crate::{
+    big_integer_null, boolean, date_time, index, integer, integer_null, primary_id, text, text_null,
+};
 
+// Schema shipped before versioned migrations. Creates the tables of a fresh database
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
 #[async_trait::async_trait]
-#[allow(unused_must_use)]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        create_tables(manager.get_connection()).await;
-        manager
-            .create_index(
-                Index::create()
-                    .name(&format!(
-                        "idx-{}-{}",
-                        entity::album::Entity.to_string(),
-                        entity::album::Column::Id.to_string()
-                    ))
-                    .table(entity::album::Entity)
-                    .col(entity::album::Column::Id)
-                    .to_owned(),
-            )
-            .await;
-        manager
-            .create_index(
-                Index::create()
-                    .name(&format!(
-                        "idx-{}-{}",
-                        entity::song::Entity.to_string(),
-                        entity::song::Column::Id.to_string()
-                    ))
-                    .table(entity::song::Entity)
-                    .col(entity::song::Column::Id)
-                    .to_owned(),
-            )
-            .await;
-        manager
-            .create_index(
-                Index::create()
-                    .name(&format!(
-                        "idx-{}-{}",
-                        entity::artist::Entity.to_string(),
-                        entity::artist::Column::Id.to_string()
-                    ))
-                    .table(entity::artist::Entity)
-                    .col(entity::artist::Column::Id)
-                    .to_owned(),
-            )
-            .await;
-        manager
-            .create_index(
-                Index::create()
-                    .name(&format!(
-                        "idx-{}-{}",
-                        entity::directory::Entity.to_string(),
-                        entity::directory::Column::Id.to_string()
-                    ))
-                    .table(entity::directory::Entity)
-                    .col(entity::directory::Column::Id)
-                    .to_owned(),
-            )
-            .await;
-        manager
-            .create_index(
-                Index::create()
-                    .name(&format!(
-                        "idx-{}-{}",
-                        entity::artist::Entity.to_string(),
-                        entity::artist::Column::Name.to_string()
-                    ))
-                    .table(entity::artist::Entity)
-                    .col(entity::artist::Column::Name)
-                    .to_owned(),
-            )
-            .await;
-        manager
-            .create_index(
-                Index::create()
-                    .name(&format!(
-                        "idx-{}-{}",
-                        entity::album::Entity.to_string(),
-                        entity::album::Column::Name.to_string()
-                    ))
-                    .table(entity::album::Entity)
-                    .col(entity::album::Column::Name)
-                    .to_owned(),
-            )
-            .await;
-        manager
-            .create_index(
-                Index::create()
-                    .name(&format!(
-                        "idx-{}-{}",
-                        entity::song::Entity.to_string(),
-                        entity::song::Column::Path.to_string()
-                    ))
-                    .table(entity::song::Entity)
-                    .col(entity::song::Column::Path)
-                    .to_owned(),
-            )
-            .await;
+        for table in baseline_tables() {
+            // deaftone.sqlite files created before versioned migrations already have the tables
+            if manager.has_table(table.name.to_string()).await? {
+                continue;
+            }
+            let mut create = Table::create();
+            create.table(table.name.clone());
+            for mut column in table.columns {
+                create.col(&mut column);
+            }
+            for mut foreign_key in table.foreign_keys {
+                create.foreign_key(&mut foreign_key);
+            }
+            manager.create_table(create).await?;
+        }
+        for (table, column) in [
+            (Albums::Table.into_iden(), Albums::Id.into_iden()),
+            (Songs::Table.into_iden(), Songs::Id.into_iden()),
+            (Artists::Table.into_iden(), Artists::Id.into_iden()),
+            (Directories::Table.into_iden(), Directories::Id.into_iden()),
+            (Artists::Table.into_iden(), Artists::Name.into_iden()),
+            (Albums::Table.into_iden(), Albums::Name.into_iden()),
+            (Songs::Table.into_iden(), Songs::Path.into_iden()),
+        ] {
+            manager.create_index(index(table, column)).await?;
+        }
         Ok(())
     }
 
-    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
-        // Replace the sample below with your own migration scripts
-        todo!();
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Tables are dropped before the tables they reference
+        for table in baseline_tables().into_iter().rev() {
+            manager
+                .drop_table(Table::drop().table(table.name).if_exists().to_owned())
+                .await?;
+        }
+        Ok(())
     }
 }
+
+pub(crate) struct BaselineTable {
+    pub(crate) name: DynIden,
+    pub(crate) columns: Vec<ColumnDef>,
+    foreign_keys: Vec<ForeignKeyCreateStatement>,
+}
+
+// Ordered so referenced tables are created first
+pub(crate) fn baseline_tables() -> Vec<BaselineTable> {
+    vec![
+        BaselineTable {
+            name: Settings::Table.into_iden(),
+            columns: vec![
+                text(Settings::Name).primary_key().to_owned(),
+                text(Settings::Value),
+            ],
+            foreign_keys: vec![],
+        },
+        BaselineTable {
+            name: Artists::Table.into_iden(),
+            columns: vec![
+                primary_id(Artists::Id),
+                text(Artists::Name),
+                text_null(Artists::Image),
+                text_null(Artists::Biography),
+                text_null(Artists::MbArtistId),
+                text_null(Artists::LinkTwitter),
+                text_null(Artists::LinkDiscogs),
+                text_null(Artists::LinkYoutube),
+                text_null(Artists::LinkFacebook),
+                text_null(Artists::LinkSpotify),
+                text_null(Artists::LinkItunes),
+                text_null(Artists::LinkWiki),
+                text_null(Artists::LinkAppleMusic),
+                text_null(Artists::LinkAmazonMusic),
+                text_null(Artists::LinkAllMusic),
+                text_null(Artists::LinkDeezer),
+                text_null(Artists::LinkTidal),
+                date_time(Artists::CreatedAt),
+                date_time(Artists::UpdatedAt),
+            ],
+            foreign_keys: vec![],
+        },
+        BaselineTable {
+            name: Albums::Table.into_iden(),
+            columns: vec![
+                primary_id(Albums::Id),
+                text(Albums::Path),
+                text(Albums::Name),
+                text_null(Albums::AlbumArtist),
+                text_null(Albums::AlbumArtistSort),
+                text_null(Albums::AlbumArtistCredit),
+                text_null(Albums::Genre),
+                text_null(Albums::Style),
+                text_null(Albums::DiscogsAlbumid),
+                text_null(Albums::DiscogsArtistid),
+                text_null(Albums::DiscogsLabelid),
+                integer(Albums::Year),
+                text_null(Albums::Composer),
+                text_null(Albums::MbAlbumId),
+                text_null(Albums::MbArtistId),
+                text_null(Albums::AlbumType),
+                text_null(Albums::Label),
+                text_null(Albums::MbReleasegroupId),
+                text_null(Albums::Asin),
+                text_null(Albums::CatalogNum),
+                text_null(Albums::Script),
+                text_null(Albums::Language),
+                text_null(Albums::Country),
+                text_null(Albums::AlbumStatus),
+                text_null(Albums::AlbumDisambig),
+                text_null(Albums::ReleaseGroupDisambig),
+                text(Albums::ArtistName),
+                text_null(Albums::Cover),
+                date_time(Albums::CreatedAt),
+                date_time(Albums::UpdatedAt),
+                text_null(Albums::ArtistId),
+            ],
+            foreign_keys: vec![ForeignKey::create()
+                .name("fk-albums-artist_id")
+                .from(Albums::Table, Albums::ArtistId)
+                .to(Artists::Table, Artists::Id)
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()],
+        },
+        BaselineTable {
+            name: Songs::Table.into_iden(),
+            columns: vec![
+                primary_id(Songs::Id),
+                text(Songs::Path).unique_key().to_owned(),
+                text(Songs::Title),
+                text(Songs::Artist),
+                text_null(Songs::ArtistSort),
+                text_null(Songs::ArtistCredit),
+                text(Songs::AlbumName),
+                text_null(Songs::AlbumArtist),
+                text_null(Songs::AlbumSort),
+                text_null(Songs::AlbumArtistCredit),
+                text_null(Songs::Genre),
+                text_null(Songs::Style),
+                text_null(Songs::DiscogsAlbumid),
+                text_null(Songs::DiscogsArtistid),
+                text_null(Songs::DiscogsLabelid),
+                text_null(Songs::Lyricist),
+                text_null(Songs::Composer),
+                text_null(Songs::ComposerSort),
+                text_null(Songs::Work),
+                text_null(Songs::MbWorkid),
+                text_null(Songs::WorkDisambig),
+                text_null(Songs::Arranger),
+                text_null(Songs::Grouping),
+                integer_null(Songs::Year),
+                text_null(Songs::Lyrics),
+                text_null(Songs::Comments),
+                integer_null(Songs::Bpm),
+                integer_null(Songs::Comp),
+                text_null(Songs::MbTrackId),
+                text_null(Songs::MbAlbumId),
+                text_null(Songs::MbArtistId),
+                text_null(Songs::MbAlbumartistId),
+                text_null(Songs::MbReleasetrackId),
+                text_null(Songs::MbReleasegroupId),
+                text_null(Songs::TrackDisambig),
+                text_null(Songs::AlbumType),
+                text_null(Songs::AlbumTypes),
+                text_null(Songs::AcoustidFingerprint),
+                text_null(Songs::AcoustidId),
+                text_null(Songs::Asin),
+                text_null(Songs::Isrc),
+                text_null(Songs::CatalogNum),
+                text_null(Songs::Script),
+                text_null(Songs::Language),
+                text_null(Songs::Country),
+                text_null(Songs::AlbumStatus),
+                text_null(Songs::Media),
+                text_null(Songs::AlbumDisambig),
+                text_null(Songs::ReleaseGroupDisambig),
+                text_null(Songs::DiscTitle),
+                text_null(Songs::Encoder),
+                integer_null(Songs::OriginalYear),
+                text_null(Songs::InitialKey),
+                big_integer_null(Songs::BitRate),
+                integer_null(Songs::BitrateMode),
+                text_null(Songs::EncoderInfo),
+                text_null(Songs::EncoderSettings),
+                text_null(Songs::Format),
+                text_null(Songs::Bitdepth),
+                text_null(Songs::Channels),
+                integer_null(Songs::Track),
+                integer_null(Songs::Disk),
+                text_null(Songs::Codec),
+                integer(Songs::Length),
+                text_null(Songs::Label),
+                text_null(Songs::SampleRate),
+                integer_null(Songs::BitsPerSample),
+                date_time(Songs::CreatedAt),
+                date_time(Songs::UpdatedAt),
+                text_null(Songs::AlbumId),
+                boolean(Songs::Liked),
+            ],
+            foreign_keys: vec![ForeignKey::create()
+                .name("fk-songs-album_id")
+                .from(Songs::Table, Songs::AlbumId)
+                .to(Albums::Table, Albums::Id)
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()],
+        },
+        BaselineTable {
+            name: Directories::Table.into_iden(),
+            columns: vec![
+                primary_id(Directories::Id),
+                text(Directories::Path).unique_key().to_owned(),
+                date_time(Directories::Mtime),
+                date_time(Directories::CreatedAt),
+                date_time(Directories::UpdatedAt),
+            ],
+            foreign_keys: vec![],
+        },
+        BaselineTable {
+            name: Playlists::Table.into_iden(),
+            columns: vec![
+                primary_id(Playlists::Id),
+                text(Playlists::Name),
+                date_time(Playlists::CreatedAt),
+                date_time(Playlists::UpdatedAt),
+            ],
+            foreign_keys: vec![],
+        },
+        BaselineTable {
+            name: PlaylistsSong::Table.into_iden(),
+            columns: vec![
+                primary_id(PlaylistsSong::Id),
+                text_null(PlaylistsSong::PlaylistId),
+                text_null(PlaylistsSong::SongId),
+            ],
+            foreign_keys: vec![
+                ForeignKey::create()
+                    .name("fk-playlists_song-playlist_id")
+                    .from(PlaylistsSong::Table, PlaylistsSong::PlaylistId)
+                    .to(Playlists::Table, Playlists::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .on_update(ForeignKeyAction::Cascade)
+                    .to_owned(),
+                ForeignKey::create()
+                    .name("fk-playlists_song-song_id")
+                    .from(PlaylistsSong::Table, PlaylistsSong::SongId)
+                    .to(Songs::Table, Songs::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .on_update(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            ],
+        },
+        BaselineTable {
+            name: CastDevices::Table.into_iden(),
+            columns: vec![
+                primary_id(CastDevices::Id),
+                text(CastDevices::Name).unique_key().to_owned(),
+                text(CastDevices::AddressV4),
+                date_time(CastDevices::CreatedAt),
+                date_time(CastDevices::UpdatedAt),
+            ],
+            foreign_keys: vec![],
+        },
+    ]
+}
+
+#[derive(Iden)]
+enum Settings {
+    Table,
+    Name,
+    Value,
+}
+
+#[derive(Iden)]
+enum Artists {
+    Table,
+    Id,
+    Name,
+    Image,
+    Biography,
+    MbArtistId,
+    LinkTwitter,
+    LinkDiscogs,
+    LinkYoutube,
+    LinkFacebook,
+    LinkSpotify,
+    LinkItunes,
+    LinkWiki,
+    LinkAppleMusic,
+    LinkAmazonMusic,
+    LinkAllMusic,
+    LinkDeezer,
+    LinkTidal,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum Albums {
+    Table,
+    Id,
+    Path,
+    Name,
+    AlbumArtist,
+    AlbumArtistSort,
+    AlbumArtistCredit,
+    Genre,
+    Style,
+    DiscogsAlbumid,
+    DiscogsArtistid,
+    DiscogsLabelid,
+    Year,
+    Composer,
+    MbAlbumId,
+    MbArtistId,
+    AlbumType,
+    Label,
+    MbReleasegroupId,
+    Asin,
+    CatalogNum,
+    Script,
+    Language,
+    Country,
+    AlbumStatus,
+    AlbumDisambig,
+    ReleaseGroupDisambig,
+    ArtistName,
+    Cover,
+    CreatedAt,
+    UpdatedAt,
+    ArtistId,
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    Id,
+    Path,
+    Title,
+    Artist,
+    ArtistSort,
+    ArtistCredit,
+    AlbumName,
+    AlbumArtist,
+    AlbumSort,
+    AlbumArtistCredit,
+    Genre,
+    Style,
+    DiscogsAlbumid,
+    DiscogsArtistid,
+    DiscogsLabelid,
+    Lyricist,
+    Composer,
+    ComposerSort,
+    Work,
+    MbWorkid,
+    WorkDisambig,
+    Arranger,
+    Grouping,
+    Year,
+    Lyrics,
+    Comments,
+    Bpm,
+    Comp,
+    MbTrackId,
+    MbAlbumId,
+    MbArtistId,
+    MbAlbumartistId,
+    MbReleasetrackId,
+    MbReleasegroupId,
+    TrackDisambig,
+    AlbumType,
+    AlbumTypes,
+    AcoustidFingerprint,
+    AcoustidId,
+    Asin,
+    Isrc,
+    CatalogNum,
+    Script,
+    Language,
+    Country,
+    AlbumStatus,
+    Media,
+    AlbumDisambig,
+    ReleaseGroupDisambig,
+    DiscTitle,
+    Encoder,
+    OriginalYear,
+    InitialKey,
+    BitRate,
+    BitrateMode,
+    EncoderInfo,
+    EncoderSettings,
+    Format,
+    Bitdepth,
+    Channels,
+    Track,
+    Disk,
+    Codec,
+    Length,
+    Label,
+    SampleRate,
+    BitsPerSample,
+    CreatedAt,
+    UpdatedAt,
+    AlbumId,
+    Liked,
+}
+
+#[derive(Iden)]
+enum Directories {
+    Table,
+    Id,
+    Path,
+    Mtime,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum Playlists {
+    Table,
+    Id,
+    Name,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum PlaylistsSong {
+    Table,
+    Id,
+    PlaylistId,
+    SongId,
+}
+
+#[derive(Iden)]
+enum CastDevices {
+    Table,
+    Id,
+    Name,
+    #[iden = "address_v4"]
+    AddressV4,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{date_time, index, integer, primary_id, text_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Plays::Table)
+                    .if_not_exists()
+                    .col(&mut primary_id(Plays::Id))
+                    .col(&mut text_null(Plays::SongId))
+                    .col(&mut date_time(Plays::PlayedAt))
+                    .col(&mut integer(Plays::Duration))
+                    // Play history outlives the song so listening stats don't change when files are removed
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-plays-song_id")
+                            .from(Plays::Table, Plays::SongId)
+                            .to(Songs::Table, Songs::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(index(Plays::Table, Plays::PlayedAt))
+            .await?;
+        manager
+            .create_index(index(Plays::Table, Plays::SongId))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Plays::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Plays {
+    Table,
+    Id,
+    SongId,
+    PlayedAt,
+    Duration,
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    Id,
+}
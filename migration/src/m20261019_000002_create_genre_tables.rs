@@ -0,0 +1,162 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{date_time, index, primary_id, text};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in [Genres::Table.into_iden(), Styles::Table.into_iden()] {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(table)
+                        .if_not_exists()
+                        .col(&mut primary_id(Genres::Id))
+                        .col(text(Genres::Name).unique_key())
+                        .col(&mut date_time(Genres::CreatedAt))
+                        .col(&mut date_time(Genres::UpdatedAt))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        // Join tables of songs and albums with their genres and styles
+        for (table, item, item_table, tag, tag_table) in [
+            (
+                SongGenres::Table.into_iden(),
+                Join::SongId,
+                Songs::Table.into_iden(),
+                Join::GenreId,
+                Genres::Table.into_iden(),
+            ),
+            (
+                AlbumGenres::Table.into_iden(),
+                Join::AlbumId,
+                Albums::Table.into_iden(),
+                Join::GenreId,
+                Genres::Table.into_iden(),
+            ),
+            (
+                SongStyles::Table.into_iden(),
+                Join::SongId,
+                Songs::Table.into_iden(),
+                Join::StyleId,
+                Styles::Table.into_iden(),
+            ),
+            (
+                AlbumStyles::Table.into_iden(),
+                Join::AlbumId,
+                Albums::Table.into_iden(),
+                Join::StyleId,
+                Styles::Table.into_iden(),
+            ),
+        ] {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(table.clone())
+                        .if_not_exists()
+                        .col(&mut text(item))
+                        .col(&mut text(tag))
+                        .primary_key(Index::create().col(item).col(tag))
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name(format!("fk-{}-{}", table.to_string(), item.to_string()))
+                                .from(table.clone(), item)
+                                .to(item_table, Join::Id)
+                                .on_delete(ForeignKeyAction::Cascade)
+                                .on_update(ForeignKeyAction::Cascade),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name(format!("fk-{}-{}", table.to_string(), tag.to_string()))
+                                .from(table.clone(), tag)
+                                .to(tag_table, Join::Id)
+                                .on_delete(ForeignKeyAction::Cascade)
+                                .on_update(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        manager
+            .create_index(index(SongGenres::Table, Join::GenreId))
+            .await?;
+        manager
+            .create_index(index(AlbumGenres::Table, Join::GenreId))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in [
+            AlbumStyles::Table.into_iden(),
+            SongStyles::Table.into_iden(),
+            AlbumGenres::Table.into_iden(),
+            SongGenres::Table.into_iden(),
+            Styles::Table.into_iden(),
+            Genres::Table.into_iden(),
+        ] {
+            manager
+                .drop_table(Table::drop().table(table).to_owned())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+// Genres and styles share their columns
+#[derive(Iden)]
+enum Genres {
+    Table,
+    Id,
+    Name,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum Styles {
+    Table,
+}
+
+#[derive(Iden)]
+enum SongGenres {
+    Table,
+}
+
+#[derive(Iden)]
+enum AlbumGenres {
+    Table,
+}
+
+#[derive(Iden)]
+enum SongStyles {
+    Table,
+}
+
+#[derive(Iden)]
+enum AlbumStyles {
+    Table,
+}
+
+// Columns of the join tables
+#[derive(Iden, Clone, Copy)]
+enum Join {
+    Id,
+    SongId,
+    AlbumId,
+    GenreId,
+    StyleId,
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+}
+
+#[derive(Iden)]
+enum Albums {
+    Table,
+}
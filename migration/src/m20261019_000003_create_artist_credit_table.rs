@@ -0,0 +1,91 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{index, integer, primary_id, text, text_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ArtistCredits::Table)
+                    .if_not_exists()
+                    .col(&mut primary_id(ArtistCredits::Id))
+                    .col(&mut text(ArtistCredits::ArtistId))
+                    .col(&mut text_null(ArtistCredits::SongId))
+                    .col(&mut text_null(ArtistCredits::AlbumId))
+                    .col(&mut text(ArtistCredits::Role))
+                    .col(&mut integer(ArtistCredits::Position))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-artist_credits-artist_id")
+                            .from(ArtistCredits::Table, ArtistCredits::ArtistId)
+                            .to(Artists::Table, Artists::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-artist_credits-song_id")
+                            .from(ArtistCredits::Table, ArtistCredits::SongId)
+                            .to(Songs::Table, Songs::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-artist_credits-album_id")
+                            .from(ArtistCredits::Table, ArtistCredits::AlbumId)
+                            .to(Albums::Table, Albums::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(index(ArtistCredits::Table, ArtistCredits::ArtistId))
+            .await?;
+        manager
+            .create_index(index(ArtistCredits::Table, ArtistCredits::SongId))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ArtistCredits::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum ArtistCredits {
+    Table,
+    Id,
+    ArtistId,
+    SongId,
+    AlbumId,
+    Role,
+    Position,
+}
+
+#[derive(Iden)]
+enum Artists {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Albums {
+    Table,
+    Id,
+}
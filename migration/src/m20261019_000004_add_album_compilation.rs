@@ -0,0 +1,29 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{add_columns, boolean, drop_columns};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Existing albums count as regular albums until they are scanned again
+        add_columns(
+            manager,
+            Albums::Table,
+            [boolean(Albums::Compilation).default(false).to_owned()],
+        )
+        .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(manager, Albums::Table, [Albums::Compilation]).await
+    }
+}
+
+#[derive(Iden)]
+enum Albums {
+    Table,
+    Compilation,
+}
@@ -0,0 +1,23 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{add_columns, drop_columns, integer_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        add_columns(manager, Songs::Table, [integer_null(Songs::DiscTotal)]).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(manager, Songs::Table, [Songs::DiscTotal]).await
+    }
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    DiscTotal,
+}
@@ -0,0 +1,91 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{add_columns, date_time, drop_columns, index, primary_id, text, text_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Libraries::Table)
+                    .if_not_exists()
+                    .col(&mut primary_id(Libraries::Id))
+                    .col(text(Libraries::Name).unique_key())
+                    .col(&mut text(Libraries::Path))
+                    .col(&mut text_null(Libraries::ScanSchedule))
+                    .col(&mut text_null(Libraries::Include))
+                    .col(&mut text_null(Libraries::Exclude))
+                    .col(&mut date_time(Libraries::CreatedAt))
+                    .col(&mut date_time(Libraries::UpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+        // Rows scanned before libraries existed are assigned when their library is added
+        add_columns(manager, Songs::Table, [text_null(Songs::LibraryId)]).await?;
+        add_columns(manager, Albums::Table, [text_null(Albums::LibraryId)]).await?;
+        add_columns(
+            manager,
+            Directories::Table,
+            [text_null(Directories::LibraryId)],
+        )
+        .await?;
+        manager
+            .create_index(index(Songs::Table, Songs::LibraryId))
+            .await?;
+        manager
+            .create_index(index(Albums::Table, Albums::LibraryId))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (table, name) in [
+            (Songs::Table.into_iden(), "idx-songs-library_id"),
+            (Albums::Table.into_iden(), "idx-albums-library_id"),
+        ] {
+            manager
+                .drop_index(Index::drop().if_exists().name(name).table(table).to_owned())
+                .await?;
+        }
+        drop_columns(manager, Songs::Table, [Songs::LibraryId]).await?;
+        drop_columns(manager, Albums::Table, [Albums::LibraryId]).await?;
+        drop_columns(manager, Directories::Table, [Directories::LibraryId]).await?;
+        manager
+            .drop_table(Table::drop().table(Libraries::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Libraries {
+    Table,
+    Id,
+    Name,
+    Path,
+    ScanSchedule,
+    Include,
+    Exclude,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    LibraryId,
+}
+
+#[derive(Iden)]
+enum Albums {
+    Table,
+    LibraryId,
+}
+
+#[derive(Iden)]
+enum Directories {
+    Table,
+    LibraryId,
+}
@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{date_time, date_time_null, index, integer, primary_id, text, text_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Tasks::Table)
+                    .if_not_exists()
+                    .col(&mut primary_id(Tasks::Id))
+                    .col(&mut text(Tasks::Kind))
+                    .col(&mut text_null(Tasks::LibraryId))
+                    .col(&mut text(Tasks::Status))
+                    .col(&mut text_null(Tasks::Error))
+                    .col(&mut integer(Tasks::DirectoriesWalked))
+                    .col(&mut integer(Tasks::SongsAdded))
+                    .col(&mut integer(Tasks::SongsUpdated))
+                    .col(&mut integer(Tasks::SongsRemoved))
+                    .col(&mut integer(Tasks::ArtistsUpdated))
+                    .col(&mut date_time(Tasks::CreatedAt))
+                    .col(&mut date_time_null(Tasks::StartedAt))
+                    .col(&mut date_time_null(Tasks::FinishedAt))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(index(Tasks::Table, Tasks::CreatedAt))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Tasks::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Tasks {
+    Table,
+    Id,
+    Kind,
+    LibraryId,
+    Status,
+    Error,
+    DirectoriesWalked,
+    SongsAdded,
+    SongsUpdated,
+    SongsRemoved,
+    ArtistsUpdated,
+    CreatedAt,
+    StartedAt,
+    FinishedAt,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{boolean, date_time, primary_id, text};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Usernames are made unique by idx-users-username
+        manager
+            .create_table(
+                Table::create()
+                    .table(Users::Table)
+                    .if_not_exists()
+                    .col(&mut primary_id(Users::Id))
+                    .col(&mut text(Users::Username))
+                    .col(&mut text(Users::PasswordHash))
+                    .col(&mut boolean(Users::Admin))
+                    .col(&mut date_time(Users::CreatedAt))
+                    .col(&mut date_time(Users::UpdatedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Users::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+    Username,
+    PasswordHash,
+    Admin,
+    CreatedAt,
+    UpdatedAt,
+}
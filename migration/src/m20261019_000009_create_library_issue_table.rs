@@ -0,0 +1,101 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{date_time, index, primary_id, text, text_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LibraryIssues::Table)
+                    .if_not_exists()
+                    .col(&mut primary_id(LibraryIssues::Id))
+                    .col(&mut text_null(LibraryIssues::LibraryId))
+                    .col(&mut text(LibraryIssues::Kind))
+                    .col(&mut text(LibraryIssues::Path))
+                    .col(&mut text_null(LibraryIssues::SongId))
+                    .col(&mut text_null(LibraryIssues::AlbumId))
+                    .col(&mut text(LibraryIssues::Message))
+                    .col(&mut date_time(LibraryIssues::CreatedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-library_issues-library_id")
+                            .from(LibraryIssues::Table, LibraryIssues::LibraryId)
+                            .to(Libraries::Table, Libraries::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-library_issues-song_id")
+                            .from(LibraryIssues::Table, LibraryIssues::SongId)
+                            .to(Songs::Table, Songs::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-library_issues-album_id")
+                            .from(LibraryIssues::Table, LibraryIssues::AlbumId)
+                            .to(Albums::Table, Albums::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(index(LibraryIssues::Table, LibraryIssues::Kind))
+            .await?;
+        manager
+            .create_index(index(LibraryIssues::Table, LibraryIssues::LibraryId))
+            .await?;
+        manager
+            .create_index(index(LibraryIssues::Table, LibraryIssues::SongId))
+            .await?;
+        manager
+            .create_index(index(LibraryIssues::Table, LibraryIssues::AlbumId))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LibraryIssues::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum LibraryIssues {
+    Table,
+    Id,
+    LibraryId,
+    Kind,
+    Path,
+    SongId,
+    AlbumId,
+    Message,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum Libraries {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Albums {
+    Table,
+    Id,
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{add_columns, drop_columns, index, text_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        add_columns(
+            manager,
+            Songs::Table,
+            [
+                text_null(Songs::MbRecordingId),
+                text_null(Songs::DuplicateOf),
+            ],
+        )
+        .await?;
+        add_columns(manager, Albums::Table, [text_null(Albums::DuplicateOf)]).await?;
+        manager
+            .create_index(index(Songs::Table, Songs::MbRecordingId))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .if_exists()
+                    .name("idx-songs-mb_recording_id")
+                    .table(Songs::Table)
+                    .to_owned(),
+            )
+            .await?;
+        drop_columns(manager, Albums::Table, [Albums::DuplicateOf]).await?;
+        drop_columns(
+            manager,
+            Songs::Table,
+            [Songs::DuplicateOf, Songs::MbRecordingId],
+        )
+        .await
+    }
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    MbRecordingId,
+    DuplicateOf,
+}
+
+#[derive(Iden)]
+enum Albums {
+    Table,
+    DuplicateOf,
+}
@@ -0,0 +1,33 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{add_columns, double_null, drop_columns};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const COLUMNS: [Songs; 4] = [
+    Songs::TrackGain,
+    Songs::TrackPeak,
+    Songs::AlbumGain,
+    Songs::AlbumPeak,
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        add_columns(manager, Songs::Table, COLUMNS.map(double_null)).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(manager, Songs::Table, COLUMNS.into_iter().rev()).await
+    }
+}
+
+#[derive(Iden, Clone, Copy)]
+enum Songs {
+    Table,
+    TrackGain,
+    TrackPeak,
+    AlbumGain,
+    AlbumPeak,
+}
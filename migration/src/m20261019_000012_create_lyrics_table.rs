@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{boolean, index, primary_id, text, text_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Lyrics::Table)
+                    .if_not_exists()
+                    .col(&mut primary_id(Lyrics::Id))
+                    .col(&mut text(Lyrics::SongId))
+                    .col(&mut text(Lyrics::Source))
+                    .col(&mut text_null(Lyrics::Path))
+                    .col(&mut text_null(Lyrics::Language))
+                    .col(&mut boolean(Lyrics::Synced))
+                    .col(&mut text(Lyrics::Content))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-lyrics-song_id")
+                            .from(Lyrics::Table, Lyrics::SongId)
+                            .to(Songs::Table, Songs::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(index(Lyrics::Table, Lyrics::SongId))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Lyrics::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Lyrics {
+    Table,
+    Id,
+    SongId,
+    Source,
+    Path,
+    Language,
+    Synced,
+    Content,
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    Id,
+}
@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{add_columns, big_integer_null, drop_columns, integer_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        add_columns(
+            manager,
+            Songs::Table,
+            [
+                big_integer_null(Songs::TotalSamples),
+                integer_null(Songs::EncoderDelay),
+                integer_null(Songs::EncoderPadding),
+                big_integer_null(Songs::DurationMs),
+            ],
+        )
+        .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(
+            manager,
+            Songs::Table,
+            [
+                Songs::DurationMs,
+                Songs::EncoderPadding,
+                Songs::EncoderDelay,
+                Songs::TotalSamples,
+            ],
+        )
+        .await
+    }
+}
+
+#[derive(Iden)]
+enum Songs {
+    Table,
+    TotalSamples,
+    EncoderDelay,
+    EncoderPadding,
+    DurationMs,
+}
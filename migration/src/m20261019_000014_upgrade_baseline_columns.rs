@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20220101_000001_create_table::{baseline_tables, BaselineTable};
+
+// Upgrades deaftone.sqlite files created from older entity definitions, whose tables were kept by the
+// baseline migration, by adding the baseline columns they lack
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in baseline_tables() {
+            upgrade_table(manager, &table).await?;
+        }
+        Ok(())
+    }
+
+    // One-way. Which columns were added isn't recorded and the baseline schema needs all of them, so rolling
+    // back keeps them. They are dropped along with the baseline tables
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
+
+// Older files may lack columns added before versioned migrations. Missing NOT NULL columns get an empty default
+// Timestamps default to the epoch since PostgreSQL rejects an empty timestamp
+async fn upgrade_table(manager: &SchemaManager<'_>, table: &BaselineTable) -> Result<(), DbErr> {
+    for column in &table.columns {
+        let name = column.get_column_name();
+        if manager.has_column(table.name.to_string(), &name).await? {
+            continue;
+        }
+        let Some(column_type) = column.get_column_type() else {
+            continue;
+        };
+        let mut added = ColumnDef::new_with_type(Alias::new(&name), column_type.clone());
+        if column
+            .get_column_spec()
+            .iter()
+            .any(|spec| matches!(spec, ColumnSpec::NotNull))
+        {
+            let default: SimpleExpr = match column_type {
+                ColumnType::Boolean => false.into(),
+                ColumnType::Integer | ColumnType::BigInteger => 0.into(),
+                ColumnType::DateTime => "1970-01-01 00:00:00".into(),
+                _ => "".into(),
+            };
+            added.not_null().default(default);
+        } else {
+            added.null();
+        }
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table.name.clone())
+                    .add_column(&mut added)
+                    .to_owned(),
+            )
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        // Tasks still queued when Deaftone stopped will never run and may hold duplicates. Fail them as on startup
+        db.execute_unprepared(
+            "UPDATE tasks SET status = 'failed', error = 'Interrupted by shutdown' WHERE status = 'queued'",
+        )
+        .await?;
+        // At most one queued task per kind and library so concurrent requests can't queue a task twice.
+        // Partial and expression indexes aren't supported by sea-query, both SQLite and PostgreSQL accept this
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX \"idx-tasks-queued\" ON tasks (kind, COALESCE(library_id, '')) WHERE status = 'queued'",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-tasks-queued")
+                    .table(Tasks::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Tasks {
+    Table,
+}
@@ -0,0 +1,354 @@
+use std::{
+    io::{self, BufRead},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use clap::{Args, Parser, Subcommand};
+use deaftone::{
+    database,
+    services::{
+        self, events::EventBus, http::error::ApiError, scanner::ScanType,
+        task::history::TaskProgress,
+    },
+};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::DatabaseConnection;
+
+#[derive(Parser)]
+#[command(version, about = "Deaftone music server")]
+pub struct Cli {
+    /// Path of the settings file. Defaults to ./settings.toml
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    // Starts the server when no command is given
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Scan the libraries in the foreground
+    Scan(ScanArgs),
+    /// Artist metadata commands
+    Metadata {
+        #[command(subcommand)]
+        command: MetadataCommand,
+    },
+    /// Database commands
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// User commands
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+    /// Playlist commands
+    Playlist {
+        #[command(subcommand)]
+        command: PlaylistCommand,
+    },
+    /// Write the OpenAPI document of the http api
+    ExportOpenapi {
+        #[arg(default_value = "./api_doc.json")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+pub struct ScanMode {
+    /// Rescan every directory
+    #[arg(long)]
+    full: bool,
+    /// Only check directories that changed or disappeared
+    #[arg(long)]
+    partial: bool,
+}
+
+#[derive(Args)]
+pub struct ScanArgs {
+    #[command(flatten)]
+    mode: ScanMode,
+    /// Only scan the library with this id
+    #[arg(long)]
+    library: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum MetadataCommand {
+    /// Fetch biographies and links for artists with a MusicBrainz id
+    Refresh,
+}
+
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// Apply pending migrations
+    Migrate,
+    /// Revert the most recently applied migrations
+    Rollback {
+        #[arg(long, default_value_t = 1)]
+        steps: u32,
+    },
+    /// Reclaim free pages and refresh query planner statistics
+    Vacuum,
+    /// Run the integrity and foreign key checks
+    Check,
+    /// Write a consistent copy of the SQLite database while the server keeps running
+    Backup { file: PathBuf },
+    /// Export likes, playlists and play history as JSON keyed by MusicBrainz track id and path
+    Export { file: PathBuf },
+    /// Import a JSON export onto the scanned library. Songs that can't be matched are listed
+    Import { file: PathBuf },
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// Create a user. Prompts for the password without echoing it
+    Add {
+        username: String,
+        /// Read the password from the first line of stdin instead of prompting for it
+        #[arg(long)]
+        password_stdin: bool,
+        #[arg(long)]
+        admin: bool,
+    },
+    /// Change the password of a user
+    Passwd {
+        username: String,
+        /// Read the password from the first line of stdin instead of prompting for it
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// List users
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum PlaylistCommand {
+    /// Create a playlist from an M3U file. Entries are matched to scanned songs by path
+    Import {
+        file: PathBuf,
+        /// Name of the playlist. Defaults to the file name
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+// Commands reuse the service functions and never start the http server
+pub async fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Scan(args) => scan(args).await,
+        Command::Metadata {
+            command: MetadataCommand::Refresh,
+        } => {
+            let db = connect().await?;
+            let settings = services::settings::SettingsService::load(&db)
+                .await?
+                .current();
+            run_with_progress(|progress| async move {
+                services::metadata::scrap_metadata(&db, &settings, &progress).await
+            })
+            .await
+        }
+        Command::Db { command } => db(command).await,
+        Command::User { command } => user(command).await,
+        Command::Playlist {
+            command: PlaylistCommand::Import { file, name },
+        } => {
+            let db = database::connect_to_db().await?;
+            let import =
+                services::playlist::import_playlist(&db, &EventBus::new(), &file, name.as_deref())
+                    .await?;
+            println!(
+                "Imported playlist {} with {} songs",
+                import.playlist_id, import.added
+            );
+            for path in import.missing {
+                println!("Missing song: {}", path);
+            }
+            Ok(())
+        }
+        Command::ExportOpenapi { output } => {
+            std::fs::write(&output, deaftone::openapi::api_doc())?;
+            println!("Successfully wrote {}", output.display());
+            Ok(())
+        }
+    }
+}
+
+// Runs migrations and syncs the libraries from the settings file
+async fn connect() -> Result<DatabaseConnection> {
+    let db = database::connect_to_db().await?;
+    services::library::sync_settings_libraries(&db).await?;
+    Ok(db)
+}
+
+async fn scan(args: ScanArgs) -> Result<()> {
+    let db = connect().await?;
+    let scan_type = match args.mode.full {
+        true => ScanType::FullScan,
+        false => ScanType::PartialScan,
+    };
+    run_with_progress(|progress| async move {
+        services::scanner::start_scan(scan_type, args.library, &db, &progress).await
+    })
+    .await
+}
+
+// Prints the progress counters every second until the job finishes
+async fn run_with_progress<F, Fut>(job: F) -> Result<()>
+where
+    F: FnOnce(Arc<TaskProgress>) -> Fut,
+    Fut: std::future::Future<Output = Result<()>>,
+{
+    let progress = Arc::new(TaskProgress::default());
+    let printer = {
+        let progress = progress.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                ticker.tick().await;
+                print_progress(&progress);
+            }
+        })
+    };
+    let result = job(progress.clone()).await;
+    printer.abort();
+    print_progress(&progress);
+    result
+}
+
+fn print_progress(progress: &TaskProgress) {
+    let counters = progress.counters();
+    println!(
+        "Directories: {} | Songs added: {} updated: {} removed: {} | Artists updated: {}",
+        counters.directories_walked,
+        counters.songs_added,
+        counters.songs_updated,
+        counters.songs_removed,
+        counters.artists_updated
+    );
+}
+
+async fn db(command: DbCommand) -> Result<()> {
+    match command {
+        DbCommand::Migrate => {
+            let db = database::open_db().await?;
+            let pending = Migrator::get_pending_migrations(&db).await?;
+            database::migrate_up(&db).await?;
+            println!("Applied {} migrations", pending.len());
+        }
+        DbCommand::Rollback { steps } => {
+            let db = database::open_db().await?;
+            database::migrate_down(&db, steps).await?;
+            println!("Reverted {} migrations", steps);
+        }
+        DbCommand::Vacuum => {
+            database::maintenance(&connect().await?).await?;
+            println!("Database vacuumed");
+        }
+        DbCommand::Check => {
+            let problems = database::check(&connect().await?).await?;
+            if !problems.is_empty() {
+                for problem in &problems {
+                    println!("{}", problem);
+                }
+                bail!("Database check found {} problems", problems.len());
+            }
+            println!("Database is ok");
+        }
+        DbCommand::Backup { file } => {
+            services::backup::backup_sqlite(&connect().await?, &file)
+                .await
+                .map_err(api_error)?;
+            println!("Backed up database to {}", file.display());
+        }
+        DbCommand::Export { file } => {
+            let export = services::backup::export_user_state(&connect().await?)
+                .await
+                .map_err(api_error)?;
+            std::fs::write(&file, serde_json::to_vec_pretty(&export)?)?;
+            println!(
+                "Exported {} likes, {} playlists and {} plays to {}",
+                export.likes.len(),
+                export.playlists.len(),
+                export.plays.len(),
+                file.display()
+            );
+        }
+        DbCommand::Import { file } => {
+            let export = serde_json::from_slice(&std::fs::read(&file)?)?;
+            let report =
+                services::backup::import_user_state(&connect().await?, &EventBus::new(), export)
+                    .await
+                    .map_err(api_error)?;
+            println!(
+                "Imported {} likes, {} playlists with {} songs and {} plays",
+                report.likes, report.playlists, report.playlist_songs, report.plays
+            );
+            for song in report.missing {
+                println!("Missing song: {}", song.path);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn user(command: UserCommand) -> Result<()> {
+    let db = database::connect_to_db().await?;
+    match command {
+        UserCommand::Add {
+            username,
+            password_stdin,
+            admin,
+        } => {
+            let password = read_password(password_stdin)?;
+            let user = services::user::create_user(&db, &username, &password, admin)
+                .await
+                .map_err(api_error)?;
+            println!("Created user {} ({})", user.username, user.id);
+        }
+        UserCommand::Passwd {
+            username,
+            password_stdin,
+        } => {
+            let password = read_password(password_stdin)?;
+            let user = services::user::set_password(&db, &username, &password)
+                .await
+                .map_err(api_error)?;
+            println!("Changed password of {}", user.username);
+        }
+        UserCommand::List => {
+            for user in services::user::get_users(&db).await.map_err(api_error)? {
+                println!(
+                    "{}\t{}\t{}",
+                    user.id,
+                    user.username,
+                    match user.admin {
+                        true => "admin",
+                        false => "user",
+                    }
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_password(from_stdin: bool) -> Result<String> {
+    if !from_stdin {
+        return Ok(rpassword::prompt_password("Password: ")?);
+    }
+    let mut password = String::new();
+    io::stdin().lock().read_line(&mut password)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+fn api_error(err: ApiError) -> anyhow::Error {
+    err.1
+}
@@ -1,56 +1,167 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 
 use migration::{Migrator, MigratorTrait};
-use sea_orm::{ConnectOptions, DatabaseConnection};
+use sea_orm::{
+    ConnectionTrait, DatabaseBackend, DatabaseConnection, SqlxPostgresConnector,
+    SqlxSqliteConnector, Statement,
+};
 use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
     sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
-    ConnectOptions as SqlxConnectOptions, Pool, Sqlite,
+    ConnectOptions,
 };
-use std::{fs, str::FromStr, time::Duration};
+use std::{str::FromStr, time::Duration};
 
 use crate::SETTINGS;
 
-// Connect to sea-orm DatabaseConnection using SETTINGS.db_path as the database to connect too
+// How long a connection waits on a locked database before failing with SQLITE_BUSY
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(8);
+
+// Connect to the configured database and run pending migrations. Every service shares the returned connection
 pub async fn connect_to_db() -> Result<DatabaseConnection, anyhow::Error> {
-    let db_path = SETTINGS.db_path.as_str();
-    if fs::metadata(db_path).is_err() {
-        fs::File::create(db_path).map_err(|e| anyhow!("Error creating file: {}", e))?;
-    }
-    let mut opt: ConnectOptions = ConnectOptions::new(format!("sqlite://{db_path}?mode=rwc"));
-    opt.max_connections(100)
-        .min_connections(5)
-        .connect_timeout(Duration::from_secs(8))
-        .idle_timeout(Duration::from_secs(8))
-        .max_lifetime(Duration::from_secs(8))
-        .sqlx_logging(false);
-
-    let pool: DatabaseConnection = sea_orm::Database::connect(opt).await?;
+    let pool = open_db().await?;
     migrate_up(&pool).await?;
     Ok(pool)
 }
-// Returns a sqlx database connection
-pub async fn connect_db_sqlx() -> Result<Pool<Sqlite>, sqlx::Error> {
-    let database_file = "deaftone.sqlite";
-    let database_url = format!("sqlite://{database_file}");
-    let pool_timeout = Duration::from_secs(30);
-    let connection_options = SqliteConnectOptions::from_str(&database_url)
-        .unwrap()
+
+// Same as connect_to_db without running migrations
+pub async fn open_db() -> Result<DatabaseConnection, anyhow::Error> {
+    connect_url(
+        &SETTINGS.db_url(),
+        SETTINGS.db_min_connections,
+        SETTINGS.db_max_connections,
+    )
+    .await
+}
+
+// Opens a pool for a sqlite: or postgres:// url
+pub async fn connect_url(
+    url: &str,
+    min_connections: u32,
+    max_connections: u32,
+) -> Result<DatabaseConnection, anyhow::Error> {
+    if url.starts_with("sqlite:") {
+        connect_sqlite(connect_options(url)?, min_connections, max_connections).await
+    } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        let options = PgConnectOptions::from_str(url)
+            .map_err(|err| anyhow!("Invalid database url: {}", err))?;
+        connect_postgres(options, min_connections, max_connections).await
+    } else {
+        bail!("Unsupported database url. Expected sqlite: or postgres://")
+    }
+}
+
+// Options for every SQLite connection in the pool. Pragmas are applied once when a connection is opened
+pub fn connect_options(url: &str) -> Result<SqliteConnectOptions, anyhow::Error> {
+    Ok(SqliteConnectOptions::from_str(url)
+        .map_err(|err| anyhow!("Invalid database url {}: {}", url, err))?
         .create_if_missing(true)
         .journal_mode(SqliteJournalMode::Wal)
         .synchronous(SqliteSynchronous::Normal)
-        .foreign_keys(false)
-        .busy_timeout(pool_timeout)
-        .disable_statement_logging()
-        .clone();
-
-    SqlitePoolOptions::new()
-        .min_connections(5)
-        .max_connections(10)
-        .connect_with(connection_options)
+        .foreign_keys(true)
+        .busy_timeout(BUSY_TIMEOUT)
+        .pragma("temp_store", "memory")
+        .pragma("mmap_size", "30000000000")
+        .pragma("page_size", "4096")
+        .disable_statement_logging())
+}
+
+// Opens the SQLite pool and wraps it in a sea-orm connection
+pub async fn connect_sqlite(
+    options: SqliteConnectOptions,
+    min_connections: u32,
+    max_connections: u32,
+) -> Result<DatabaseConnection, anyhow::Error> {
+    let pool = SqlitePoolOptions::new()
+        .min_connections(min_connections)
+        .max_connections(max_connections)
+        .acquire_timeout(ACQUIRE_TIMEOUT)
+        .connect_with(options)
+        .await
+        .map_err(|err| anyhow!("Failed to open database: {}", err))?;
+    Ok(SqlxSqliteConnector::from_sqlx_sqlite_pool(pool))
+}
+
+// Opens the PostgreSQL pool and wraps it in a sea-orm connection. Foreign keys are always enforced by PostgreSQL
+pub async fn connect_postgres(
+    options: PgConnectOptions,
+    min_connections: u32,
+    max_connections: u32,
+) -> Result<DatabaseConnection, anyhow::Error> {
+    let pool = PgPoolOptions::new()
+        .min_connections(min_connections)
+        .max_connections(max_connections)
+        .acquire_timeout(ACQUIRE_TIMEOUT)
+        .connect_with(
+            options
+                .application_name("deaftone")
+                .disable_statement_logging(),
+        )
+        .await
+        .map_err(|err| anyhow!("Failed to open database: {}", err))?;
+    Ok(SqlxPostgresConnector::from_sqlx_postgres_pool(pool))
+}
+
+// Applies pending migrations in order. Errors are returned so Deaftone never starts on a half migrated schema
+pub async fn migrate_up(pool: &DatabaseConnection) -> Result<(), anyhow::Error> {
+    Migrator::up(pool, None)
+        .await
+        .map_err(|err| anyhow!("Database migration failed: {}", err))
+}
+
+// Reverts the most recently applied migrations
+pub async fn migrate_down(pool: &DatabaseConnection, steps: u32) -> Result<(), anyhow::Error> {
+    Migrator::down(pool, Some(steps))
         .await
+        .map_err(|err| anyhow!("Database rollback failed: {}", err))
 }
 
-async fn migrate_up(pool: &DatabaseConnection) -> Result<(), anyhow::Error> {
-    Migrator::up(pool, None).await?;
+// Reclaims free pages and refreshes the query planner statistics
+pub async fn maintenance(db: &DatabaseConnection) -> Result<(), anyhow::Error> {
+    tracing::info!("Running database maintenance");
+    match db.get_database_backend() {
+        DatabaseBackend::Postgres => {
+            db.execute_unprepared("VACUUM ANALYZE").await?;
+        }
+        _ => {
+            db.execute_unprepared("VACUUM").await?;
+            db.execute_unprepared("PRAGMA optimize").await?;
+        }
+    }
     Ok(())
 }
+
+// Runs the SQLite integrity and foreign key checks. Returns the problems found
+// PostgreSQL enforces foreign keys on every write so there is nothing to check
+pub async fn check(db: &DatabaseConnection) -> Result<Vec<String>, anyhow::Error> {
+    let backend = db.get_database_backend();
+    if backend == DatabaseBackend::Postgres {
+        return Ok(Vec::new());
+    }
+    let mut problems = Vec::new();
+    for row in db
+        .query_all(Statement::from_string(backend, "PRAGMA integrity_check"))
+        .await?
+    {
+        let result: String = row.try_get_by_index(0)?;
+        if result != "ok" {
+            problems.push(result);
+        }
+    }
+    for row in db
+        .query_all(Statement::from_string(backend, "PRAGMA foreign_key_check"))
+        .await?
+    {
+        let table: String = row.try_get_by_index(0)?;
+        let rowid: Option<i64> = row.try_get_by_index(1)?;
+        let parent: String = row.try_get_by_index(2)?;
+        problems.push(format!(
+            "Row {} in {} references a missing row in {}",
+            rowid.unwrap_or_default(),
+            table,
+            parent
+        ));
+    }
+    Ok(problems)
+}
@@ -1,4 +1,8 @@
+// Lets the OpenApi derive in openapi.rs refer to handlers as deaftone::services::... like the former gen-openapi binary
+extern crate self as deaftone;
+
 pub mod database;
+pub mod openapi;
 pub mod services;
 pub mod settings;
 pub mod test_util;
@@ -16,6 +20,8 @@ use crate::settings::Settings;
 pub struct AppState {
     pub database: DatabaseConnection,
     pub services: DeaftoneService,
+    // Token required by the /admin endpoints. They are disabled when None
+    pub admin_token: Option<String>,
 }
 // Inject global settings static ref
 lazy_static! {
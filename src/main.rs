@@ -1,17 +1,29 @@
 use anyhow::Result;
+use clap::Parser;
 use core::panic;
 use deaftone::{
     services::{
         casting::{device::DeviceService, CHROMECAST_SERVICE_NAME},
-        task::TaskType,
+        events::EventBus,
+        scheduler::Scheduler,
+        settings::SettingsService,
+        task::{history, TaskCommand},
         DeaftoneService,
     },
     AppState, SETTINGS,
 };
+use std::time::Duration;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod cli;
+
+// How long running tasks get to stop after the server has shut down
+const TASK_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = cli::Cli::parse();
+    deaftone::settings::init(cli.config.clone())?;
     // Setup tracing logger
     let (non_blocking, _guard) = tracing_appender::non_blocking(std::io::stdout());
     tracing_subscriber::registry()
@@ -20,6 +32,9 @@ async fn main() -> Result<()> {
         ))
         .with(tracing_subscriber::fmt::layer().with_writer(non_blocking))
         .init();
+    if let Some(command) = cli.command {
+        return cli::run(command).await;
+    }
     tracing::info!(
         "
 ██████╗ ███████╗ █████╗ ███████╗████████╗ ██████╗ ███╗   ██╗███████╗
@@ -28,40 +43,56 @@ async fn main() -> Result<()> {
 ██║  ██║██╔══╝  ██╔══██║██╔══╝     ██║   ██║   ██║██║╚██╗██║██╔══╝  
 ██████╔╝███████╗██║  ██║██║        ██║   ╚██████╔╝██║ ╚████║███████╗
 ╚═════╝ ╚══════╝╚═╝  ╚═╝╚═╝        ╚═╝    ╚═════╝ ╚═╝  ╚═══╝╚══════╝
-Version: {:} | Media Directory: {:} | Database: {:}",
+Version: {:} | Media Directory: {:} | Database: {:} | Listening on: {:}",
         env!("CARGO_PKG_VERSION"),
         SETTINGS.media_path.as_str(),
-        SETTINGS.db_path.as_str()
+        SETTINGS.db_path.as_str(),
+        SETTINGS.listen_addr.as_str()
     );
 
     let database = deaftone::database::connect_to_db().await?;
+    deaftone::services::library::sync_settings_libraries(&database).await?;
+    let interrupted = history::fail_interrupted(&database).await?;
+    if interrupted > 0 {
+        tracing::warn!("Marked {:} interrupted tasks as failed", interrupted);
+    }
 
     // Create task service with channel length of 10
     let (tasks_send, tasks_receiver) =
-        tokio::sync::mpsc::channel::<deaftone::services::task::TaskType>(10);
+        tokio::sync::mpsc::channel::<deaftone::services::task::TaskCommand>(10);
 
+    let events = EventBus::new();
+    let settings = SettingsService::load(&database).await?;
     let services = DeaftoneService {
         device: DeviceService::new(database.clone()),
         task: tasks_send.clone(),
+        events: events.clone(),
+        settings: settings.clone(),
     };
+    // Every service shares the connection pool held by the app state
+    let mut task_service = deaftone::services::task::TaskService::new(
+        tasks_receiver,
+        &database,
+        events.clone(),
+        settings.subscribe(),
+    );
+    let scheduler = Scheduler::new(tasks_send.clone(), database.clone(), settings.subscribe());
+    let mdns = deaftone::services::casting::Mdns::new(CHROMECAST_SERVICE_NAME, &database, events);
     // Build app state
-    let state = AppState { database, services };
+    let state = AppState {
+        database,
+        services,
+        admin_token: SETTINGS.admin_token.clone(),
+    };
+
+    // Spawn task service. Awaited on shutdown so running tasks get to stop
+    let task_handle = tokio::spawn(async move { task_service.run().await });
 
-    // Spawn task service
-    std::mem::drop(tokio::spawn(async move {
-        deaftone::services::task::TaskService::new(tasks_receiver)
-            .run()
-            .await
-    }));
+    // Spawn scheduler which enqueues scheduled tasks onto the task service
+    std::mem::drop(tokio::spawn(async move { scheduler.run().await }));
 
     // Spawn casting service
-    std::mem::drop(tokio::spawn(async move {
-        deaftone::services::casting::Mdns::new(CHROMECAST_SERVICE_NAME)
-            .await
-            .expect("Failed to start casting service")
-            .discover()
-            .await
-    }));
+    std::mem::drop(tokio::spawn(async move { mdns.discover().await }));
 
     // Spawn http service
     std::mem::drop(
@@ -69,15 +100,23 @@ Version: {:} | Media Directory: {:} | Database: {:}",
     );
 
     // Send shutdown signal to tasks service
-    match tasks_send.send(TaskType::Shutdown).await {
+    match tasks_send.send(TaskCommand::Shutdown).await {
         Ok(_e) => {
-            tracing::info!("Shutting down TaskService. Please wait for task queue completion")
+            tracing::info!("Shutting down TaskService. Please wait for running tasks to stop")
         }
         Err(err) => {
             tracing::error!("Failed to send shutdown command to TaskService {:}", err);
             panic!("Failed to shutdown TaskService")
         }
     };
+    match tokio::time::timeout(TASK_SHUTDOWN_TIMEOUT, task_handle).await {
+        Ok(Ok(())) => tracing::info!("TaskService stopped"),
+        Ok(Err(err)) => tracing::error!("TaskService failed: {:}", err),
+        Err(_) => tracing::warn!(
+            "Tasks didn't stop within {:?}. They are marked as interrupted on the next start",
+            TASK_SHUTDOWN_TIMEOUT
+        ),
+    }
 
     tracing::info!("Goodbye!");
     Ok(())
@@ -0,0 +1,189 @@
+use utoipa::OpenApi;
+
+// Returns the OpenAPI document of the http api as pretty printed JSON. Written to api_doc.json by deaftone export-openapi
+pub fn api_doc() -> std::string::String {
+    #[derive(OpenApi)]
+    #[openapi(
+        info(description = "## About 
+This api is inspired by https://auraspec.readthedocs.io/en/latest/api.html
+### Response Format and Errors
+
+The MIME type for all responses MUST be ``application/vnd.api+json``. Every response is a JSON object. When a request is successful, the document has a top-level key data corresponding to the response’s “primary data.” When it fails, the document has an errors key, which maps to an array of JSON API error objects. Other keys may also be present, as described below.
+
+On a successfuly query
+```
+{
+    status: {success}
+    data: {Response}
+}
+```
+On a failure
+```
+{
+    status: {error},
+    message: {String},
+}
+```
+"),
+        paths(
+            deaftone::services::http::handlers::playlist::get_playlist,
+            deaftone::services::http::handlers::albums::get_albums,
+            deaftone::services::http::handlers::albums::get_album,
+            deaftone::services::http::handlers::albums::get_cover,
+            deaftone::services::http::handlers::artists::get_artists,
+            deaftone::services::http::handlers::artists::get_artist,
+            deaftone::services::http::handlers::genres::get_genres,
+            deaftone::services::http::handlers::genres::get_genre_albums,
+            deaftone::services::http::handlers::genres::get_genre_songs,
+            deaftone::services::http::handlers::libraries::get_libraries,
+            deaftone::services::http::handlers::libraries::create_library,
+            deaftone::services::http::handlers::libraries::get_library,
+            deaftone::services::http::handlers::libraries::update_library,
+            deaftone::services::http::handlers::libraries::delete_library,
+            deaftone::services::http::handlers::songs::get_song,
+            deaftone::services::http::handlers::songs::scrobble_song,
+            deaftone::services::http::handlers::songs::get_song_lyrics,
+            deaftone::services::http::handlers::streams::stream_handler,
+            deaftone::services::http::handlers::streams::download_song,
+            deaftone::services::http::handlers::streams::download_album,
+            deaftone::services::http::handlers::streams::hls_master_playlist,
+            deaftone::services::http::handlers::streams::hls_media_playlist,
+            deaftone::services::http::handlers::streams::hls_segment,
+            deaftone::services::http::handlers::stats::get_top_artists,
+            deaftone::services::http::handlers::stats::get_top_albums,
+            deaftone::services::http::handlers::stats::get_top_songs,
+            deaftone::services::http::handlers::stats::get_top_genres,
+            deaftone::services::http::handlers::stats::get_listening_time,
+            deaftone::services::http::handlers::stats::get_library_growth,
+            deaftone::services::http::handlers::stats::get_format_breakdown,
+            deaftone::services::http::handlers::admin::get_backup,
+            deaftone::services::http::handlers::admin::get_export,
+            deaftone::services::http::handlers::admin::import_user_state,
+            deaftone::services::http::handlers::admin::get_issues,
+            deaftone::services::http::handlers::admin::get_duplicates,
+            deaftone::services::http::handlers::events::events,
+            deaftone::services::http::handlers::tasks::handle_task,
+            deaftone::services::http::handlers::tasks::get_tasks,
+            deaftone::services::http::handlers::tasks::get_task,
+            deaftone::services::http::handlers::tasks::cancel_task,
+            deaftone::services::http::handlers::settings::get_settings,
+            deaftone::services::http::handlers::settings::get_runtime_settings,
+            deaftone::services::http::handlers::settings::update_runtime_settings,
+        ),
+        components(
+            schemas(
+                deaftone::services::http::handlers::PlayListResponse,
+                deaftone::services::http::ArtistResponseOpenApi,
+                deaftone::services::http::ArtistsResponseOpenApi,
+                deaftone::services::http::AlbumResponseOpenApi,
+                deaftone::services::http::AlbumsResponseOpenApi,
+                deaftone::services::http::error::ErrorResponse,
+                deaftone::services::http::error::Status,
+                deaftone::services::http::handlers::GetAllAlbums,
+                deaftone::services::http::handlers::AlbumResponse,
+                deaftone::services::http::handlers::AlbumDisc,
+                deaftone::services::http::handlers::ArtistResponse,
+                deaftone::services::http::handlers::SongResponse,
+                deaftone::services::http::handlers::GetAllArtists,
+                deaftone::services::http::handlers::ArtistLinks,
+                deaftone::services::http::handlers::StatsQuery,
+                deaftone::services::http::handlers::GetAllGenres,
+                deaftone::services::http::GenresResponseOpenApi,
+                deaftone::services::http::SongsResponseOpenApi,
+                entity::genre::Model,
+                deaftone::services::http::handlers::LibraryQuery,
+                deaftone::services::http::handlers::LibraryRequest,
+                deaftone::services::http::LibrariesResponseOpenApi,
+                deaftone::services::http::LibraryResponseOpenApi,
+                deaftone::services::http::MessageResponseOpenApi,
+                entity::library::Model,
+                deaftone::services::http::TopItemsResponseOpenApi,
+                deaftone::services::http::ListeningTimeResponseOpenApi,
+                deaftone::services::http::LibraryGrowthResponseOpenApi,
+                deaftone::services::http::FormatBreakdownResponseOpenApi,
+                deaftone::services::stats::TopItem,
+                deaftone::services::stats::ListeningTime,
+                deaftone::services::stats::LibraryGrowth,
+                deaftone::services::stats::FormatBreakdown,
+                deaftone::services::http::ImportReportResponseOpenApi,
+                deaftone::services::backup::SongRef,
+                deaftone::services::backup::ExportedPlaylist,
+                deaftone::services::backup::ExportedPlay,
+                deaftone::services::backup::UserStateExport,
+                deaftone::services::backup::ImportReport,
+                deaftone::services::http::handlers::IssuesQuery,
+                deaftone::services::http::IssuesResponseOpenApi,
+                deaftone::services::issues::IssuesResponse,
+                deaftone::services::issues::IssueCount,
+                deaftone::services::issues::IssueKind,
+                entity::library_issue::Model,
+                deaftone::services::http::handlers::DuplicatesQuery,
+                deaftone::services::http::handlers::AlbumQuery,
+                deaftone::services::http::DuplicatesResponseOpenApi,
+                deaftone::services::duplicates::DuplicateGroup,
+                deaftone::services::duplicates::DuplicateSong,
+                deaftone::services::duplicates::MatchedBy,
+                deaftone::services::http::handlers::LyricsQuery,
+                deaftone::services::http::LyricsResponseOpenApi,
+                deaftone::services::lyrics::Lyrics,
+                deaftone::services::lyrics::LyricLine,
+                deaftone::services::http::handlers::EventsQuery,
+                deaftone::services::events::Event,
+                deaftone::services::events::ItemKind,
+                deaftone::services::events::ScanCounters,
+                deaftone::services::http::handlers::TaskQuery,
+                deaftone::services::http::handlers::TaskResponse,
+                deaftone::services::http::handlers::GetTasks,
+                deaftone::services::http::TasksResponseOpenApi,
+                deaftone::services::http::TaskModelResponseOpenApi,
+                entity::task::Model,
+                deaftone::services::http::SettingsResponseOpenApi,
+                deaftone::settings::Settings,
+                deaftone::settings::LibrarySettings,
+                deaftone::settings::ScheduleSettings,
+                deaftone::services::http::RuntimeSettingsResponseOpenApi,
+                deaftone::services::settings::RuntimeSettings,
+                deaftone::services::settings::TranscodingProfile,
+                deaftone::services::settings::ReplayGainMode,
+                entity::play::Model,
+                entity::album::Model,
+                entity::song::Model,
+                entity::artist::Model,
+            )
+        ),
+        tags(
+            (name = "deaftone::services::http::handlers::albums", description = "Deaftone Albums API"),
+            (name = "deaftone::services::http::handlers::artists", description = "Deaftone Artists API"),
+            (name = "deaftone::services::http::handlers::genres", description = "Deaftone Genres API"),
+            (name = "deaftone::services::http::handlers::libraries", description = "Deaftone Libraries API"),
+            (name = "deaftone::services::http::handlers::playlists", description = "Deaftone Playlists API"),
+            (name = "deaftone::services::http::handlers::stats", description = "Deaftone Statistics API"),
+            (name = "deaftone::services::http::handlers::admin", description = "Deaftone Backup and Restore API"),
+            (name = "deaftone::services::http::handlers::tasks", description = "Deaftone Tasks API"),
+            (name = "deaftone::services::http::handlers::events", description = "Deaftone Events API"),
+            (name = "deaftone::services::http::handlers::settings", description = "Deaftone Settings API")
+
+            //(name = "deaftone", description = "Deaftone API")
+        )
+    )]
+    /*     #[openapi(
+        paths(
+            get_albums,
+            get_album,
+        ),
+        components(
+            schemas(
+                deaftone::handlers::albums::GetAllAlbumsQuery,
+                deaftone::handlers::albums::AlbumResponse,
+                entity::album::Model as AlbumModel,
+                deaftone::handlers::artists::GetArtistsQuery,
+                entity::artist::Model as ArtistModel,
+            )
+        ),
+        tags(
+            (name = "Album Api", description = "Deaftone API")
+        )
+    )] */
+    struct ApiDoc;
+    ApiDoc::openapi().to_pretty_json().unwrap()
+}
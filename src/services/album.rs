@@ -1,93 +1,154 @@
-use crate::services::scanner::tag_helper::AudioMetadata;
+use crate::services::{http::handlers::AlbumDisc, scanner::tag_helper::AudioMetadata};
 use anyhow::anyhow;
 use chrono::Utc;
 use hyper::StatusCode;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set,
 };
-use sea_orm::{PaginatorTrait, QuerySelect};
-use sqlx::{Sqlite, Transaction};
+use sea_orm::{PaginatorTrait, QuerySelect, QueryTrait, Select};
+use std::{collections::HashSet, path::Path};
 use uuid::Uuid;
 
 use super::http::error::ApiError;
 
 // Creates a album entry with belonging to provided artist_id
 pub async fn create_album(
-    tx: &mut Transaction<'_, Sqlite>,
+    db: &impl ConnectionTrait,
     cover: Option<String>,
-    artist_id: &String,
+    artist_id: &str,
+    library_id: &str,
     metadata: &AudioMetadata,
 ) -> Result<String, anyhow::Error> {
-    let id: String = Uuid::new_v4().to_string();
-    let init_time: String = Utc::now().naive_local().to_string();
-    sqlx::query(
-        "INSERT OR REPLACE INTO albums (
-            id,
-            path,
-            name,
-            album_artist,
-            album_artist_sort,
-            album_artist_credit,
-            discogs_albumid,
-            discogs_artistid,
-            discogs_labelid,
-            year,
-            composer,
-            mb_album_id,
-            mb_artist_id,
-            album_type,
-            label,
-            mb_releasegroup_id,
-            asin,
-            catalog_num,
-            script,
-            country,
-            album_disambig,
-            release_group_disambig,
-            artist_name,
-            cover,
-            created_at,
-            updated_at,
-            artist_id
-         )
-    VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)",
-    )
-    .bind(&id)
-    .bind(&metadata.path)
-    .bind(&metadata.album_name)
-    .bind(&metadata.album_artist)
-    .bind(&metadata.album_sort) // ALBUM_ARTIST_SORT
-    .bind(&metadata.artist) // ARTIST CREDIT
-    /*     .bind(&metadata.genre) // ARTIST CREDIT
-    .bind(&metadata.style) // ARTIST CREDIT */
-    .bind(&metadata.discogs_albumid)
-    .bind(&metadata.discogs_artistid)
-    .bind(&metadata.discogs_labelid)
-    .bind(metadata.year)
-    .bind(&metadata.composer)
-    .bind(&metadata.mb_album_id)
-    .bind(&metadata.mb_artist_id)
-    .bind(&metadata.album_type)
-    .bind(&metadata.label)
-    .bind(&metadata.mb_releasegroup_id)
-    .bind(&metadata.asin)
-    .bind(&metadata.catalog_num)
-    .bind(&metadata.script)
-    /*     .bind(&metadata.language)
-     */
-    .bind(&metadata.country)
-    /*     .bind(&metadata.album_status)
-     */
-    .bind(&metadata.album_disambig)
-    .bind(&metadata.release_group_disambig)
-    .bind(&metadata.artist)
-    .bind(cover)
-    .bind(&init_time)
-    .bind(&init_time)
-    .bind(artist_id)
-    .execute(&mut **tx)
-    .await?;
-    Ok(id)
+    let init_time = Utc::now().naive_local();
+    let album = entity::album::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        path: Set(metadata.path.clone()),
+        name: Set(metadata.album_name.clone()),
+        album_artist: Set(Some(metadata.album_artist.clone())),
+        album_artist_sort: Set(metadata.album_sort.clone()),
+        album_artist_credit: Set(Some(metadata.artist.clone())),
+        genre: Set(metadata.genre.as_ref().map(|g| g.join("; "))),
+        style: Set(metadata.style.as_ref().map(|s| s.join("; "))),
+        discogs_albumid: Set(metadata.discogs_albumid.clone()),
+        discogs_artistid: Set(metadata.discogs_artistid.clone()),
+        discogs_labelid: Set(metadata.discogs_labelid.clone()),
+        year: Set(metadata.year),
+        composer: Set(metadata.composer.clone()),
+        mb_album_id: Set(metadata.mb_album_id.clone()),
+        mb_artist_id: Set(metadata.mb_artist_id.clone()),
+        album_type: Set(metadata.album_type.clone()),
+        label: Set(metadata.label.clone()),
+        mb_releasegroup_id: Set(metadata.mb_releasegroup_id.clone()),
+        asin: Set(metadata.asin.clone()),
+        catalog_num: Set(metadata.catalog_num.clone()),
+        script: Set(metadata.script.clone()),
+        language: Set(None),
+        country: Set(metadata.country.clone()),
+        album_status: Set(None),
+        album_disambig: Set(metadata.album_disambig.clone()),
+        release_group_disambig: Set(metadata.release_group_disambig.clone()),
+        artist_name: Set(metadata.album_artist.clone()),
+        cover: Set(cover),
+        created_at: Set(init_time),
+        updated_at: Set(init_time),
+        artist_id: Set(Some(artist_id.to_owned())),
+        compilation: Set(metadata.compilation),
+        library_id: Set(Some(library_id.to_owned())),
+        duplicate_of: Set(None),
+    };
+    Ok(album.insert(db).await?.id)
+}
+
+// Returns the id of the existing album the metadata belongs to. Albums are matched on MusicBrainzAlbumId when tagged
+// Otherwise on album artist, name and directory so same named albums by different artists stay apart
+pub async fn find_album(
+    db: &impl ConnectionTrait,
+    artist_id: &str,
+    library_id: &str,
+    metadata: &AudioMetadata,
+) -> Result<Option<String>, anyhow::Error> {
+    if let Some(mb_album_id) = &metadata.mb_album_id {
+        let existing: Option<String> = entity::album::Entity::find()
+            .select_only()
+            .column(entity::album::Column::Id)
+            .filter(entity::album::Column::MbAlbumId.eq(mb_album_id.as_str()))
+            .filter(entity::album::Column::LibraryId.eq(library_id))
+            .into_tuple()
+            .one(db)
+            .await?;
+        if existing.is_some() {
+            return Ok(existing);
+        }
+    }
+    let candidates: Vec<(String, String)> = entity::album::Entity::find()
+        .select_only()
+        .columns([entity::album::Column::Id, entity::album::Column::Path])
+        .filter(entity::album::Column::ArtistId.eq(artist_id))
+        .filter(entity::album::Column::Name.eq(metadata.album_name.as_str()))
+        .filter(entity::album::Column::LibraryId.eq(library_id))
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(candidates
+        .into_iter()
+        .find(|(_, path)| in_directory(path, &metadata.parent_path))
+        .map(|(id, _)| id))
+}
+
+// Albums store the path of their first song so the album directory is its parent
+fn in_directory(album_path: &str, dir: &str) -> bool {
+    Path::new(album_path)
+        .parent()
+        .and_then(|p| p.to_str())
+        .is_some_and(|parent| album_dir(parent) == album_dir(dir))
+}
+
+// Disc folders such as "CD1", "Disc 2" or "Disk 2 - Bonus" belong to the album in the parent directory
+pub fn album_dir(dir: &str) -> &str {
+    let path = Path::new(dir);
+    let disc_folder = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(is_disc_folder);
+    match (disc_folder, path.parent().and_then(|p| p.to_str())) {
+        (true, Some(parent)) => parent,
+        _ => dir,
+    }
+}
+
+fn is_disc_folder(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    ["cd", "disc", "disk"].iter().any(|prefix| {
+        lower.strip_prefix(prefix).is_some_and(|rest| {
+            rest.trim_start_matches([' ', '_', '-', '.'])
+                .starts_with(|c: char| c.is_ascii_digit())
+        })
+    })
+}
+
+// Groups songs into discs ordered by disc and track number. Untagged discs are treated as disc 1
+pub fn group_discs(mut songs: Vec<entity::song::Model>) -> Vec<AlbumDisc> {
+    let disc_number = |song: &entity::song::Model| song.disk.filter(|d| *d > 0).unwrap_or(1);
+    songs.sort_by_key(|song| (disc_number(song), song.track.unwrap_or_default()));
+    let mut discs: Vec<AlbumDisc> = Vec::new();
+    for song in songs {
+        let disc = disc_number(&song);
+        match discs.last_mut() {
+            Some(last) if last.disc == disc => {
+                if last.title.is_none() {
+                    last.title = song.disc_title.clone();
+                }
+                last.songs.push(song)
+            }
+            _ => discs.push(AlbumDisc {
+                disc,
+                title: song.disc_title.clone(),
+                songs: vec![song],
+            }),
+        }
+    }
+    discs
 }
 
 // Returns a album by the album_id with songs
@@ -186,12 +247,39 @@ pub async fn _update_cover_for_path(
     Ok(())
 }
 
+// Returns a select on albums limited to the library when provided
+fn select_albums(library: Option<&str>, hide_duplicates: bool) -> Select<entity::album::Entity> {
+    entity::album::Entity::find()
+        .apply_if(library, |query, library| {
+            query.filter(entity::album::Column::LibraryId.eq(library))
+        })
+        .apply_if(hide_duplicates.then_some(()), |query, _| {
+            query.filter(entity::album::Column::DuplicateOf.is_null())
+        })
+}
+
+// Drops songs whose better copy is on the same album, such as an album that was ripped twice
+pub fn hide_duplicate_songs(songs: Vec<entity::song::Model>) -> Vec<entity::song::Model> {
+    let ids: HashSet<String> = songs.iter().map(|song| song.id.clone()).collect();
+    songs
+        .into_iter()
+        .filter(|song| {
+            !song
+                .duplicate_of
+                .as_ref()
+                .is_some_and(|best| ids.contains(best))
+        })
+        .collect()
+}
+
 // Returns vec of albums taking into account sorting options and size
 // Sort options: name artist_name year latest
 pub async fn get_albums(
     db: &DatabaseConnection,
     size: Option<u64>,
     sort: Option<String>,
+    library: Option<String>,
+    hide_duplicates: bool,
 ) -> anyhow::Result<Vec<entity::album::Model>, ApiError> {
     let order = match sort.as_deref() {
         Some("name") => entity::album::Column::Name,
@@ -204,14 +292,14 @@ pub async fn get_albums(
     let limit = size.unwrap_or(500);
     Ok(match order {
         entity::album::Column::CreatedAt => {
-            entity::album::Entity::find()
+            select_albums(library.as_deref(), hide_duplicates)
                 .order_by_desc(order)
                 .limit(limit)
                 .all(db)
                 .await?
         }
         _ => {
-            entity::album::Entity::find()
+            select_albums(library.as_deref(), hide_duplicates)
                 .order_by_asc(order)
                 .limit(limit)
                 .all(db)
@@ -226,6 +314,8 @@ pub async fn get_albums_paginate(
     page: Option<u64>,
     size: Option<u64>,
     sort: Option<String>,
+    library: Option<String>,
+    hide_duplicates: bool,
 ) -> anyhow::Result<Vec<entity::album::Model>, ApiError> {
     let order = match sort.unwrap_or_default().as_str() {
         "name" => entity::album::Column::Name,
@@ -236,12 +326,46 @@ pub async fn get_albums_paginate(
     };
 
     let db_albums = match order {
-        entity::album::Column::CreatedAt => entity::album::Entity::find()
+        entity::album::Column::CreatedAt => select_albums(library.as_deref(), hide_duplicates)
             .order_by_desc(order)
             .paginate(db, size.unwrap_or(100)),
-        _ => entity::album::Entity::find()
+        _ => select_albums(library.as_deref(), hide_duplicates)
             .order_by_asc(order)
             .paginate(db, size.unwrap_or(100)),
     };
     Ok(db_albums.fetch_page(page.unwrap_or(0)).await?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_directory() {
+        assert!(in_directory(
+            "/music/Akon/Freedom/01.flac",
+            "/music/Akon/Freedom"
+        ));
+        assert!(!in_directory(
+            "/music/Akon/Freedom/01.flac",
+            "/music/Queen/Freedom"
+        ));
+        assert!(in_directory(
+            "/music/Akon/Freedom/CD1/01.flac",
+            "/music/Akon/Freedom/CD2"
+        ));
+        assert!(!in_directory(
+            "/music/Akon/Freedom/Live/01.flac",
+            "/music/Akon/Freedom"
+        ));
+    }
+
+    #[test]
+    fn test_album_dir() {
+        assert_eq!(album_dir("/music/Freedom/CD1"), "/music/Freedom");
+        assert_eq!(album_dir("/music/Freedom/Disc 2 - Bonus"), "/music/Freedom");
+        assert_eq!(album_dir("/music/Freedom/disk_3"), "/music/Freedom");
+        assert_eq!(album_dir("/music/Freedom"), "/music/Freedom");
+        assert_eq!(album_dir("/music/CDs"), "/music/CDs");
+    }
+}
@@ -0,0 +1,317 @@
+use std::{collections::HashSet, io, path::Path, time::SystemTime};
+
+use anyhow::{anyhow, Result};
+use async_stream::try_stream;
+use axum::body::Bytes;
+use chrono::{DateTime, Datelike, Local, Timelike};
+use futures::Stream;
+use tokio::io::AsyncReadExt;
+
+// Zip archives streamed straight from the library. Entries are stored without compression since audio
+// files don't compress, so the archive size is known before the first byte is sent and no temp file is needed
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const DATA_DESCRIPTOR: u32 = 0x08074b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const VERSION: u16 = 20;
+// Sizes and crc follow the data in a descriptor and names are UTF-8
+const FLAGS: u16 = 1 << 3 | 1 << 11;
+const LOCAL_HEADER_LEN: u64 = 30;
+const DATA_DESCRIPTOR_LEN: u64 = 16;
+const CENTRAL_HEADER_LEN: u64 = 46;
+const END_OF_CENTRAL_DIRECTORY_LEN: u64 = 22;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    // MS-DOS time and date
+    pub modified: (u16, u16),
+}
+
+impl ArchiveEntry {
+    pub async fn new(name: String, path: String) -> io::Result<Self> {
+        let metadata = tokio::fs::metadata(&path).await?;
+        Ok(ArchiveEntry {
+            name,
+            path,
+            size: metadata.len(),
+            modified: dos_time(metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)),
+        })
+    }
+}
+
+// Plain zip caps archives at 4 GiB and 65535 entries
+pub fn archive_size(entries: &[ArchiveEntry]) -> Result<u64> {
+    let size = entries
+        .iter()
+        .fold(END_OF_CENTRAL_DIRECTORY_LEN, |size, entry| {
+            size + LOCAL_HEADER_LEN
+                + DATA_DESCRIPTOR_LEN
+                + CENTRAL_HEADER_LEN
+                + 2 * entry.name.len() as u64
+                + entry.size
+        });
+    if size > u32::MAX as u64 || entries.len() > u16::MAX as usize {
+        return Err(anyhow!(
+            "Archive of {} files and {} bytes is too large",
+            entries.len(),
+            size
+        ));
+    }
+    Ok(size)
+}
+
+fn dos_time(time: SystemTime) -> (u16, u16) {
+    let time: DateTime<Local> = time.into();
+    if time.year() < 1980 {
+        return (0, 1 << 5 | 1);
+    }
+    (
+        (time.hour() << 11 | time.minute() << 5 | (time.second() / 2)) as u16,
+        (((time.year() - 1980) as u32) << 9 | time.month() << 5 | time.day()) as u16,
+    )
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+// Continues a crc32 over more bytes. Start from 0
+pub fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn local_header(entry: &ArchiveEntry) -> Vec<u8> {
+    let mut header = Vec::with_capacity(LOCAL_HEADER_LEN as usize + entry.name.len());
+    header.extend(LOCAL_HEADER.to_le_bytes());
+    header.extend(VERSION.to_le_bytes());
+    header.extend(FLAGS.to_le_bytes());
+    // Stored
+    header.extend(0u16.to_le_bytes());
+    header.extend(entry.modified.0.to_le_bytes());
+    header.extend(entry.modified.1.to_le_bytes());
+    // Crc and sizes are in the data descriptor
+    header.extend([0u8; 12]);
+    header.extend((entry.name.len() as u16).to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    header.extend(entry.name.as_bytes());
+    header
+}
+
+fn data_descriptor(crc: u32, size: u32) -> Vec<u8> {
+    let mut descriptor = Vec::with_capacity(DATA_DESCRIPTOR_LEN as usize);
+    descriptor.extend(DATA_DESCRIPTOR.to_le_bytes());
+    descriptor.extend(crc.to_le_bytes());
+    descriptor.extend(size.to_le_bytes());
+    descriptor.extend(size.to_le_bytes());
+    descriptor
+}
+
+fn central_header(entry: &ArchiveEntry, crc: u32, offset: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(CENTRAL_HEADER_LEN as usize + entry.name.len());
+    header.extend(CENTRAL_HEADER.to_le_bytes());
+    header.extend(VERSION.to_le_bytes());
+    header.extend(VERSION.to_le_bytes());
+    header.extend(FLAGS.to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    header.extend(entry.modified.0.to_le_bytes());
+    header.extend(entry.modified.1.to_le_bytes());
+    header.extend(crc.to_le_bytes());
+    header.extend((entry.size as u32).to_le_bytes());
+    header.extend((entry.size as u32).to_le_bytes());
+    header.extend((entry.name.len() as u16).to_le_bytes());
+    // Extra field, comment, disk number, internal and external attributes
+    header.extend([0u8; 12]);
+    header.extend(offset.to_le_bytes());
+    header.extend(entry.name.as_bytes());
+    header
+}
+
+fn end_of_central_directory(entries: u16, size: u32, offset: u32) -> Vec<u8> {
+    let mut record = Vec::with_capacity(END_OF_CENTRAL_DIRECTORY_LEN as usize);
+    record.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+    record.extend([0u8; 4]);
+    record.extend(entries.to_le_bytes());
+    record.extend(entries.to_le_bytes());
+    record.extend(size.to_le_bytes());
+    record.extend(offset.to_le_bytes());
+    record.extend(0u16.to_le_bytes());
+    record
+}
+
+// Streams the archive. Entries must have been checked with archive_size. A file that changed size since
+// the entry was made ends the stream with an error rather than sending a corrupt archive
+pub fn stream(entries: Vec<ArchiveEntry>) -> impl Stream<Item = io::Result<Bytes>> {
+    try_stream! {
+        let mut offset: u32 = 0;
+        let mut central_directory = Vec::new();
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        for entry in &entries {
+            let header = local_header(entry);
+            let entry_offset = offset;
+            offset += header.len() as u32;
+            yield Bytes::from(header);
+
+            let mut file = tokio::fs::File::open(&entry.path).await?.take(entry.size);
+            let mut crc = 0;
+            let mut read: u64 = 0;
+            loop {
+                let n = file.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                crc = crc32(crc, &buf[..n]);
+                read += n as u64;
+                yield Bytes::copy_from_slice(&buf[..n]);
+            }
+            if read != entry.size {
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{} changed while it was archived", entry.path),
+                ))?;
+            }
+            offset += entry.size as u32;
+
+            let descriptor = data_descriptor(crc, entry.size as u32);
+            offset += descriptor.len() as u32;
+            yield Bytes::from(descriptor);
+            central_directory.extend(central_header(entry, crc, entry_offset));
+        }
+        let size = central_directory.len() as u32;
+        yield Bytes::from(central_directory);
+        yield Bytes::from(end_of_central_directory(entries.len() as u16, size, offset));
+    }
+}
+
+// Names album songs after their files. Discs get their own folder when the album has more than one
+pub fn album_entry_names(discs: &[(i32, Vec<&str>)]) -> Vec<String> {
+    let mut used = HashSet::new();
+    let mut names = Vec::new();
+    for (disc, paths) in discs {
+        for path in paths {
+            let path = Path::new(path);
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let folder = match discs.len() {
+                1 => String::new(),
+                _ => format!("Disc {disc}/"),
+            };
+            let mut name = format!("{folder}{file_name}");
+            let mut copy = 1;
+            while !used.insert(name.to_lowercase()) {
+                copy += 1;
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                name = match path.extension() {
+                    Some(ext) => format!("{folder}{stem} ({copy}).{}", ext.to_string_lossy()),
+                    None => format!("{folder}{stem} ({copy})"),
+                };
+            }
+            names.push(name);
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(0, b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf43926);
+        assert_eq!(crc32(0, b""), 0);
+    }
+
+    #[test]
+    fn test_album_entry_names() {
+        let names = album_entry_names(&[(1, vec!["/music/Album/01 Intro.flac"])]);
+        assert_eq!(names, vec!["01 Intro.flac"]);
+
+        let names = album_entry_names(&[
+            (
+                1,
+                vec![
+                    "/music/Album/CD1/01 Intro.flac",
+                    "/music/Album/01 Intro.flac",
+                ],
+            ),
+            (2, vec!["/music/Album/CD2/01 Intro.flac"]),
+        ]);
+        assert_eq!(
+            names,
+            vec![
+                "Disc 1/01 Intro.flac",
+                "Disc 1/01 Intro (2).flac",
+                "Disc 2/01 Intro.flac"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let root = std::env::temp_dir().join(format!("deaftone-archive-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut entries = Vec::new();
+        for (name, content) in [("a.flac", vec![1u8; 100_000]), ("ü.flac", vec![])] {
+            let path = root.join(name);
+            std::fs::write(&path, content).unwrap();
+            entries.push(
+                ArchiveEntry::new(name.to_string(), path.to_string_lossy().to_string())
+                    .await
+                    .unwrap(),
+            );
+        }
+        let size = archive_size(&entries).unwrap();
+        let archive: Vec<u8> = stream(entries.clone())
+            .map_ok(|bytes| bytes.to_vec())
+            .try_concat()
+            .await
+            .unwrap();
+        assert_eq!(archive.len() as u64, size);
+
+        let end = &archive[archive.len() - END_OF_CENTRAL_DIRECTORY_LEN as usize..];
+        assert_eq!(&end[..4], END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+        let directory = u32::from_le_bytes([end[16], end[17], end[18], end[19]]) as usize;
+        let central = &archive[directory..];
+        assert_eq!(&central[..4], CENTRAL_HEADER.to_le_bytes());
+        assert_eq!(
+            u32::from_le_bytes([central[16], central[17], central[18], central[19]]),
+            crc32(0, &[1u8; 100_000])
+        );
+        assert_eq!(&central[46..52], b"a.flac");
+
+        // Files that changed since the entry was made fail the stream
+        std::fs::write(root.join("a.flac"), [1u8; 10]).unwrap();
+        assert!(stream(entries).try_collect::<Vec<_>>().await.is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
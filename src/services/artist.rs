@@ -1,41 +1,253 @@
 use anyhow::anyhow;
 use chrono::Utc;
 use hyper::StatusCode;
+use sea_orm::sea_query::{Expr, Func, Query};
 use sea_orm::{
-    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
-    QuerySelect,
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Select, Set,
 };
-use sqlx::{Sqlite, Transaction};
 use uuid::Uuid;
 
+use crate::services::scanner::tag_helper::AudioMetadata;
+
 use super::http::error::ApiError;
 
+// Roles an artist can be credited with on a song or album
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArtistRole {
+    Main,
+    Featured,
+    Composer,
+    Lyricist,
+    Arranger,
+}
+
+impl ArtistRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtistRole::Main => "main",
+            ArtistRole::Featured => "featured",
+            ArtistRole::Composer => "composer",
+            ArtistRole::Lyricist => "lyricist",
+            ArtistRole::Arranger => "arranger",
+        }
+    }
+}
+
 // Creates a artist entry with artist name passed and MusicBrainzArtistId
 pub async fn create_artist(
-    tx: &mut Transaction<'_, Sqlite>,
+    db: &impl ConnectionTrait,
     artist_name: &str,
     mb_artist_id: &Option<String>,
 ) -> Result<String, anyhow::Error> {
-    let id: String = Uuid::new_v4().to_string();
-    let init_time: String = Utc::now().naive_local().to_string();
-    sqlx::query(
-        "INSERT OR REPLACE INTO artists (
-            id, 
-            name,
-            mb_artist_id,
-            created_at,
-            updated_at
-         )
-    VALUES (?,?,?,?,?)",
-    )
-    .bind(&id)
-    .bind(artist_name)
-    .bind(mb_artist_id)
-    .bind(&init_time)
-    .bind(&init_time)
-    .execute(&mut **tx)
+    let init_time = Utc::now().naive_local();
+    let artist = entity::artist::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        name: Set(artist_name.to_owned()),
+        mb_artist_id: Set(mb_artist_id.clone()),
+        created_at: Set(init_time),
+        updated_at: Set(init_time),
+        ..Default::default()
+    };
+    Ok(artist.insert(db).await?.id)
+}
+
+// Returns the id of the artist. Matching on MusicBrainzArtistId first and then on name. Creating the artist when neither matches
+pub async fn get_or_create_artist(
+    db: &impl ConnectionTrait,
+    artist_name: &str,
+    mb_artist_id: Option<&str>,
+) -> Result<String, anyhow::Error> {
+    if let Some(mb_artist_id) = mb_artist_id {
+        let existing: Option<String> = entity::artist::Entity::find()
+            .select_only()
+            .column(entity::artist::Column::Id)
+            .filter(entity::artist::Column::MbArtistId.eq(mb_artist_id))
+            .into_tuple()
+            .one(db)
+            .await?;
+        if let Some(id) = existing {
+            return Ok(id);
+        }
+    }
+    // Artists with a different MusicBrainzArtistId are different artists sharing a name
+    let existing: Option<(String, Option<String>)> = entity::artist::Entity::find()
+        .select_only()
+        .columns([
+            entity::artist::Column::Id,
+            entity::artist::Column::MbArtistId,
+        ])
+        .filter(
+            Expr::expr(Func::lower(Expr::col(entity::artist::Column::Name)))
+                .eq(Func::lower(Expr::val(artist_name))),
+        )
+        .apply_if(mb_artist_id, |query, _| {
+            query.filter(entity::artist::Column::MbArtistId.is_null())
+        })
+        .into_tuple()
+        .one(db)
+        .await?;
+    match existing {
+        Some((id, existing_mb_id)) => {
+            if existing_mb_id.is_none() && mb_artist_id.is_some() {
+                entity::artist::ActiveModel {
+                    id: Set(id.clone()),
+                    mb_artist_id: Set(mb_artist_id.map(String::from)),
+                    ..Default::default()
+                }
+                .update(db)
+                .await?;
+            }
+            Ok(id)
+        }
+        None => {
+            tracing::info!("Creating artists \"{:}\"", artist_name);
+            create_artist(db, artist_name, &mb_artist_id.map(String::from)).await
+        }
+    }
+}
+
+// Returns the MusicBrainzArtistId at index. Ids are only trusted when there is one per artist
+fn mb_id_at<'a>(mb_ids: &'a [String], artists: &[String], index: usize) -> Option<&'a str> {
+    match mb_ids.len() == artists.len() {
+        true => mb_ids.get(index).map(|s| s.as_str()),
+        false => None,
+    }
+}
+
+// Resolves the album artists of the metadata into artist ids. The first id is the primary album artist
+pub async fn resolve_album_artists(
+    db: &impl ConnectionTrait,
+    metadata: &AudioMetadata,
+) -> Result<Vec<String>, anyhow::Error> {
+    let names = match metadata.album_artists.is_empty() {
+        true => vec![metadata.album_artist.clone()],
+        false => metadata.album_artists.clone(),
+    };
+    let mut ids = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        let mb_id = match metadata.album_artists.is_empty() {
+            true => metadata.mb_albumartist_id.as_deref(),
+            false => mb_id_at(&metadata.mb_albumartist_ids, &names, index),
+        };
+        ids.push(get_or_create_artist(db, name, mb_id).await?);
+    }
+    Ok(ids)
+}
+
+async fn insert_credit(
+    db: &impl ConnectionTrait,
+    artist_id: &str,
+    song_id: Option<&str>,
+    album_id: Option<&str>,
+    role: ArtistRole,
+    position: usize,
+) -> Result<(), anyhow::Error> {
+    entity::artist_credit::Entity::insert(entity::artist_credit::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        artist_id: Set(artist_id.to_owned()),
+        song_id: Set(song_id.map(String::from)),
+        album_id: Set(album_id.map(String::from)),
+        role: Set(role.as_str().to_owned()),
+        position: Set(position as i32),
+    })
+    .exec_without_returning(db)
     .await?;
-    Ok(id)
+    Ok(())
+}
+
+// Credits the album artists to the album in order
+pub async fn credit_album(
+    db: &impl ConnectionTrait,
+    album_id: &str,
+    artist_ids: &[String],
+) -> Result<(), anyhow::Error> {
+    for (position, artist_id) in artist_ids.iter().enumerate() {
+        insert_credit(
+            db,
+            artist_id,
+            None,
+            Some(album_id),
+            ArtistRole::Main,
+            position,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+// Credits every track artist, featured artist, composer, lyricist and arranger found in the metadata to the song
+// Credits from an earlier scan of the song are replaced
+pub async fn credit_song(
+    db: &impl ConnectionTrait,
+    song_id: &str,
+    metadata: &AudioMetadata,
+) -> Result<(), anyhow::Error> {
+    entity::artist_credit::Entity::delete_many()
+        .filter(entity::artist_credit::Column::SongId.eq(song_id))
+        .exec(db)
+        .await?;
+    for (index, name) in metadata.artists.iter().enumerate() {
+        let role = match metadata.featured_artists.contains(name) {
+            true => ArtistRole::Featured,
+            false => ArtistRole::Main,
+        };
+        let mb_id = mb_id_at(&metadata.mb_artist_ids, &metadata.artists, index);
+        let artist_id = get_or_create_artist(db, name, mb_id).await?;
+        insert_credit(db, &artist_id, Some(song_id), None, role, index).await?;
+    }
+    for (role, names) in [
+        (ArtistRole::Composer, &metadata.composers),
+        (ArtistRole::Lyricist, &metadata.lyricists),
+        (ArtistRole::Arranger, &metadata.arrangers),
+    ] {
+        for (index, name) in names.iter().enumerate() {
+            let artist_id = get_or_create_artist(db, name, None).await?;
+            insert_credit(db, &artist_id, Some(song_id), None, role, index).await?;
+        }
+    }
+    Ok(())
+}
+
+// Returns albums by other artists that the artist is credited on. Such as features
+pub async fn get_artist_appearances(
+    db: &DatabaseConnection,
+    artist_id: &str,
+) -> anyhow::Result<Vec<entity::album::Model>, ApiError> {
+    let album_credits = Query::select()
+        .column(entity::artist_credit::Column::AlbumId)
+        .from(entity::artist_credit::Entity)
+        .and_where(entity::artist_credit::Column::ArtistId.eq(artist_id))
+        .and_where(entity::artist_credit::Column::AlbumId.is_not_null())
+        .to_owned();
+    let song_credits = Query::select()
+        .column((entity::song::Entity, entity::song::Column::AlbumId))
+        .from(entity::artist_credit::Entity)
+        .inner_join(
+            entity::song::Entity,
+            Expr::col((entity::song::Entity, entity::song::Column::Id)).equals((
+                entity::artist_credit::Entity,
+                entity::artist_credit::Column::SongId,
+            )),
+        )
+        .and_where(
+            Expr::col((
+                entity::artist_credit::Entity,
+                entity::artist_credit::Column::ArtistId,
+            ))
+            .eq(artist_id),
+        )
+        .to_owned();
+    Ok(entity::album::Entity::find()
+        .filter(entity::album::Column::ArtistId.ne(artist_id))
+        .filter(
+            Condition::any()
+                .add(entity::album::Column::Id.in_subquery(album_credits))
+                .add(entity::album::Column::Id.in_subquery(song_credits)),
+        )
+        .order_by_desc(entity::album::Column::Year)
+        .all(db)
+        .await?)
 }
 
 // Return a artist by there artist_id. Also merges there connected MusicBrainArtistId albums
@@ -62,11 +274,27 @@ pub async fn get_artist_by_id(
     }
 }
 
+// Returns a select on artists limited to those with albums in the library when provided
+fn select_artists(library: Option<&str>) -> Select<entity::artist::Entity> {
+    entity::artist::Entity::find().apply_if(library, |query, library| {
+        query.filter(
+            entity::artist::Column::Id.in_subquery(
+                Query::select()
+                    .column(entity::album::Column::ArtistId)
+                    .from(entity::album::Entity)
+                    .and_where(entity::album::Column::LibraryId.eq(library))
+                    .to_owned(),
+            ),
+        )
+    })
+}
+
 // Returns a vec of artist with size and sort options
 pub async fn get_artists(
     db: &DatabaseConnection,
     size: Option<u64>,
     sort: Option<String>,
+    library: Option<String>,
 ) -> anyhow::Result<Vec<entity::artist::Model>, ApiError> {
     let order = match sort.as_deref() {
         Some("name") => entity::artist::Column::Name,
@@ -75,14 +303,14 @@ pub async fn get_artists(
     };
     let result = match order {
         entity::artist::Column::CreatedAt => {
-            entity::artist::Entity::find()
+            select_artists(library.as_deref())
                 .order_by_desc(order)
                 .limit(size)
                 .all(db)
                 .await?
         }
         _ => {
-            entity::artist::Entity::find()
+            select_artists(library.as_deref())
                 .order_by_asc(order)
                 .limit(size)
                 .all(db)
@@ -98,6 +326,7 @@ pub async fn get_artists_paginate(
     page: Option<u64>,
     size: Option<u64>,
     sort: Option<String>,
+    library: Option<String>,
 ) -> anyhow::Result<Vec<entity::artist::Model>, ApiError> {
     let order = match sort.unwrap_or_default().as_str() {
         "name" => entity::artist::Column::Name,
@@ -106,10 +335,10 @@ pub async fn get_artists_paginate(
     };
 
     let db_artist = match order {
-        entity::artist::Column::CreatedAt => entity::artist::Entity::find()
+        entity::artist::Column::CreatedAt => select_artists(library.as_deref())
             .order_by_desc(order)
             .paginate(db, size.unwrap_or(u64::MAX)),
-        _ => entity::artist::Entity::find()
+        _ => select_artists(library.as_deref())
             .order_by_asc(order)
             .paginate(db, size.unwrap_or(u64::MAX)),
     };
@@ -0,0 +1,390 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::anyhow;
+use chrono::{NaiveDateTime, Utc};
+use hyper::StatusCode;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection,
+    EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set, Statement, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{
+    events::{Event, EventBus},
+    http::error::ApiError,
+};
+
+// Bumped when the export format changes in a way older versions can't import
+pub const EXPORT_VERSION: u32 = 1;
+
+// Identifies a song across databases. The MusicBrainz track id is tried first since paths change when a library moves
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub struct SongRef {
+    pub mb_track_id: Option<String>,
+    #[schema(example = "/music/Akon/Konvicted/01 Smack That.flac")]
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ExportedPlaylist {
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub songs: Vec<SongRef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ExportedPlay {
+    pub song: SongRef,
+    pub played_at: NaiveDateTime,
+    // Seconds listened
+    pub duration: i32,
+}
+
+// Likes, playlists and play history without any database ids. Deaftone doesn't store ratings yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UserStateExport {
+    pub version: u32,
+    pub exported_at: NaiveDateTime,
+    pub likes: Vec<SongRef>,
+    pub playlists: Vec<ExportedPlaylist>,
+    pub plays: Vec<ExportedPlay>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    pub likes: usize,
+    pub playlists: usize,
+    pub playlist_songs: usize,
+    pub plays: usize,
+    // Songs of the export that don't match a scanned song. Their state is skipped
+    pub missing: Vec<SongRef>,
+}
+
+// Maps songs of the current library by MusicBrainz track id and by path
+#[derive(Default)]
+struct SongIndex {
+    by_mb_track_id: HashMap<String, String>,
+    by_path: HashMap<String, String>,
+    refs: HashMap<String, SongRef>,
+}
+
+impl SongIndex {
+    async fn load(db: &impl ConnectionTrait) -> Result<Self, ApiError> {
+        let songs: Vec<(String, String, Option<String>)> = entity::song::Entity::find()
+            .select_only()
+            .columns([
+                entity::song::Column::Id,
+                entity::song::Column::Path,
+                entity::song::Column::MbTrackId,
+            ])
+            .into_tuple()
+            .all(db)
+            .await?;
+        let mut index = SongIndex::default();
+        for (id, path, mb_track_id) in songs {
+            index.insert(id, path, mb_track_id);
+        }
+        Ok(index)
+    }
+
+    fn insert(&mut self, id: String, path: String, mb_track_id: Option<String>) {
+        if let Some(mb_track_id) = &mb_track_id {
+            self.by_mb_track_id
+                .entry(mb_track_id.clone())
+                .or_insert_with(|| id.clone());
+        }
+        self.by_path.insert(path.clone(), id.clone());
+        self.refs.insert(id, SongRef { mb_track_id, path });
+    }
+
+    fn resolve(&self, song: &SongRef) -> Option<&str> {
+        song.mb_track_id
+            .as_ref()
+            .and_then(|mb_track_id| self.by_mb_track_id.get(mb_track_id))
+            .or_else(|| self.by_path.get(&song.path))
+            .map(String::as_str)
+    }
+
+    fn song_ref(&self, id: &str) -> Option<SongRef> {
+        self.refs.get(id).cloned()
+    }
+}
+
+// Writes a consistent copy of the SQLite database to path while Deaftone keeps running
+// VACUUM INTO reads from a single transaction so writes made during the backup aren't included
+pub async fn backup_sqlite(db: &DatabaseConnection, path: &Path) -> Result<(), ApiError> {
+    if db.get_database_backend() != DatabaseBackend::Sqlite {
+        return Err(ApiError(
+            StatusCode::NOT_IMPLEMENTED,
+            anyhow!("Backups of PostgreSQL databases are made with pg_dump"),
+        ));
+    }
+    if path.exists() {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            anyhow!("Backup file {} already exists", path.display()),
+        ));
+    }
+    db.execute(Statement::from_sql_and_values(
+        DatabaseBackend::Sqlite,
+        "VACUUM INTO ?",
+        [path.to_string_lossy().to_string().into()],
+    ))
+    .await?;
+    tracing::info!("Backed up database to {}", path.display());
+    Ok(())
+}
+
+// Deletes the users from the backup at path. VACUUM rewrites the file so the deleted rows are really gone
+// The backup keeps the rollback journal of VACUUM INTO so no -wal file is left next to it
+pub async fn remove_users(path: &Path) -> Result<(), anyhow::Error> {
+    let backup =
+        crate::database::connect_sqlite(SqliteConnectOptions::new().filename(path), 1, 1).await?;
+    entity::user::Entity::delete_many().exec(&backup).await?;
+    backup.execute_unprepared("VACUUM").await?;
+    backup.close().await?;
+    Ok(())
+}
+
+// Exports the user state keyed by MusicBrainz track id and path so it can be imported into another database
+pub async fn export_user_state(db: &DatabaseConnection) -> Result<UserStateExport, ApiError> {
+    let index = SongIndex::load(db).await?;
+    let likes: Vec<String> = entity::song::Entity::find()
+        .select_only()
+        .column(entity::song::Column::Id)
+        .filter(entity::song::Column::Liked.eq(true))
+        .order_by_asc(entity::song::Column::Path)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut playlists = Vec::new();
+    for playlist in entity::playlist::Entity::find()
+        .order_by_asc(entity::playlist::Column::CreatedAt)
+        .all(db)
+        .await?
+    {
+        let songs: Vec<Option<String>> = entity::playlist_song::Entity::find()
+            .select_only()
+            .column(entity::playlist_song::Column::SongId)
+            .filter(entity::playlist_song::Column::PlaylistId.eq(playlist.id.as_str()))
+            .into_tuple()
+            .all(db)
+            .await?;
+        playlists.push(ExportedPlaylist {
+            name: playlist.name,
+            created_at: playlist.created_at,
+            updated_at: playlist.updated_at,
+            songs: songs
+                .iter()
+                .flatten()
+                .filter_map(|id| index.song_ref(id))
+                .collect(),
+        });
+    }
+
+    let plays = entity::play::Entity::find()
+        .order_by_asc(entity::play::Column::PlayedAt)
+        .all(db)
+        .await?
+        .into_iter()
+        // Plays of removed songs can't be matched on import so they aren't exported
+        .filter_map(|play| {
+            Some(ExportedPlay {
+                song: index.song_ref(play.song_id.as_deref()?)?,
+                played_at: play.played_at,
+                duration: play.duration,
+            })
+        })
+        .collect();
+
+    Ok(UserStateExport {
+        version: EXPORT_VERSION,
+        exported_at: Utc::now().naive_local(),
+        likes: likes.iter().filter_map(|id| index.song_ref(id)).collect(),
+        playlists,
+        plays,
+    })
+}
+
+// Re-links exported state onto the scanned songs. Importing the same export twice doesn't duplicate
+// playlists or plays. Playlists are matched on name and creation time, plays on song and time played
+pub async fn import_user_state(
+    db: &DatabaseConnection,
+    events: &EventBus,
+    export: UserStateExport,
+) -> Result<ImportReport, ApiError> {
+    if export.version > EXPORT_VERSION {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            anyhow!(
+                "Export version {} is newer than the supported version {}",
+                export.version,
+                EXPORT_VERSION
+            ),
+        ));
+    }
+    let tx = db.begin().await?;
+    let index = SongIndex::load(&tx).await?;
+    let mut report = ImportReport::default();
+    let mut missing = HashSet::new();
+    let mut resolve = |song: &SongRef| match index.resolve(song) {
+        Some(id) => Some(id.to_owned()),
+        None => {
+            if missing.insert(song.clone()) {
+                report.missing.push(song.clone());
+            }
+            None
+        }
+    };
+
+    let liked: Vec<String> = export.likes.iter().filter_map(&mut resolve).collect();
+    let mut likes = 0;
+    if !liked.is_empty() {
+        entity::song::Entity::update_many()
+            .col_expr(entity::song::Column::Liked, true.into())
+            .filter(entity::song::Column::Id.is_in(liked.iter().map(String::as_str)))
+            .exec(&tx)
+            .await?;
+        likes = liked.len();
+    }
+
+    let existing_playlists: HashSet<(String, NaiveDateTime)> = entity::playlist::Entity::find()
+        .all(&tx)
+        .await?
+        .into_iter()
+        .map(|playlist| (playlist.name, playlist.created_at))
+        .collect();
+    let mut created_playlists = Vec::new();
+    let mut playlist_songs = 0;
+    for playlist in &export.playlists {
+        if existing_playlists.contains(&(playlist.name.clone(), playlist.created_at)) {
+            continue;
+        }
+        let playlist_id = Uuid::new_v4().to_string();
+        entity::playlist::ActiveModel {
+            id: Set(playlist_id.clone()),
+            name: Set(playlist.name.clone()),
+            created_at: Set(playlist.created_at),
+            updated_at: Set(playlist.updated_at),
+        }
+        .insert(&tx)
+        .await?;
+        for song_id in playlist.songs.iter().filter_map(&mut resolve) {
+            entity::playlist_song::ActiveModel {
+                id: Set(Uuid::new_v4().to_string()),
+                playlist_id: Set(Some(playlist_id.clone())),
+                song_id: Set(Some(song_id)),
+            }
+            .insert(&tx)
+            .await?;
+            playlist_songs += 1;
+        }
+        created_playlists.push(playlist_id);
+    }
+
+    let mut existing_plays: HashSet<(String, NaiveDateTime)> = entity::play::Entity::find()
+        .select_only()
+        .columns([entity::play::Column::SongId, entity::play::Column::PlayedAt])
+        .filter(entity::play::Column::SongId.is_not_null())
+        .into_tuple()
+        .all(&tx)
+        .await?
+        .into_iter()
+        .collect();
+    let mut plays = Vec::new();
+    for play in &export.plays {
+        let Some(song_id) = resolve(&play.song) else {
+            continue;
+        };
+        if existing_plays.insert((song_id.clone(), play.played_at)) {
+            plays.push(entity::play::ActiveModel {
+                id: Set(Uuid::new_v4().to_string()),
+                song_id: Set(Some(song_id)),
+                played_at: Set(play.played_at),
+                duration: Set(play.duration),
+            });
+        }
+    }
+    let imported_plays = plays.len();
+    for chunk in plays.chunks(500) {
+        entity::play::Entity::insert_many(chunk.to_vec())
+            .exec_without_returning(&tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    report.likes = likes;
+    report.playlists = created_playlists.len();
+    report.playlist_songs = playlist_songs;
+    report.plays = imported_plays;
+    for playlist_id in created_playlists {
+        events.publish(Event::PlaylistChanged { playlist_id });
+    }
+    tracing::info!(
+        "Imported {} likes, {} playlists and {} plays. {} songs are missing",
+        report.likes,
+        report.playlists,
+        report.plays,
+        report.missing.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song_ref(mb_track_id: Option<&str>, path: &str) -> SongRef {
+        SongRef {
+            mb_track_id: mb_track_id.map(String::from),
+            path: String::from(path),
+        }
+    }
+
+    #[test]
+    fn test_song_index_resolve() {
+        let mut index = SongIndex::default();
+        index.insert(
+            String::from("moved"),
+            String::from("/new/Akon/01 Smack That.flac"),
+            Some(String::from("mb-smack-that")),
+        );
+        index.insert(
+            String::from("untagged"),
+            String::from("/new/Akon/02 Blown Away.flac"),
+            None,
+        );
+        // Matched on MusicBrainz track id even though the library moved
+        assert_eq!(
+            index.resolve(&song_ref(
+                Some("mb-smack-that"),
+                "/old/Akon/01 Smack That.flac"
+            )),
+            Some("moved")
+        );
+        // Falls back to the path when the track id is unknown
+        assert_eq!(
+            index.resolve(&song_ref(Some("mb-other"), "/new/Akon/02 Blown Away.flac")),
+            Some("untagged")
+        );
+        assert_eq!(
+            index.resolve(&song_ref(None, "/old/Akon/02 Blown Away.flac")),
+            None
+        );
+        assert_eq!(
+            index.song_ref("moved"),
+            Some(song_ref(
+                Some("mb-smack-that"),
+                "/new/Akon/01 Smack That.flac"
+            ))
+        );
+    }
+}
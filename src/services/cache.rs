@@ -0,0 +1,41 @@
+use std::{fs, path::Path, time::SystemTime};
+
+use anyhow::Result;
+
+use crate::SETTINGS;
+
+use super::scheduler::parse_interval;
+
+// Removes cached files older than cache_max_age from cache_path
+pub async fn evict_cache() -> Result<()> {
+    let max_age = parse_interval(&SETTINGS.cache_max_age)?.to_std()?;
+    // Walking a large cache blocks, so it runs off the async workers
+    let removed = tokio::task::spawn_blocking(move || {
+        evict_older_than(Path::new(&SETTINGS.cache_path), max_age)
+    })
+    .await??;
+    tracing::info!("Evicted {:} cached files", removed);
+    Ok(())
+}
+
+fn evict_older_than(dir: &Path, max_age: std::time::Duration) -> Result<usize> {
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            removed += evict_older_than(&path, max_age)?;
+            continue;
+        }
+        let age = SystemTime::now()
+            .duration_since(fs::metadata(&path)?.modified()?)
+            .unwrap_or_default();
+        if age > max_age {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -1,40 +1,31 @@
 use chrono::Utc;
 use mdns_sd::{ServiceDaemon, ServiceEvent};
-use sqlx::{sqlite::SqliteQueryResult, Pool};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
 use std::{collections::HashSet, net::IpAddr, thread::sleep};
-use tokio::time::Duration;
 use tokio::time::Instant;
 use uuid::Uuid;
 
-use crate::database;
+use crate::{
+    services::events::{Event, EventBus},
+    SETTINGS,
+};
 
 pub mod device;
 
 pub struct Mdns {
     service_name: String,
-    sqlite_pool: Pool<sqlx::Sqlite>, // This is an ugly workaround for: https://github.com/keepsimple1/mdns-sd/issues/145
+    db: DatabaseConnection,
+    events: EventBus,
 }
 pub const CHROMECAST_SERVICE_NAME: &str = "_googlecast._tcp.local.";
 pub const AIRPLAY_SERVICE_NAME: &str = "_raop._tcp.local.";
-#[allow(dead_code)]
-#[derive(Debug, sqlx::FromRow)]
-struct Device {
-    id: String,
-    name: String,
-    address_v4: String,
-    created_at: String,
-    updated_at: String,
-}
 impl Mdns {
-    pub async fn new(application_name: &str) -> Result<Self, anyhow::Error> {
-        let sqlite_pool = match database::connect_db_sqlx().await {
-            Ok(pool) => pool,
-            Err(_) => database::connect_db_sqlx().await.unwrap(),
-        };
-        Ok(Self {
+    pub fn new(application_name: &str, db: &DatabaseConnection, events: EventBus) -> Self {
+        Self {
             service_name: application_name.to_string(),
-            sqlite_pool,
-        })
+            db: db.clone(),
+            events,
+        }
     }
 
     pub async fn discover(&self) {
@@ -45,20 +36,24 @@ impl Mdns {
         loop {
             tracing::debug!("Starting dns discovery...");
             let start_time = Instant::now();
-            let max_duration = Duration::from_secs(60);
+            let max_duration = SETTINGS.mdns_discovery_duration();
             while let Ok(event) = receiver.recv() {
                 match event {
                     ServiceEvent::ServiceResolved(info) => {
                         tracing::debug!("Resolved a new service: {}", info.get_fullname());
                         if let Some(first_ipv4) = find_first_ipv4(info.get_addresses()) {
                             tracing::debug!("First IPv4 address: {}", first_ipv4);
-                            Self::insert_or_update_device(
+                            let device_id = Self::insert_or_update_device(
                                 info.get_fullname(),
                                 &first_ipv4.to_string(),
-                                &self.sqlite_pool,
+                                &self.db,
                             )
                             .await
                             .expect("Failed to insert_or_update_device");
+                            self.events.publish(Event::CastStatus {
+                                device_id,
+                                status: String::from("available"),
+                            });
                         } else {
                             tracing::debug!("No IPv4 address found in the set");
                         }
@@ -74,55 +69,43 @@ impl Mdns {
                 }
             }
             tracing::debug!("Sleeping dns discovery...");
-            sleep(Duration::from_secs(5 * 60)); // Sleep for 5 minutes before restarting the loop
+            sleep(SETTINGS.mdns_discovery_interval()); // Sleep before restarting the loop
         }
     }
 
     async fn insert_or_update_device(
         device_name: &str,
         new_address_v4: &str,
-        db: &Pool<sqlx::Sqlite>,
-    ) -> Result<SqliteQueryResult, anyhow::Error> {
-        let init_time: String = Utc::now().naive_local().to_string();
+        db: &DatabaseConnection,
+    ) -> Result<String, anyhow::Error> {
+        let init_time = Utc::now().naive_local();
 
-        let existing_device =
-            sqlx::query_as::<_, Device>("SELECT * FROM cast_devices WHERE name = ?")
-                .bind(device_name)
-                .persistent(true)
-                .fetch_optional(db)
-                .await?;
-        if let Some(mut device) = existing_device {
+        let existing_device = entity::cast_devices::Entity::find()
+            .filter(entity::cast_devices::Column::Name.eq(device_name))
+            .one(db)
+            .await?;
+        if let Some(device) = existing_device {
             // Device exists, update address_v4
-            device.address_v4 = new_address_v4.to_string();
             tracing::debug!("Updating device: {:?}", &device.name);
-
-            Ok(
-                sqlx::query("UPDATE cast_devices SET address_v4 = ?, updated_at = ? WHERE id = ?")
-                    .bind(device.address_v4)
-                    .bind(init_time)
-                    .bind(device.id)
-                    .execute(db)
-                    .await?,
-            )
+            let id = device.id.clone();
+            let mut device: entity::cast_devices::ActiveModel = device.into();
+            device.address_v4 = Set(new_address_v4.to_string());
+            device.updated_at = Set(init_time);
+            device.update(db).await?;
+            Ok(id)
         } else {
             tracing::debug!("Creating device: {:?}", &device_name);
-            Ok(sqlx::query(
-                "INSERT INTO cast_devices (
-                        id,
-                        name,
-                        address_v4,
-                        created_at,
-                        updated_at
-                    )
-                    VALUES (?,?,?,?,?)",
-            )
-            .bind(Uuid::new_v4().to_string())
-            .bind(device_name)
-            .bind(new_address_v4)
-            .bind(&init_time)
-            .bind(&init_time)
-            .execute(db)
-            .await?)
+            let id = Uuid::new_v4().to_string();
+            entity::cast_devices::ActiveModel {
+                id: Set(id.clone()),
+                name: Set(device_name.to_string()),
+                address_v4: Set(new_address_v4.to_string()),
+                created_at: Set(init_time),
+                updated_at: Set(init_time),
+            }
+            .insert(db)
+            .await?;
+            Ok(id)
         }
     }
 }
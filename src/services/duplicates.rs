@@ -0,0 +1,590 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use anyhow::anyhow;
+use hyper::StatusCode;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    FromQueryResult, QueryFilter, QueryOrder, QuerySelect, QueryTrait, TransactionTrait,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{
+    fingerprint::chromaprint, http::error::ApiError, scanner::tag_helper::PLACEHOLDER_TITLE,
+};
+
+// Songs matched on artist and title are duplicates when their lengths differ by at most this many seconds
+const LENGTH_TOLERANCE: i32 = 3;
+
+// Songs are only compared on their fingerprints when they share this many fingerprint items
+const MIN_SHARED_ITEMS: usize = 10;
+// Items found in more songs than this are silence or noise and say nothing about the recording
+const MAX_ITEM_SONGS: usize = 32;
+// Share of matching bits for two fingerprints to be of the same recording. Unrelated songs are around 0.5
+const FINGERPRINT_SIMILARITY: f64 = 0.85;
+// Fingerprint items compared around the start to make up for encoder delay. One item is about 124ms
+const FINGERPRINT_OFFSET: usize = 4;
+
+const LOSSLESS_CODECS: [&str; 6] = ["FLAC", "ALAC", "WAV", "AIFF", "APE", "WAVPACK"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedBy {
+    // MusicBrainz recording id or release track id
+    Musicbrainz,
+    Isrc,
+    Acoustid,
+    // Similar Chromaprint fingerprints
+    Fingerprint,
+    // Normalized artist and title with lengths within a few seconds
+    Metadata,
+}
+
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct DuplicateCandidate {
+    pub id: String,
+    pub path: String,
+    pub title: String,
+    pub artist: String,
+    pub album_id: Option<String>,
+    pub album_name: String,
+    pub length: i32,
+    pub codec: Option<String>,
+    pub sample_rate: Option<String>,
+    pub bits_per_sample: Option<i32>,
+    pub bit_rate: Option<i64>,
+    pub mb_recording_id: Option<String>,
+    pub mb_track_id: Option<String>,
+    pub isrc: Option<String>,
+    pub acoustid_id: Option<String>,
+    pub acoustid_fingerprint: Option<String>,
+}
+
+impl DuplicateCandidate {
+    fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+            .as_deref()
+            .and_then(|rate| rate.parse().ok())
+    }
+
+    // Lossless copies first, then bit depth, sample rate and bit rate
+    fn quality(&self) -> (bool, i32, u32, i64) {
+        let lossless = self
+            .codec
+            .as_deref()
+            .is_some_and(|codec| LOSSLESS_CODECS.contains(&codec.to_uppercase().as_str()));
+        (
+            lossless,
+            self.bits_per_sample.unwrap_or_default(),
+            self.sample_rate().unwrap_or_default(),
+            self.bit_rate.unwrap_or_default(),
+        )
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DuplicateSong {
+    pub id: String,
+    pub path: String,
+    pub title: String,
+    pub artist: String,
+    pub album_id: Option<String>,
+    pub album_name: String,
+    pub length: i32,
+    pub codec: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<i32>,
+    // Average kbps
+    pub bit_rate: Option<i64>,
+    // Highest quality copy of the group. The others are hidden by ?hide_duplicates=true
+    pub best: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DuplicateGroup {
+    pub matched_by: Vec<MatchedBy>,
+    // Best copy first
+    pub songs: Vec<DuplicateSong>,
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        UnionFind {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, item: usize) -> usize {
+        let mut root = item;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut item = item;
+        while self.parent[item] != root {
+            let next = self.parent[item];
+            self.parent[item] = root;
+            item = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+// Lowercases and drops punctuation so "Smack That (feat. Eminem)" and "smack that feat eminem" match
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c.is_alphanumeric() {
+            true => c.to_lowercase().next().unwrap_or(c),
+            false => ' ',
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// Pairs of songs with similar fingerprints. Only songs sharing some exact fingerprint items are compared
+fn fingerprint_links(songs: &[DuplicateCandidate]) -> Vec<(usize, usize, MatchedBy)> {
+    let fingerprints: Vec<Option<Vec<u32>>> = songs
+        .iter()
+        .map(|song| {
+            song.acoustid_fingerprint
+                .as_deref()
+                .and_then(chromaprint::decode)
+        })
+        .collect();
+    let mut items: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (index, fingerprint) in fingerprints.iter().enumerate() {
+        for item in fingerprint.iter().flatten().collect::<HashSet<&u32>>() {
+            items.entry(*item).or_default().push(index);
+        }
+    }
+    let mut shared: HashMap<(usize, usize), usize> = HashMap::new();
+    for indexes in items.values() {
+        if indexes.len() > MAX_ITEM_SONGS {
+            continue;
+        }
+        for (position, a) in indexes.iter().enumerate() {
+            for b in &indexes[position + 1..] {
+                *shared.entry((*a, *b)).or_default() += 1;
+            }
+        }
+    }
+    let mut links: Vec<(usize, usize, MatchedBy)> = shared
+        .into_iter()
+        .filter(|((a, b), count)| {
+            *count >= MIN_SHARED_ITEMS
+                && (songs[*a].length - songs[*b].length).abs() <= LENGTH_TOLERANCE
+        })
+        .filter(|((a, b), _)| match (&fingerprints[*a], &fingerprints[*b]) {
+            (Some(a), Some(b)) => {
+                chromaprint::similarity(a, b, FINGERPRINT_OFFSET) >= FINGERPRINT_SIMILARITY
+            }
+            _ => false,
+        })
+        .map(|((a, b), _)| (a, b, MatchedBy::Fingerprint))
+        .collect();
+    links.sort();
+    links
+}
+
+// Groups songs that are copies of the same recording. Each group is sorted best copy first
+pub fn find_groups(
+    songs: Vec<DuplicateCandidate>,
+) -> Vec<(BTreeSet<MatchedBy>, Vec<DuplicateCandidate>)> {
+    let mut groups = UnionFind::new(songs.len());
+    let mut links: Vec<(usize, usize, MatchedBy)> = Vec::new();
+    let mut seen: HashMap<(MatchedBy, &str), usize> = HashMap::new();
+    for (index, song) in songs.iter().enumerate() {
+        let ids = [
+            (MatchedBy::Musicbrainz, song.mb_recording_id.as_deref()),
+            (MatchedBy::Musicbrainz, song.mb_track_id.as_deref()),
+            (MatchedBy::Isrc, song.isrc.as_deref()),
+            (MatchedBy::Acoustid, song.acoustid_id.as_deref()),
+        ];
+        for (matched_by, id) in ids {
+            let Some(id) = id.map(str::trim).filter(|id| !id.is_empty()) else {
+                continue;
+            };
+            match seen.get(&(matched_by, id)) {
+                Some(first) => links.push((*first, index, matched_by)),
+                None => {
+                    seen.insert((matched_by, id), index);
+                }
+            }
+        }
+    }
+
+    links.extend(fingerprint_links(&songs));
+
+    let mut by_name: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (index, song) in songs.iter().enumerate() {
+        if song.title != PLACEHOLDER_TITLE {
+            by_name
+                .entry((normalize(&song.artist), normalize(&song.title)))
+                .or_default()
+                .push(index);
+        }
+    }
+    // Only across albums, an album can hold several takes of a title. Every song is compared with
+    // the shortest one so lengths can't drift beyond the tolerance through a chain of songs
+    for mut indexes in by_name.into_values().filter(|indexes| indexes.len() > 1) {
+        indexes.sort_by_key(|index| (songs[*index].length, *index));
+        let first = &songs[indexes[0]];
+        for index in &indexes[1..] {
+            let song = &songs[*index];
+            if song.album_id != first.album_id && song.length - first.length <= LENGTH_TOLERANCE {
+                links.push((indexes[0], *index, MatchedBy::Metadata));
+            }
+        }
+    }
+
+    for (a, b, _) in &links {
+        groups.union(*a, *b);
+    }
+    let mut matched_by: HashMap<usize, BTreeSet<MatchedBy>> = HashMap::new();
+    for (a, _, method) in &links {
+        matched_by
+            .entry(groups.find(*a))
+            .or_default()
+            .insert(*method);
+    }
+    let mut members: HashMap<usize, Vec<DuplicateCandidate>> = HashMap::new();
+    for (index, song) in songs.into_iter().enumerate() {
+        let root = groups.find(index);
+        if matched_by.contains_key(&root) {
+            members.entry(root).or_default().push(song);
+        }
+    }
+    let mut result: Vec<(BTreeSet<MatchedBy>, Vec<DuplicateCandidate>)> = members
+        .into_iter()
+        .map(|(root, mut songs)| {
+            songs.sort_by(|a, b| b.quality().cmp(&a.quality()).then(a.path.cmp(&b.path)));
+            (matched_by.remove(&root).unwrap_or_default(), songs)
+        })
+        .collect();
+    result.sort_by(|a, b| a.1[0].path.cmp(&b.1[0].path));
+    result
+}
+
+async fn load_candidates(
+    db: &impl ConnectionTrait,
+    library_id: Option<&str>,
+) -> Result<Vec<DuplicateCandidate>, sea_orm::DbErr> {
+    entity::song::Entity::find()
+        .select_only()
+        .columns([
+            entity::song::Column::Id,
+            entity::song::Column::Path,
+            entity::song::Column::Title,
+            entity::song::Column::Artist,
+            entity::song::Column::AlbumId,
+            entity::song::Column::AlbumName,
+            entity::song::Column::Length,
+            entity::song::Column::Codec,
+            entity::song::Column::SampleRate,
+            entity::song::Column::BitsPerSample,
+            entity::song::Column::BitRate,
+            entity::song::Column::MbRecordingId,
+            entity::song::Column::MbTrackId,
+            entity::song::Column::Isrc,
+            entity::song::Column::AcoustidId,
+            entity::song::Column::AcoustidFingerprint,
+        ])
+        .apply_if(library_id, |query, library_id| {
+            query.filter(entity::song::Column::LibraryId.eq(library_id))
+        })
+        .order_by_asc(entity::song::Column::Path)
+        .into_model::<DuplicateCandidate>()
+        .all(db)
+        .await
+}
+
+// Marks every copy but the best one with duplicate_of. Albums whose songs are all copies of songs on
+// one other album are marked as well. Runs across libraries since the same album is often in several
+pub async fn detect(db: &DatabaseConnection) -> Result<usize, anyhow::Error> {
+    let groups = find_groups(load_candidates(db, None).await?);
+    let mut best_album: HashMap<String, Option<String>> = HashMap::new();
+    let mut duplicates: Vec<(String, Vec<String>)> = Vec::new();
+    for (_, songs) in &groups {
+        let best = &songs[0];
+        for song in songs {
+            best_album.insert(song.id.clone(), best.album_id.clone());
+        }
+        duplicates.push((
+            best.id.clone(),
+            songs[1..].iter().map(|song| song.id.clone()).collect(),
+        ));
+    }
+
+    // Album id and the album of the best copy of each of its songs
+    let album_songs: Vec<(String, Option<String>)> = entity::song::Entity::find()
+        .select_only()
+        .columns([entity::song::Column::Id, entity::song::Column::AlbumId])
+        .into_tuple()
+        .all(db)
+        .await?;
+    let mut albums: HashMap<String, Option<BTreeSet<String>>> = HashMap::new();
+    for (song_id, album_id) in album_songs {
+        let Some(album_id) = album_id else {
+            continue;
+        };
+        let best = best_album
+            .get(&song_id)
+            .cloned()
+            .flatten()
+            .filter(|best| *best != album_id);
+        let entry = albums
+            .entry(album_id)
+            .or_insert_with(|| Some(BTreeSet::new()));
+        match best {
+            Some(best) => {
+                if let Some(targets) = entry {
+                    targets.insert(best);
+                }
+            }
+            // A song without a better copy elsewhere keeps the album listed
+            None => *entry = None,
+        }
+    }
+    let mut duplicate_albums: HashMap<String, Vec<String>> = HashMap::new();
+    for (album_id, targets) in albums {
+        if let Some(targets) = targets.filter(|targets| targets.len() == 1) {
+            let target = targets.into_iter().next().unwrap_or_default();
+            duplicate_albums.entry(target).or_default().push(album_id);
+        }
+    }
+
+    let tx = db.begin().await?;
+    entity::song::Entity::update_many()
+        .col_expr(
+            entity::song::Column::DuplicateOf,
+            Expr::value(Option::<String>::None),
+        )
+        .filter(entity::song::Column::DuplicateOf.is_not_null())
+        .exec(&tx)
+        .await?;
+    entity::album::Entity::update_many()
+        .col_expr(
+            entity::album::Column::DuplicateOf,
+            Expr::value(Option::<String>::None),
+        )
+        .filter(entity::album::Column::DuplicateOf.is_not_null())
+        .exec(&tx)
+        .await?;
+    let mut marked = 0;
+    for (best, copies) in &duplicates {
+        for chunk in copies.chunks(500) {
+            entity::song::Entity::update_many()
+                .col_expr(
+                    entity::song::Column::DuplicateOf,
+                    Expr::value(best.as_str()),
+                )
+                .filter(entity::song::Column::Id.is_in(chunk.iter().map(String::as_str)))
+                .exec(&tx)
+                .await?;
+        }
+        marked += copies.len();
+    }
+    for (best, copies) in &duplicate_albums {
+        entity::album::Entity::update_many()
+            .col_expr(
+                entity::album::Column::DuplicateOf,
+                Expr::value(best.as_str()),
+            )
+            .filter(entity::album::Column::Id.is_in(copies.iter().map(String::as_str)))
+            .exec(&tx)
+            .await?;
+    }
+    tx.commit().await?;
+    tracing::info!(
+        "Found {} duplicate songs in {} groups and {} duplicate albums",
+        marked,
+        groups.len(),
+        duplicate_albums.values().map(Vec::len).sum::<usize>()
+    );
+    Ok(marked)
+}
+
+pub async fn get_duplicates(
+    db: &DatabaseConnection,
+    library_id: Option<&str>,
+    matched_by: Option<&str>,
+) -> Result<Vec<DuplicateGroup>, ApiError> {
+    let matched_by = match matched_by {
+        None => None,
+        Some("musicbrainz") => Some(MatchedBy::Musicbrainz),
+        Some("isrc") => Some(MatchedBy::Isrc),
+        Some("acoustid") => Some(MatchedBy::Acoustid),
+        Some("fingerprint") => Some(MatchedBy::Fingerprint),
+        Some("metadata") => Some(MatchedBy::Metadata),
+        Some(other) => {
+            return Err(ApiError(
+                StatusCode::BAD_REQUEST,
+                anyhow!("Unknown matched_by {}", other),
+            ))
+        }
+    };
+    Ok(find_groups(load_candidates(db, library_id).await?)
+        .into_iter()
+        .filter(|(methods, _)| matched_by.map_or(true, |method| methods.contains(&method)))
+        .map(|(methods, songs)| DuplicateGroup {
+            matched_by: methods.into_iter().collect(),
+            songs: songs
+                .into_iter()
+                .enumerate()
+                .map(|(index, song)| DuplicateSong {
+                    sample_rate: song.sample_rate(),
+                    id: song.id,
+                    path: song.path,
+                    title: song.title,
+                    artist: song.artist,
+                    album_id: song.album_id,
+                    album_name: song.album_name,
+                    length: song.length,
+                    codec: song.codec,
+                    bit_depth: song.bits_per_sample,
+                    bit_rate: song.bit_rate,
+                    best: index == 0,
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: &str, artist: &str, title: &str, length: i32) -> DuplicateCandidate {
+        DuplicateCandidate {
+            id: String::from(id),
+            path: format!("/music/{id}.flac"),
+            title: String::from(title),
+            artist: String::from(artist),
+            album_id: Some(format!("album-{id}")),
+            album_name: String::from("Konvicted"),
+            length,
+            codec: Some(String::from("FLAC")),
+            sample_rate: Some(String::from("44100")),
+            bits_per_sample: Some(16),
+            bit_rate: Some(900),
+            mb_recording_id: None,
+            mb_track_id: None,
+            isrc: None,
+            acoustid_id: None,
+            acoustid_fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("Smack That (feat. Eminem)"),
+            normalize("smack that  feat eminem")
+        );
+    }
+
+    #[test]
+    fn test_find_groups() {
+        let mut hi_res = candidate("hi-res", "Akon", "Smack That", 212);
+        hi_res.bits_per_sample = Some(24);
+        hi_res.sample_rate = Some(String::from("96000"));
+        let mut mp3 = candidate("mp3", "AKON", "smack that", 214);
+        mp3.codec = Some(String::from("MP3"));
+        mp3.bits_per_sample = None;
+        mp3.bit_rate = Some(320);
+        let mut isrc_a = candidate("isrc-a", "Akon", "Lonely", 235);
+        isrc_a.isrc = Some(String::from("USUM70605421"));
+        let mut isrc_b = candidate("isrc-b", "Akon", "Lonely (Radio Edit)", 200);
+        isrc_b.isrc = Some(String::from("USUM70605421"));
+        let songs = vec![
+            mp3,
+            candidate("cd", "Akon", "Smack That", 213),
+            hi_res,
+            // Same title but a different length is a different version
+            candidate("live", "Akon", "Smack That", 260),
+            isrc_a,
+            isrc_b,
+        ];
+        let groups = find_groups(songs);
+        assert_eq!(groups.len(), 2);
+        let ids: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|(_, songs)| songs.iter().map(|song| song.id.as_str()).collect())
+            .collect();
+        assert_eq!(
+            ids,
+            vec![vec!["hi-res", "cd", "mp3"], vec!["isrc-a", "isrc-b"]]
+        );
+        assert_eq!(groups[0].0, BTreeSet::from([MatchedBy::Metadata]));
+        assert_eq!(groups[1].0, BTreeSet::from([MatchedBy::Isrc]));
+    }
+
+    #[test]
+    fn test_find_groups_by_name() {
+        let mut reprise = candidate("reprise", "Akon", "Smack That", 213);
+        reprise.album_id = Some(String::from("album-cd"));
+        let songs = vec![
+            candidate("cd", "Akon", "Smack That", 212),
+            // Same album as cd
+            reprise,
+            // Within the tolerance of the previous song but not of the first one
+            candidate("edit", "Akon", "Smack That", 215),
+            candidate("extended", "Akon", "Smack That", 218),
+        ];
+        let groups = find_groups(songs);
+        let ids: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|(_, songs)| songs.iter().map(|song| song.id.as_str()).collect())
+            .collect();
+        assert_eq!(ids, vec![vec!["cd", "edit"]]);
+    }
+
+    #[test]
+    fn test_find_groups_by_fingerprint() {
+        let mut state: u32 = 7;
+        let original: Vec<u32> = (0..600)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state
+            })
+            .collect();
+        // A lossy copy flips a few bits of every item. Most items still match exactly
+        let lossy: Vec<u32> = original
+            .iter()
+            .enumerate()
+            .map(|(index, item)| match index % 3 {
+                0 => item ^ (1 << (index % 32)),
+                _ => *item,
+            })
+            .collect();
+        let other: Vec<u32> = original.iter().map(|item| item.rotate_left(7)).collect();
+
+        let mut flac = candidate("flac", "Akon", "Smack That", 212);
+        flac.acoustid_fingerprint = Some(chromaprint::encode(&original));
+        let mut mp3 = candidate("mp3", "Unknown Artist", "Track 1", 213);
+        mp3.codec = Some(String::from("MP3"));
+        mp3.acoustid_fingerprint = Some(chromaprint::encode(&lossy));
+        let mut remix = candidate("remix", "Akon", "I Wanna Love You", 212);
+        remix.acoustid_fingerprint = Some(chromaprint::encode(&other));
+        let groups = find_groups(vec![mp3, remix, flac]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].0, BTreeSet::from([MatchedBy::Fingerprint]));
+        let ids: Vec<&str> = groups[0].1.iter().map(|song| song.id.as_str()).collect();
+        assert_eq!(ids, vec!["flac", "mp3"]);
+    }
+}
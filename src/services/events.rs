@@ -0,0 +1,230 @@
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+use utoipa::ToSchema;
+
+// Events are dropped for subscribers that fall this far behind
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Library,
+    Album,
+    Song,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ScanCounters {
+    pub directories_walked: i32,
+    pub songs_added: i32,
+    pub songs_updated: i32,
+    pub songs_removed: i32,
+    pub artists_updated: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    ScanStarted {
+        task_id: String,
+        library_id: Option<String>,
+    },
+    ScanProgress {
+        task_id: String,
+        counters: ScanCounters,
+    },
+    ScanFinished {
+        task_id: String,
+        status: String,
+        counters: ScanCounters,
+    },
+    LibraryItemAdded {
+        kind: ItemKind,
+        id: String,
+        library_id: Option<String>,
+    },
+    LibraryItemRemoved {
+        kind: ItemKind,
+        id: String,
+        library_id: Option<String>,
+    },
+    PlaylistChanged {
+        playlist_id: String,
+    },
+    CastStatus {
+        device_id: String,
+        status: String,
+    },
+    NowPlaying {
+        song_id: String,
+        device_id: Option<String>,
+        user_id: Option<String>,
+    },
+}
+
+impl Event {
+    // Name of the event. Same as the type field and used as the SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::ScanStarted { .. } => "scan_started",
+            Event::ScanProgress { .. } => "scan_progress",
+            Event::ScanFinished { .. } => "scan_finished",
+            Event::LibraryItemAdded { .. } => "library_item_added",
+            Event::LibraryItemRemoved { .. } => "library_item_removed",
+            Event::PlaylistChanged { .. } => "playlist_changed",
+            Event::CastStatus { .. } => "cast_status",
+            Event::NowPlaying { .. } => "now_playing",
+        }
+    }
+
+    // User the event belongs to. Events without a user are sent to everyone
+    pub fn user_id(&self) -> Option<&str> {
+        match self {
+            Event::NowPlaying { user_id, .. } => user_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+// Decides which events a subscriber receives
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    // Only these event names. Every event when empty
+    pub types: Vec<String>,
+    // The subscribing user. Events of other users are skipped
+    pub user_id: Option<String>,
+}
+
+impl EventFilter {
+    // Parses a comma separated list of event names such as "scan_finished,now_playing"
+    pub fn new(types: Option<&str>, user_id: Option<String>) -> Self {
+        EventFilter {
+            types: types
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            user_id,
+        }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        let type_matches = self.types.is_empty() || self.types.iter().any(|t| t == event.name());
+        let user_matches = match event.user_id() {
+            Some(user_id) => self.user_id.as_deref() == Some(user_id),
+            None => true,
+        };
+        type_matches && user_matches
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    sender: Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        EventBus { sender }
+    }
+
+    // Sends the event to every subscriber. Events published without subscribers are dropped
+    pub fn publish(&self, event: Event) {
+        tracing::trace!("Publishing event: {:?}", event);
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+// Waits for the next event matching filter. Returns None once the bus is closed
+pub async fn next_event(receiver: &mut Receiver<Event>, filter: &EventFilter) -> Option<Event> {
+    loop {
+        match receiver.recv().await {
+            Ok(event) if filter.matches(&event) => return Some(event),
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(
+                    "Event subscriber lagged behind. Skipped {:} events",
+                    skipped
+                )
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now_playing(user_id: Option<&str>) -> Event {
+        Event::NowPlaying {
+            song_id: String::from("song"),
+            device_id: None,
+            user_id: user_id.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_filter_types() {
+        let filter = EventFilter::new(Some("scan_finished, now_playing"), None);
+        assert!(filter.matches(&now_playing(None)));
+        assert!(!filter.matches(&Event::PlaylistChanged {
+            playlist_id: String::from("playlist")
+        }));
+        assert!(
+            EventFilter::new(Some(""), None).matches(&Event::PlaylistChanged {
+                playlist_id: String::from("playlist")
+            })
+        );
+    }
+
+    #[test]
+    fn test_filter_user() {
+        let filter = EventFilter::new(None, Some(String::from("alice")));
+        assert!(filter.matches(&now_playing(Some("alice"))));
+        assert!(!filter.matches(&now_playing(Some("bob"))));
+        assert!(filter.matches(&now_playing(None)));
+        assert!(!EventFilter::default().matches(&now_playing(Some("alice"))));
+    }
+
+    #[test]
+    fn test_serialize_event() {
+        let event = Event::LibraryItemAdded {
+            kind: ItemKind::Album,
+            id: String::from("album"),
+            library_id: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"type": "library_item_added", "kind": "album", "id": "album", "library_id": null})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_next_event() {
+        let bus = EventBus::new();
+        let mut receiver = bus.subscribe();
+        bus.publish(Event::PlaylistChanged {
+            playlist_id: String::from("playlist"),
+        });
+        bus.publish(now_playing(None));
+        let filter = EventFilter::new(Some("now_playing"), None);
+        assert_eq!(
+            next_event(&mut receiver, &filter).await,
+            Some(now_playing(None))
+        );
+    }
+}
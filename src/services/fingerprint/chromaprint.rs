@@ -0,0 +1,434 @@
+// Chromaprint compatible audio fingerprints. Implements the default algorithm of libchromaprint so
+// fingerprints match the ones written by fpcalc and picard and can be looked up on AcoustID
+use std::f64::consts::PI;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+// Input has to be mono signed 16 bit PCM at this rate
+pub const SAMPLE_RATE: u32 = 11025;
+// Like fpcalc only the start of a song is fingerprinted
+pub const MAX_DURATION: u32 = 120;
+// Id of the default algorithm (CHROMAPRINT_ALGORITHM_TEST2) stored in compressed fingerprints
+pub const ALGORITHM: u8 = 1;
+
+const FRAME_SIZE: usize = 4096;
+const FRAME_STEP: usize = FRAME_SIZE / 3;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3520.0;
+const NUM_BANDS: usize = 12;
+const CHROMA_FILTER: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+const NORM_THRESHOLD: f64 = 0.01;
+const MAX_FILTER_WIDTH: usize = 16;
+
+// Compressed fingerprints store bit positions in 3 bits with larger values spilling into 5 bits
+const MAX_NORMAL_VALUE: u8 = 7;
+
+// (type, y, height, width) and the quantizer thresholds of every classifier
+const CLASSIFIERS: [(u8, usize, usize, usize, [f64; 3]); 16] = [
+    (0, 4, 3, 15, [1.98215, 2.35817, 2.63523]),
+    (4, 4, 6, 15, [-1.03809, -0.651211, -0.282167]),
+    (1, 0, 4, 16, [-0.298702, 0.119262, 0.558497]),
+    (3, 8, 2, 12, [-0.105439, 0.0153946, 0.135898]),
+    (3, 4, 4, 8, [-0.142891, 0.0258736, 0.200632]),
+    (4, 0, 3, 5, [-0.826319, -0.590612, -0.368214]),
+    (1, 2, 2, 9, [-0.557409, -0.233035, 0.0534525]),
+    (2, 7, 3, 4, [-0.0646826, 0.00620476, 0.0784847]),
+    (2, 6, 2, 16, [-0.192387, -0.029699, 0.215855]),
+    (2, 1, 3, 2, [-0.0397818, -0.00568076, 0.0292026]),
+    (5, 10, 1, 15, [-0.53823, -0.369934, -0.190235]),
+    (3, 6, 2, 10, [-0.124877, 0.0296483, 0.139239]),
+    (2, 1, 1, 14, [-0.101475, 0.0225617, 0.231971]),
+    (3, 5, 6, 4, [-0.0799915, -0.00729616, 0.063262]),
+    (1, 9, 2, 12, [-0.272556, 0.019424, 0.302559]),
+    (3, 4, 2, 14, [-0.164292, -0.0321188, 0.0846339]),
+];
+
+// Radix-2 FFT of Hamming windowed frames
+struct Spectrum {
+    window: Vec<f64>,
+    twiddles: Vec<(f64, f64)>,
+    reversed: Vec<usize>,
+}
+
+impl Spectrum {
+    fn new() -> Self {
+        let bits = FRAME_SIZE.trailing_zeros();
+        Spectrum {
+            window: (0..FRAME_SIZE)
+                .map(|i| {
+                    (0.54 - 0.46 * (2.0 * PI * i as f64 / (FRAME_SIZE - 1) as f64).cos())
+                        / i16::MAX as f64
+                })
+                .collect(),
+            twiddles: (0..FRAME_SIZE / 2)
+                .map(|i| {
+                    let angle = -2.0 * PI * i as f64 / FRAME_SIZE as f64;
+                    (angle.cos(), angle.sin())
+                })
+                .collect(),
+            reversed: (0..FRAME_SIZE)
+                .map(|i| i.reverse_bits() >> (usize::BITS - bits))
+                .collect(),
+        }
+    }
+
+    // Energy of the bins 0..=FRAME_SIZE / 2
+    fn power(&self, frame: &[i16]) -> Vec<f64> {
+        let mut re = vec![0.0; FRAME_SIZE];
+        let mut im = vec![0.0; FRAME_SIZE];
+        for (i, sample) in frame.iter().enumerate() {
+            re[self.reversed[i]] = *sample as f64 * self.window[i];
+        }
+        let mut size = 2;
+        while size <= FRAME_SIZE {
+            let half = size / 2;
+            let stride = FRAME_SIZE / size;
+            for start in (0..FRAME_SIZE).step_by(size) {
+                for k in 0..half {
+                    let (w_re, w_im) = self.twiddles[k * stride];
+                    let (a, b) = (start + k, start + k + half);
+                    let t_re = re[b] * w_re - im[b] * w_im;
+                    let t_im = re[b] * w_im + im[b] * w_re;
+                    re[b] = re[a] - t_re;
+                    im[b] = im[a] - t_im;
+                    re[a] += t_re;
+                    im[a] += t_im;
+                }
+            }
+            size *= 2;
+        }
+        (0..=FRAME_SIZE / 2)
+            .map(|i| re[i] * re[i] + im[i] * im[i])
+            .collect()
+    }
+}
+
+fn freq_to_index(freq: f64) -> usize {
+    (FRAME_SIZE as f64 * freq / SAMPLE_RATE as f64).round() as usize
+}
+
+// Pitch class of every FFT bin between MIN_FREQ and MAX_FREQ
+fn chroma_bands() -> Vec<(usize, usize)> {
+    let min = freq_to_index(MIN_FREQ).max(1);
+    let max = freq_to_index(MAX_FREQ).min(FRAME_SIZE / 2);
+    (min..max)
+        .map(|i| {
+            let freq = i as f64 * SAMPLE_RATE as f64 / FRAME_SIZE as f64;
+            let octave = (freq / (440.0 / 16.0)).log2();
+            (i, (NUM_BANDS as f64 * (octave - octave.floor())) as usize)
+        })
+        .collect()
+}
+
+// Sum of rows x1..x2 and bands y1..y2 of the chroma image. integral has a leading row and column of zeros
+fn area(integral: &[[f64; NUM_BANDS + 1]], x1: usize, y1: usize, x2: usize, y2: usize) -> f64 {
+    integral[x2][y2] - integral[x1][y2] - integral[x2][y1] + integral[x1][y1]
+}
+
+fn classify(integral: &[[f64; NUM_BANDS + 1]], x: usize) -> u32 {
+    let mut bits = 0;
+    for (kind, y, h, w, thresholds) in CLASSIFIERS {
+        let area = |x1, y1, x2, y2| area(integral, x1, y1, x2, y2);
+        let (a, b) = match kind {
+            0 => (area(x, y, x + w, y + h), 0.0),
+            1 => (
+                area(x, y + h / 2, x + w, y + h),
+                area(x, y, x + w, y + h / 2),
+            ),
+            2 => (
+                area(x + w / 2, y, x + w, y + h),
+                area(x, y, x + w / 2, y + h),
+            ),
+            3 => (
+                area(x, y + h / 2, x + w / 2, y + h) + area(x + w / 2, y, x + w, y + h / 2),
+                area(x, y, x + w / 2, y + h / 2) + area(x + w / 2, y + h / 2, x + w, y + h),
+            ),
+            4 => (
+                area(x, y + h / 3, x + w, y + 2 * h / 3),
+                area(x, y, x + w, y + h / 3) + area(x, y + 2 * h / 3, x + w, y + h),
+            ),
+            _ => (
+                area(x + w / 3, y, x + 2 * w / 3, y + h),
+                area(x, y, x + w / 3, y + h) + area(x + 2 * w / 3, y, x + w, y + h),
+            ),
+        };
+        let value = ((1.0 + a) / (1.0 + b)).ln();
+        let quantized = thresholds.iter().filter(|t| value >= **t).count();
+        // Gray code so neighbouring quantizer steps differ by one bit
+        bits = (bits << 2) | [0, 1, 3, 2][quantized];
+    }
+    bits
+}
+
+// Raw fingerprint of mono 16 bit samples at SAMPLE_RATE. One item for every FRAME_STEP samples
+pub fn fingerprint(samples: &[i16]) -> Vec<u32> {
+    let spectrum = Spectrum::new();
+    let bands = chroma_bands();
+    let mut chroma: Vec<[f64; NUM_BANDS]> = Vec::new();
+    let mut start = 0;
+    while start + FRAME_SIZE <= samples.len() {
+        let power = spectrum.power(&samples[start..start + FRAME_SIZE]);
+        let mut features = [0.0; NUM_BANDS];
+        for (bin, band) in &bands {
+            features[*band] += power[*bin];
+        }
+        chroma.push(features);
+        start += FRAME_STEP;
+    }
+
+    let mut integral = vec![[0.0; NUM_BANDS + 1]];
+    for frames in chroma.windows(CHROMA_FILTER.len()) {
+        let mut features = [0.0; NUM_BANDS];
+        for (frame, coefficient) in frames.iter().zip(CHROMA_FILTER) {
+            for (feature, value) in features.iter_mut().zip(frame) {
+                *feature += value * coefficient;
+            }
+        }
+        let norm = features.iter().map(|f| f * f).sum::<f64>().sqrt();
+        let mut row = [0.0; NUM_BANDS + 1];
+        let previous = integral[integral.len() - 1];
+        let mut sum = 0.0;
+        for band in 0..NUM_BANDS {
+            if norm >= NORM_THRESHOLD {
+                sum += features[band] / norm;
+            }
+            row[band + 1] = previous[band + 1] + sum;
+        }
+        integral.push(row);
+    }
+
+    let rows = integral.len() - 1;
+    (0..(rows + 1).saturating_sub(MAX_FILTER_WIDTH))
+        .map(|x| classify(&integral, x))
+        .collect()
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u8, width: usize) {
+        for bit in 0..width {
+            if self.bits % 8 == 0 {
+                self.bytes.push(0);
+            }
+            if value >> bit & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 1 << (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+}
+
+// Compressed and base64 encoded like chromaprint_encode_fingerprint. This is the format of
+// ACOUSTID_FINGERPRINT tags and what AcoustID lookups expect
+pub fn encode(fingerprint: &[u32]) -> String {
+    // Every item is xored with the previous one and stored as the distances between its set bits
+    let mut deltas = Vec::new();
+    let mut previous = 0;
+    for item in fingerprint {
+        let mut value = item ^ previous;
+        let (mut bit, mut last_bit) = (1u8, 0u8);
+        while value != 0 {
+            if value & 1 == 1 {
+                deltas.push(bit - last_bit);
+                last_bit = bit;
+            }
+            value >>= 1;
+            bit += 1;
+        }
+        deltas.push(0);
+        previous = *item;
+    }
+    let len = fingerprint.len();
+    let mut bytes = vec![ALGORITHM, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    let mut normal = BitWriter {
+        bytes: Vec::new(),
+        bits: 0,
+    };
+    let mut exceptional = BitWriter {
+        bytes: Vec::new(),
+        bits: 0,
+    };
+    for delta in deltas {
+        normal.write(delta.min(MAX_NORMAL_VALUE), 3);
+        if delta >= MAX_NORMAL_VALUE {
+            exceptional.write(delta - MAX_NORMAL_VALUE, 5);
+        }
+    }
+    bytes.extend(normal.bytes);
+    bytes.extend(exceptional.bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn read_bits(bytes: &[u8], index: usize, width: usize) -> Option<u8> {
+    let mut value = 0;
+    for bit in 0..width {
+        let position = index * width + bit;
+        let byte = bytes.get(position / 8)?;
+        value |= (byte >> (position % 8) & 1) << bit;
+    }
+    Some(value)
+}
+
+// Inverse of encode. None for malformed fingerprints and fingerprints of other algorithms
+pub fn decode(encoded: &str) -> Option<Vec<u32>> {
+    let bytes = URL_SAFE_NO_PAD.decode(encoded.trim()).ok()?;
+    if bytes.len() < 4 || bytes[0] != ALGORITHM {
+        return None;
+    }
+    let len = (bytes[1] as usize) << 16 | (bytes[2] as usize) << 8 | bytes[3] as usize;
+    let body = &bytes[4..];
+
+    let mut normal = Vec::new();
+    let mut items = 0;
+    while items < len {
+        let value = read_bits(body, normal.len(), 3)?;
+        if value == 0 {
+            items += 1;
+        }
+        normal.push(value);
+    }
+    let exceptional = &body[(normal.len() * 3 + 7) / 8..];
+    let mut spilled = 0;
+
+    let mut fingerprint = Vec::with_capacity(len);
+    let (mut value, mut bit, mut previous) = (0u32, 0u32, 0u32);
+    for delta in normal {
+        if delta == 0 {
+            previous ^= value;
+            fingerprint.push(previous);
+            value = 0;
+            bit = 0;
+            continue;
+        }
+        let mut delta = delta as u32;
+        if delta == MAX_NORMAL_VALUE as u32 {
+            delta += read_bits(exceptional, spilled, 5)? as u32;
+            spilled += 1;
+        }
+        bit += delta;
+        if bit > 32 {
+            return None;
+        }
+        value |= 1 << (bit - 1);
+    }
+    Some(fingerprint)
+}
+
+// Share of matching bits of the best alignment of two fingerprints. Copies of a recording are shifted by
+// the encoder delay so items up to max_offset apart are compared as well
+pub fn similarity(a: &[u32], b: &[u32], max_offset: usize) -> f64 {
+    let mut best = 0.0;
+    for offset in -(max_offset as isize)..=max_offset as isize {
+        let (a, b) = match offset < 0 {
+            true => (a, b.get(offset.unsigned_abs()..).unwrap_or_default()),
+            false => (a.get(offset as usize..).unwrap_or_default(), b),
+        };
+        let len = a.len().min(b.len());
+        if len == 0 {
+            continue;
+        }
+        let errors: u32 = a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum();
+        let score = 1.0 - errors as f64 / (len * 32) as f64;
+        if score > best {
+            best = score;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A few seconds of notes picked by seed with some harmonics and optional noise
+    fn melody(seed: u64, noise: f64) -> Vec<i16> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as f64 / (1u64 << 31) as f64
+        };
+        let note_length = SAMPLE_RATE as usize / 3;
+        let mut samples = Vec::new();
+        for _ in 0..60 {
+            let freq = 110.0 * 2f64.powf((next() * 36.0).floor() / 12.0);
+            for i in 0..note_length {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let tone = (2.0 * PI * freq * t).sin() + 0.5 * (4.0 * PI * freq * t).sin();
+                let sample = 8000.0 * tone + noise * (next() - 0.5) * 2.0;
+                samples.push(sample.clamp(i16::MIN as f64, i16::MAX as f64) as i16);
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn test_fingerprint_length() {
+        let samples = melody(1, 0.0);
+        let frames = (samples.len() - FRAME_SIZE) / FRAME_STEP + 1;
+        assert_eq!(
+            fingerprint(&samples).len(),
+            frames - (CHROMA_FILTER.len() - 1) - (MAX_FILTER_WIDTH - 1)
+        );
+        assert!(fingerprint(&samples[..FRAME_SIZE]).is_empty());
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let raw = fingerprint(&melody(2, 0.0));
+        let encoded = encode(&raw);
+        assert!(encoded.starts_with("AQAA"));
+        assert_eq!(decode(&encoded).unwrap(), raw);
+
+        let edge_cases = vec![0, u32::MAX, 1 << 31, 1, 0x8000_0001, 0];
+        assert_eq!(decode(&encode(&edge_cases)).unwrap(), edge_cases);
+        assert_eq!(decode(&encode(&[])).unwrap(), Vec::<u32>::new());
+        assert!(decode("not a fingerprint").is_none());
+    }
+
+    // Known answers of the libchromaprint test suite, the library fpcalc is built on
+    #[test]
+    fn test_libchromaprint_fixtures() {
+        // test_api.cpp fingerprints 130 blocks of 1024 zero samples at 44100 Hz
+        let silence = vec![0; 130 * 1024 * SAMPLE_RATE as usize / 44100];
+        let raw = fingerprint(&silence);
+        assert_eq!(encode(&raw), "AQAAA0mUaEkSRZEGAA");
+        assert_eq!(decode("AQAAA0mUaEkSRZEGAA").unwrap(), raw);
+
+        // test_fingerprint_compressor.cpp, without the algorithm byte
+        let compressed = [
+            (vec![1], vec![0, 0, 1, 1]),
+            (vec![7], vec![0, 0, 1, 73, 0]),
+            (vec![1 << 6], vec![0, 0, 1, 7, 0]),
+            (vec![1 << 8], vec![0, 0, 1, 7, 2]),
+            (vec![1, 0], vec![0, 0, 2, 65, 0]),
+            (vec![1, 1], vec![0, 0, 2, 1, 0]),
+        ];
+        for (raw, bytes) in compressed {
+            let encoded = encode(&raw);
+            assert_eq!(URL_SAFE_NO_PAD.decode(&encoded).unwrap()[1..], bytes);
+            assert_eq!(decode(&encoded).unwrap(), raw);
+        }
+    }
+
+    #[test]
+    fn test_similarity() {
+        let original = fingerprint(&melody(3, 0.0));
+        let noisy = fingerprint(&melody(3, 1500.0));
+        // Delayed by a bit more than one frame step
+        let mut delayed = vec![0; FRAME_STEP + 300];
+        delayed.extend(melody(3, 0.0));
+        let delayed = fingerprint(&delayed);
+        let other = fingerprint(&melody(4, 0.0));
+
+        assert_eq!(similarity(&original, &original, 0), 1.0);
+        assert!(similarity(&original, &noisy, 0) > 0.85);
+        assert!(similarity(&original, &delayed, 3) > 0.8);
+        assert!(similarity(&original, &other, 3) < 0.75);
+    }
+}
@@ -0,0 +1,100 @@
+use std::process::Stdio;
+
+use anyhow::{anyhow, Context, Result};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait,
+};
+use tokio::process::Command;
+
+use crate::{services::task::history::TaskProgress, SETTINGS};
+
+pub mod chromaprint;
+
+// Decodes the start of the file to the PCM chromaprint expects
+async fn decode_audio(path: &str) -> Result<Vec<i16>> {
+    let output = Command::new(&SETTINGS.ffmpeg_path)
+        .stdin(Stdio::null())
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(path)
+        .arg("-map")
+        .arg("0:a:0")
+        .arg("-t")
+        .arg(chromaprint::MAX_DURATION.to_string())
+        .arg("-ac")
+        .arg("1")
+        .arg("-ar")
+        .arg(chromaprint::SAMPLE_RATE.to_string())
+        .arg("-f")
+        .arg("s16le")
+        .arg("-")
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}", SETTINGS.ffmpeg_path))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffmpeg failed to decode {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+        .collect())
+}
+
+pub async fn fingerprint_file(path: &str) -> Result<String> {
+    let samples = decode_audio(path).await?;
+    tokio::task::spawn_blocking(move || chromaprint::encode(&chromaprint::fingerprint(&samples)))
+        .await
+        .map_err(|err| anyhow!("Fingerprinting {} panicked: {}", path, err))
+}
+
+// Fingerprints every song without an ACOUSTID_FINGERPRINT. Songs ffmpeg can't decode are skipped
+// and retried on the next run. Duplicates are detected again afterwards so matching fingerprints are grouped
+pub async fn fingerprint_songs(
+    db: &DatabaseConnection,
+    library_id: Option<&str>,
+    progress: &TaskProgress,
+) -> Result<()> {
+    let songs: Vec<(String, String)> = entity::song::Entity::find()
+        .select_only()
+        .columns([entity::song::Column::Id, entity::song::Column::Path])
+        .filter(entity::song::Column::AcoustidFingerprint.is_null())
+        .apply_if(library_id, |query, library_id| {
+            query.filter(entity::song::Column::LibraryId.eq(library_id))
+        })
+        .order_by_asc(entity::song::Column::Path)
+        .into_tuple()
+        .all(db)
+        .await?;
+    tracing::info!("Fingerprinting {:} songs", songs.len());
+
+    for (id, path) in songs {
+        progress.check_cancelled()?;
+        let fingerprint = match fingerprint_file(&path).await {
+            Ok(fingerprint) => fingerprint,
+            // ffmpeg is missing. Every other song would fail the same way
+            Err(err) if err.downcast_ref::<std::io::Error>().is_some() => return Err(err),
+            Err(err) => {
+                tracing::warn!("Failed to fingerprint {:}: {:}", path, err);
+                continue;
+            }
+        };
+        entity::song::Entity::update_many()
+            .col_expr(
+                entity::song::Column::AcoustidFingerprint,
+                Expr::value(fingerprint),
+            )
+            .filter(entity::song::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        progress.song_updated();
+    }
+    crate::services::duplicates::detect(db).await?;
+    Ok(())
+}
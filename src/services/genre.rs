@@ -0,0 +1,219 @@
+use anyhow::anyhow;
+use chrono::Utc;
+use hyper::StatusCode;
+use sea_orm::{
+    sea_query::{Alias, Expr, Func, OnConflict, Query},
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, ModelTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, QueryTrait,
+};
+use uuid::Uuid;
+
+use crate::services::scanner::tag_helper::AudioMetadata;
+
+use super::http::error::ApiError;
+
+// Returns the id of the genre or style with name. Creating it when it doesn't exist yet
+async fn get_or_create(
+    db: &impl ConnectionTrait,
+    table: &str,
+    name: &str,
+) -> Result<String, anyhow::Error> {
+    let backend = db.get_database_backend();
+    let existing = db
+        .query_one(
+            backend.build(
+                Query::select()
+                    .column(Alias::new("id"))
+                    .from(Alias::new(table))
+                    .and_where(
+                        Expr::expr(Func::lower(Expr::col(Alias::new("name"))))
+                            .eq(Func::lower(Expr::val(name))),
+                    ),
+            ),
+        )
+        .await?;
+    match existing {
+        Some(row) => Ok(row.try_get("", "id")?),
+        None => {
+            let id: String = Uuid::new_v4().to_string();
+            let init_time = Utc::now().naive_local();
+            db.execute(
+                backend.build(
+                    Query::insert()
+                        .into_table(Alias::new(table))
+                        .columns([
+                            Alias::new("id"),
+                            Alias::new("name"),
+                            Alias::new("created_at"),
+                            Alias::new("updated_at"),
+                        ])
+                        .values_panic([
+                            id.clone().into(),
+                            name.into(),
+                            init_time.into(),
+                            init_time.into(),
+                        ]),
+                ),
+            )
+            .await?;
+            Ok(id)
+        }
+    }
+}
+
+// Links every value to the owner through the join table. Join tables are named {owner}_{table} with columns {owner}_id and {kind}_id
+async fn link(
+    db: &impl ConnectionTrait,
+    owner: &str,
+    owner_id: &str,
+    table: &str,
+    kind: &str,
+    values: &Option<Vec<String>>,
+) -> Result<(), anyhow::Error> {
+    let owner_column = Alias::new(format!("{owner}_id"));
+    let kind_column = Alias::new(format!("{kind}_id"));
+    for name in values.iter().flatten() {
+        let id = get_or_create(db, table, name).await?;
+        db.execute(
+            db.get_database_backend().build(
+                Query::insert()
+                    .into_table(Alias::new(format!("{owner}_{table}")))
+                    .columns([owner_column.clone(), kind_column.clone()])
+                    .values_panic([owner_id.into(), id.into()])
+                    .on_conflict(
+                        OnConflict::columns([owner_column.clone(), kind_column.clone()])
+                            .do_nothing()
+                            .to_owned(),
+                    ),
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+// Links the song to the genres and styles found in its tags. Links from an earlier scan of the song are replaced
+pub async fn link_song(
+    db: &impl ConnectionTrait,
+    song_id: &str,
+    metadata: &AudioMetadata,
+) -> Result<(), anyhow::Error> {
+    entity::song_genre::Entity::delete_many()
+        .filter(entity::song_genre::Column::SongId.eq(song_id))
+        .exec(db)
+        .await?;
+    entity::song_style::Entity::delete_many()
+        .filter(entity::song_style::Column::SongId.eq(song_id))
+        .exec(db)
+        .await?;
+    link(db, "song", song_id, "genres", "genre", &metadata.genre).await?;
+    link(db, "song", song_id, "styles", "style", &metadata.style).await
+}
+
+// Links the album to the genres and styles found in the tags of its first song
+pub async fn link_album(
+    db: &impl ConnectionTrait,
+    album_id: &str,
+    metadata: &AudioMetadata,
+) -> Result<(), anyhow::Error> {
+    link(db, "album", album_id, "genres", "genre", &metadata.genre).await?;
+    link(db, "album", album_id, "styles", "style", &metadata.style).await
+}
+
+pub async fn get_genre_by_id(
+    db: &DatabaseConnection,
+    genre_id: &str,
+) -> Result<entity::genre::Model, ApiError> {
+    match entity::genre::Entity::find_by_id(genre_id)
+        .one(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })? {
+        Some(genre) => Ok(genre),
+        None => Err(ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow!("Unable to find Genre with id: {}", genre_id),
+        )),
+    }
+}
+
+// Returns a vec of genres sorted by name. Paginated when page is provided. Limited to genres with songs in the library when provided
+pub async fn get_genres(
+    db: &DatabaseConnection,
+    page: Option<u64>,
+    size: Option<u64>,
+    library: Option<String>,
+) -> Result<Vec<entity::genre::Model>, ApiError> {
+    let query = entity::genre::Entity::find()
+        .apply_if(library, |query, library| {
+            query.filter(
+                entity::genre::Column::Id.in_subquery(
+                    Query::select()
+                        .column((
+                            entity::song_genre::Entity,
+                            entity::song_genre::Column::GenreId,
+                        ))
+                        .from(entity::song_genre::Entity)
+                        .inner_join(
+                            entity::song::Entity,
+                            Expr::col((entity::song::Entity, entity::song::Column::Id)).equals((
+                                entity::song_genre::Entity,
+                                entity::song_genre::Column::SongId,
+                            )),
+                        )
+                        .and_where(
+                            Expr::col((entity::song::Entity, entity::song::Column::LibraryId))
+                                .eq(library),
+                        )
+                        .to_owned(),
+                ),
+            )
+        })
+        .order_by_asc(entity::genre::Column::Name);
+    Ok(match page {
+        Some(page) => {
+            query
+                .paginate(db, size.unwrap_or(100))
+                .fetch_page(page)
+                .await?
+        }
+        None => query.limit(size).all(db).await?,
+    })
+}
+
+// Returns the albums tagged with the genre
+pub async fn get_genre_albums(
+    db: &DatabaseConnection,
+    genre_id: &str,
+    library: Option<String>,
+) -> Result<Vec<entity::album::Model>, ApiError> {
+    let genre = get_genre_by_id(db, genre_id).await?;
+    Ok(genre
+        .find_related(entity::album::Entity)
+        .apply_if(library, |query, library| {
+            query.filter(entity::album::Column::LibraryId.eq(library))
+        })
+        .order_by_asc(entity::album::Column::Name)
+        .all(db)
+        .await?)
+}
+
+// Returns the songs tagged with the genre
+pub async fn get_genre_songs(
+    db: &DatabaseConnection,
+    genre_id: &str,
+    library: Option<String>,
+) -> Result<Vec<entity::song::Model>, ApiError> {
+    let genre = get_genre_by_id(db, genre_id).await?;
+    Ok(genre
+        .find_related(entity::song::Entity)
+        .apply_if(library, |query, library| {
+            query.filter(entity::song::Column::LibraryId.eq(library))
+        })
+        .order_by_asc(entity::song::Column::AlbumName)
+        .order_by_asc(entity::song::Column::Track)
+        .all(db)
+        .await?)
+}
@@ -0,0 +1,292 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::NaiveDateTime;
+use sha2::{Digest, Sha256};
+use tokio::process::Command;
+
+use crate::{
+    services::{loudness, settings::TranscodingProfile},
+    SETTINGS,
+};
+
+// HLS output of the transcoding profiles. Playlists are built from the song duration and segments are
+// transcoded by ffmpeg the first time they are requested, then served from cache_path
+
+pub const SEGMENT_DURATION: u32 = 10;
+
+// Codecs HLS clients can play from MPEG-TS segments with their RFC 6381 name
+fn hls_codec(codec: &str) -> Option<&'static str> {
+    match codec {
+        "aac" | "libfdk_aac" => Some("mp4a.40.2"),
+        "libmp3lame" | "mp3" => Some("mp4a.40.34"),
+        _ => None,
+    }
+}
+
+// Parses ffmpeg bitrates such as 128k or 1M to bits per second
+fn parse_bitrate(bitrate: &str) -> Option<u64> {
+    let bitrate = bitrate.trim();
+    let (number, multiplier) = match bitrate.char_indices().last()? {
+        (i, 'k' | 'K') => (&bitrate[..i], 1_000),
+        (i, 'm' | 'M') => (&bitrate[..i], 1_000_000),
+        _ => (bitrate, 1),
+    };
+    let bps = number.parse::<f64>().ok()? * multiplier as f64;
+    (bps > 0.0).then_some(bps as u64)
+}
+
+// Profiles that can be offered as HLS variants
+pub fn variants(profiles: &[TranscodingProfile]) -> Vec<&TranscodingProfile> {
+    profiles
+        .iter()
+        .filter(|profile| {
+            hls_codec(&profile.codec).is_some() && parse_bitrate(&profile.bitrate).is_some()
+        })
+        .collect()
+}
+
+pub fn master_playlist(profiles: &[&TranscodingProfile]) -> String {
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:3\n");
+    for profile in profiles {
+        let (Some(codec), Some(bandwidth)) =
+            (hls_codec(&profile.codec), parse_bitrate(&profile.bitrate))
+        else {
+            continue;
+        };
+        let _ = writeln!(
+            playlist,
+            "#EXT-X-STREAM-INF:BANDWIDTH={bandwidth},CODECS=\"{codec}\"\n{}/playlist.m3u8",
+            profile.name
+        );
+    }
+    playlist
+}
+
+// Duration of the song in milliseconds. Uses the exact duration_ms and falls back to the whole seconds of
+// length for songs scanned before it was stored. None when neither is known
+pub fn duration_ms(song: &entity::song::Model) -> Option<u64> {
+    song.duration_ms
+        .filter(|duration| *duration > 0)
+        .map(|duration| duration as u64)
+        .or_else(|| (song.length > 0).then(|| song.length as u64 * 1000))
+}
+
+pub fn segment_count(duration_ms: u64) -> u32 {
+    let segment_ms = u64::from(SEGMENT_DURATION) * 1000;
+    ((duration_ms.max(1) + segment_ms - 1) / segment_ms) as u32
+}
+
+fn is_last_segment(duration_ms: u64, segment: u32) -> bool {
+    segment + 1 >= segment_count(duration_ms)
+}
+
+fn segment_duration_ms(duration_ms: u64, segment: u32) -> u64 {
+    let segment_ms = u64::from(SEGMENT_DURATION) * 1000;
+    duration_ms
+        .saturating_sub(u64::from(segment) * segment_ms)
+        .min(segment_ms)
+}
+
+pub fn media_playlist(duration_ms: u64) -> String {
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{SEGMENT_DURATION}\n\
+        #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n"
+    );
+    for segment in 0..segment_count(duration_ms) {
+        let _ = writeln!(
+            playlist,
+            "#EXTINF:{:.3},\n{segment}.ts",
+            segment_duration_ms(duration_ms, segment) as f64 / 1000.0
+        );
+    }
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+// Segments are cached per profile settings, song version and applied gain so changing a profile,
+// rescanning a changed file or measuring its loudness doesn't serve stale segments
+fn segment_path(
+    cache: &Path,
+    song_id: &str,
+    updated_at: NaiveDateTime,
+    profile: &TranscodingProfile,
+    gain: Option<f64>,
+    segment: u32,
+) -> PathBuf {
+    let settings = serde_json::to_string(profile).unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(settings.as_bytes());
+    hasher.update(updated_at.and_utc().timestamp_micros().to_le_bytes());
+    if let Some(gain) = gain {
+        hasher.update(format!("{:.2}", gain).as_bytes());
+    }
+    let hash = hex::encode(&hasher.finalize()[..8]);
+    cache
+        .join("hls")
+        .join(song_id)
+        .join(format!("{}-{hash}", profile.name))
+        .join(format!("{segment}.ts"))
+}
+
+// Path of the cached segment. Transcodes it first when it isn't cached yet
+pub async fn segment(
+    song: &entity::song::Model,
+    duration_ms: u64,
+    profile: &TranscodingProfile,
+    segment: u32,
+) -> Result<PathBuf> {
+    let gain = profile
+        .replaygain
+        .and_then(|mode| loudness::playback_gain(mode, song));
+    let path = segment_path(
+        Path::new(&SETTINGS.cache_path),
+        &song.id,
+        song.updated_at,
+        profile,
+        gain,
+        segment,
+    );
+    if tokio::fs::try_exists(&path).await.unwrap_or_default() {
+        return Ok(path);
+    }
+    tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+    // Concurrent requests for the same segment each write their own file and the last rename wins
+    let partial = path.with_extension(format!("{}.part", uuid::Uuid::new_v4().simple()));
+    let start = segment * SEGMENT_DURATION;
+    let mut command = Command::new(&SETTINGS.ffmpeg_path);
+    command
+        .stdin(Stdio::null())
+        .arg("-v")
+        .arg("error")
+        .arg("-ss")
+        .arg(start.to_string());
+    // The last segment runs to the end of the file so a duration that is slightly off doesn't cut it short
+    if !is_last_segment(duration_ms, segment) {
+        command.arg("-t").arg(SEGMENT_DURATION.to_string());
+    }
+    command.arg("-i").arg(&song.path).arg("-map").arg("0:a:0");
+    if let Some(gain) = gain {
+        command.arg("-af").arg(format!("volume={:.2}dB", gain));
+    }
+    let output = command
+        .arg("-codec:a")
+        .arg(&profile.codec)
+        .arg("-b:a")
+        .arg(&profile.bitrate)
+        // Keeps timestamps continuous across segments
+        .arg("-output_ts_offset")
+        .arg(start.to_string())
+        .arg("-f")
+        .arg("mpegts")
+        .arg(&partial)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}", SETTINGS.ffmpeg_path))?;
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(anyhow!(
+            "ffmpeg failed to transcode segment {} of {}: {}",
+            segment,
+            song.path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    tokio::fs::rename(&partial, &path).await?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, codec: &str, bitrate: &str) -> TranscodingProfile {
+        TranscodingProfile {
+            name: name.to_string(),
+            codec: codec.to_string(),
+            bitrate: bitrate.to_string(),
+            format: String::from("mp3"),
+            replaygain: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_bitrate() {
+        assert_eq!(parse_bitrate("128k"), Some(128_000));
+        assert_eq!(parse_bitrate("1.5M"), Some(1_500_000));
+        assert_eq!(parse_bitrate("96000"), Some(96_000));
+        assert_eq!(parse_bitrate("fast"), None);
+        assert_eq!(parse_bitrate(""), None);
+    }
+
+    #[test]
+    fn test_master_playlist() {
+        let profiles = [
+            profile("aac_64", "aac", "64k"),
+            profile("opus_96", "libopus", "96k"),
+            profile("mp3_320", "libmp3lame", "320k"),
+        ];
+        let variants = variants(&profiles);
+        assert_eq!(variants.len(), 2);
+        assert_eq!(
+            master_playlist(&variants),
+            "#EXTM3U\n#EXT-X-VERSION:3\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"\naac_64/playlist.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=320000,CODECS=\"mp4a.40.34\"\nmp3_320/playlist.m3u8\n"
+        );
+    }
+
+    #[test]
+    fn test_media_playlist() {
+        assert_eq!(segment_count(25_000), 3);
+        assert_eq!(segment_count(20_000), 2);
+        assert_eq!(segment_count(20_001), 3);
+        assert!(!is_last_segment(25_000, 1));
+        assert!(is_last_segment(25_000, 2));
+        assert_eq!(
+            media_playlist(25_480),
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:10\n\
+            #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n\
+            #EXTINF:10.000,\n0.ts\n#EXTINF:10.000,\n1.ts\n#EXTINF:5.480,\n2.ts\n\
+            #EXT-X-ENDLIST\n"
+        );
+    }
+
+    #[test]
+    fn test_segment_path() {
+        let cache = Path::new("/cache");
+        let updated_at =
+            NaiveDateTime::parse_from_str("2024-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let mp3 = profile("mp3_128", "libmp3lame", "128k");
+        let path = segment_path(cache, "song", updated_at, &mp3, None, 3);
+        assert!(path.starts_with("/cache/hls/song"));
+        assert!(path.ends_with("3.ts"));
+        // Changing the profile, the song or the gain changes the cache folder
+        let higher = TranscodingProfile {
+            bitrate: String::from("192k"),
+            ..mp3.clone()
+        };
+        for other in [
+            segment_path(cache, "song", updated_at, &higher, None, 3),
+            segment_path(
+                cache,
+                "song",
+                updated_at + chrono::Duration::seconds(1),
+                &mp3,
+                None,
+                3,
+            ),
+            segment_path(cache, "song", updated_at, &mp3, Some(-6.5), 3),
+        ] {
+            assert_ne!(path.parent(), other.parent());
+        }
+        assert_eq!(
+            segment_path(cache, "song", updated_at, &mp3, Some(-6.5), 3),
+            segment_path(cache, "song", updated_at, &mp3, Some(-6.5), 3)
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::anyhow;
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use hyper::{header, StatusCode};
+
+use crate::AppState;
+
+use super::error::ApiError;
+
+// Extractor of the /admin endpoints. Requests must send Authorization: Bearer <admin_token>
+// The endpoints are disabled while no admin_token is configured
+pub struct Admin;
+
+#[async_trait]
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(token) = state.admin_token.as_deref() else {
+            return Err(ApiError(
+                StatusCode::FORBIDDEN,
+                anyhow!("Admin endpoints are disabled. Set admin_token to enable them"),
+            ));
+        };
+        let sent = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match sent {
+            Some(sent) if tokens_match(sent, token) => Ok(Admin),
+            _ => Err(ApiError(
+                StatusCode::UNAUTHORIZED,
+                anyhow!("Missing or invalid admin token"),
+            )),
+        }
+    }
+}
+
+// Compares every byte so the time taken doesn't tell how much of the token was right
+fn tokens_match(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret ", "secret"));
+        assert!(!tokens_match("", "secret"));
+    }
+}
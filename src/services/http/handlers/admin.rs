@@ -0,0 +1,194 @@
+use super::{DuplicatesQuery, IssuesQuery};
+use crate::{
+    services::{
+        self,
+        backup::{ImportReport, UserStateExport},
+        duplicates::DuplicateGroup,
+        http::{
+            auth::Admin,
+            error::{ApiError, Status},
+            SuccessResponse,
+        },
+        issues::IssuesResponse,
+    },
+    AppState,
+};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, HeaderValue, Response},
+    Json,
+};
+use chrono::Local;
+use futures::StreamExt;
+use std::path::PathBuf;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+// Exports of large play histories are well over the default body limit
+pub const IMPORT_BODY_LIMIT: usize = 256 * 1024 * 1024;
+
+fn attachment(content_type: &str, filename: &str, body: Body) -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        )
+        .body(body)
+        .unwrap()
+}
+
+// Removes the backup file once it is dropped, whether the response was sent, the client went away or the
+// backup failed
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/backup",
+    responses(
+        (status = 200, description = "Consistent copy of the SQLite database without the users", content_type = "application/vnd.sqlite3", body = Vec<u8>),
+        (status = 501, description = "The server uses PostgreSQL. Use pg_dump instead", body = ErrorResponse<String>),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse<String>),
+        (status = 403, description = "No admin_token is configured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_backup(
+    _admin: Admin,
+    State(state): State<AppState>,
+) -> Result<Response<Body>, ApiError> {
+    let backup =
+        TempFile(std::env::temp_dir().join(format!("deaftone-backup-{}.sqlite", Uuid::new_v4())));
+    services::backup::backup_sqlite(&state.database, &backup.0).await?;
+    // Password hashes never leave the server. Users are added to a restored database with the user command
+    services::backup::remove_users(&backup.0).await?;
+    let file = tokio::fs::File::open(&backup.0).await?;
+    let len = file.metadata().await?.len();
+    // The stream owns the file guard so the backup is removed when the body is dropped
+    let stream = ReaderStream::new(file).map(move |chunk| {
+        let _ = &backup;
+        chunk
+    });
+    let mut response = attachment(
+        "application/vnd.sqlite3",
+        &format!("deaftone-{}.sqlite", Local::now().format("%Y%m%d-%H%M%S")),
+        Body::from_stream(stream),
+    );
+    response
+        .headers_mut()
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/export",
+    responses(
+        (status = 200, description = "Likes, playlists and plays keyed by MusicBrainz track id and path. Can be posted to /admin/import as is", body = UserStateExport),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse<String>),
+        (status = 403, description = "No admin_token is configured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_export(
+    _admin: Admin,
+    State(state): State<AppState>,
+) -> Result<Response<Body>, ApiError> {
+    let export = services::backup::export_user_state(&state.database).await?;
+    Ok(attachment(
+        "application/json",
+        &format!(
+            "deaftone-export-{}.json",
+            Local::now().format("%Y%m%d-%H%M%S")
+        ),
+        Body::from(serde_json::to_vec(&export)?),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/import",
+    request_body = UserStateExport,
+    responses(
+        (status = 200, description = "Imported state. Songs missing from the library are listed and skipped", body = ImportReportResponseOpenApi),
+        (status = 400, description = "Export from a newer version of Deaftone", body = ErrorResponse<String>),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse<String>),
+        (status = 403, description = "No admin_token is configured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn import_user_state(
+    _admin: Admin,
+    State(state): State<AppState>,
+    Json(export): Json<UserStateExport>,
+) -> Result<Json<SuccessResponse<ImportReport>>, ApiError> {
+    let report =
+        services::backup::import_user_state(&state.database, &state.services.events, export)
+            .await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: report,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/issues",
+    params(IssuesQuery),
+    responses(
+        (status = 200, description = "Problems found by the last scans with the number of issues of every kind", body = IssuesResponseOpenApi),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse<String>),
+        (status = 403, description = "No admin_token is configured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_issues(
+    _admin: Admin,
+    State(state): State<AppState>,
+    params: Query<IssuesQuery>,
+) -> Result<Json<SuccessResponse<IssuesResponse>>, ApiError> {
+    let params = params.0;
+    let issues = services::issues::get_issues(
+        &state.database,
+        params.kind,
+        params.library,
+        params.page,
+        params.size,
+    )
+    .await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: issues,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/duplicates",
+    params(DuplicatesQuery),
+    responses(
+        (status = 200, description = "Songs that are copies of the same recording with the best copy first", body = DuplicatesResponseOpenApi),
+        (status = 400, description = "Unknown matched_by", body = ErrorResponse<String>),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse<String>),
+        (status = 403, description = "No admin_token is configured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_duplicates(
+    _admin: Admin,
+    State(state): State<AppState>,
+    params: Query<DuplicatesQuery>,
+) -> Result<Json<SuccessResponse<Vec<DuplicateGroup>>>, ApiError> {
+    let groups = services::duplicates::get_duplicates(
+        &state.database,
+        params.library.as_deref(),
+        params.matched_by.as_deref(),
+    )
+    .await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: groups,
+    }))
+}
@@ -1,4 +1,4 @@
-use super::{AlbumResponse, GetAllAlbums};
+use super::{AlbumQuery, AlbumResponse, GetAllAlbums};
 use crate::{
     services::{
         self,
@@ -25,7 +25,8 @@ use tower_http::services::ServeFile;
     get,
     path = "/albums/{album_id}",
     params(
-        ("album_id" = String, Path, description = "Album Id")
+        ("album_id" = String, Path, description = "Album Id"),
+        AlbumQuery
     ),
     responses(
         (status = 200, description = "Returns a album", body = AlbumResponseOpenApi),
@@ -37,8 +38,13 @@ use tower_http::services::ServeFile;
 pub async fn get_album(
     Path(album_id): Path<String>,
     State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<AlbumQuery>,
 ) -> Result<Json<SuccessResponse<AlbumResponse>>, ApiError> {
-    let (album_model, songs) = services::album::get_album_by_id(&state.database, &album_id).await?;
+    let (album_model, mut songs) =
+        services::album::get_album_by_id(&state.database, &album_id).await?;
+    if params.hide_duplicates.unwrap_or_default() {
+        songs = services::album::hide_duplicate_songs(songs);
+    }
     Ok(Json(SuccessResponse {
         status: Status::Success,
         message: AlbumResponse {
@@ -49,7 +55,7 @@ pub async fn get_album(
             artist_id: album_model.artist_id.unwrap_or_default(),
             year: album_model.year,
             song_count: songs.len() as i32,
-            songs,
+            discs: services::album::group_discs(songs),
         },
     }))
 }
@@ -76,10 +82,21 @@ pub async fn get_albums(
                 params.page,
                 params.size,
                 params.sort,
+                params.library,
+                params.hide_duplicates.unwrap_or_default(),
+            )
+            .await?
+        }
+        _ => {
+            services::album::get_albums(
+                &state.database,
+                params.size,
+                params.sort,
+                params.library,
+                params.hide_duplicates.unwrap_or_default(),
             )
             .await?
         }
-        _ => services::album::get_albums(&state.database, params.size, params.sort).await?,
     };
     Ok(Json(SuccessResponse {
         status: Status::Success,
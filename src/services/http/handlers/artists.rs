@@ -35,6 +35,7 @@ pub async fn get_artist(
 ) -> Result<Json<SuccessResponse<ArtistResponse>>, ApiError> {
     let (artist_model, albums) =
         services::artist::get_artist_by_id(&state.database, &artist_id).await?;
+    let appearances = services::artist::get_artist_appearances(&state.database, &artist_id).await?;
     Ok(Json(SuccessResponse {
         status: Status::Success,
         message: DbArtist {
@@ -57,6 +58,7 @@ pub async fn get_artist(
                 youtube: artist_model.link_youtube,
             },
             albums,
+            appearances,
         },
     }))
 }
@@ -83,10 +85,14 @@ pub async fn get_artists(
                 params.page,
                 params.size,
                 params.sort,
+                params.library,
             )
             .await?
         }
-        _ => services::artist::get_artists(&state.database, params.size, params.sort).await?,
+        _ => {
+            services::artist::get_artists(&state.database, params.size, params.sort, params.library)
+                .await?
+        }
     };
     Ok(Json(SuccessResponse {
         status: Status::Success,
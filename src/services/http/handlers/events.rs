@@ -0,0 +1,91 @@
+use std::convert::Infallible;
+
+use crate::{
+    services::events::{next_event, Event, EventFilter},
+    AppState,
+};
+#[cfg(feature = "websocket")]
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::{
+    extract::{Query, State},
+    response::{
+        sse::{self, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use futures::Stream;
+use tokio::sync::broadcast::Receiver;
+
+use super::EventsQuery;
+
+#[cfg(feature = "websocket")]
+#[utoipa::path(
+    get,
+    path = "/events",
+    params(EventsQuery),
+    responses(
+        (status = 200, description = "Stream of server events. Server-Sent Events unless the request asks for a WebSocket upgrade", body = Event)
+    )
+)]
+pub async fn events(
+    ws: Option<WebSocketUpgrade>,
+    Query(params): Query<EventsQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    let receiver = state.services.events.subscribe();
+    let filter = EventFilter::new(params.types.as_deref(), None);
+    match ws {
+        Some(ws) => ws.on_upgrade(move |socket| forward_events(socket, receiver, filter)),
+        None => event_stream(receiver, filter).into_response(),
+    }
+}
+
+#[cfg(not(feature = "websocket"))]
+#[utoipa::path(
+    get,
+    path = "/events",
+    params(EventsQuery),
+    responses(
+        (status = 200, description = "Stream of server events as Server-Sent Events", body = Event)
+    )
+)]
+pub async fn events(Query(params): Query<EventsQuery>, State(state): State<AppState>) -> Response {
+    let receiver = state.services.events.subscribe();
+    let filter = EventFilter::new(params.types.as_deref(), None);
+    event_stream(receiver, filter).into_response()
+}
+
+fn event_stream(
+    receiver: Receiver<Event>,
+    filter: EventFilter,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let stream = futures::stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        let event = next_event(&mut receiver, &filter).await?;
+        let sse_event = sse::Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .unwrap_or_default();
+        Some((Ok(sse_event), (receiver, filter)))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// Sends every matching event as a JSON text message until the client disconnects
+#[cfg(feature = "websocket")]
+async fn forward_events(mut socket: WebSocket, mut receiver: Receiver<Event>, filter: EventFilter) {
+    loop {
+        tokio::select! {
+            event = next_event(&mut receiver, &filter) => {
+                let Some(event) = event else { break };
+                let Ok(text) = serde_json::to_string(&event) else { continue };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use super::{GetAllGenres, LibraryQuery};
+use crate::{
+    services::{
+        self,
+        http::{
+            error::{ApiError, Status},
+            SuccessResponse,
+        },
+    },
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+#[utoipa::path(
+    get,
+    path = "/genres",
+    params(
+        GetAllGenres
+    ),
+    responses(
+        (status = 200, description = "List containing genres", body = GenresResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_genres(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<GetAllGenres>,
+) -> Result<Json<SuccessResponse<Vec<entity::genre::Model>>>, ApiError> {
+    let genres =
+        services::genre::get_genres(&state.database, params.page, params.size, params.library)
+            .await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: genres,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/genres/{genre_id}/albums",
+    params(
+        ("genre_id" = String, Path, description = "Genre Id"),
+        LibraryQuery
+    ),
+    responses(
+        (status = 200, description = "Albums tagged with the genre", body = AlbumsResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>),
+        (status = 404, description = "Genre not found", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_genre_albums(
+    Path(genre_id): Path<String>,
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<LibraryQuery>,
+) -> Result<Json<SuccessResponse<Vec<entity::album::Model>>>, ApiError> {
+    let albums =
+        services::genre::get_genre_albums(&state.database, &genre_id, params.library).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: albums,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/genres/{genre_id}/songs",
+    params(
+        ("genre_id" = String, Path, description = "Genre Id"),
+        LibraryQuery
+    ),
+    responses(
+        (status = 200, description = "Songs tagged with the genre", body = SongsResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>),
+        (status = 404, description = "Genre not found", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_genre_songs(
+    Path(genre_id): Path<String>,
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<LibraryQuery>,
+) -> Result<Json<SuccessResponse<Vec<entity::song::Model>>>, ApiError> {
+    let songs =
+        services::genre::get_genre_songs(&state.database, &genre_id, params.library).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: songs,
+    }))
+}
@@ -0,0 +1,134 @@
+use super::LibraryRequest;
+use crate::{
+    services::{
+        self,
+        events::{Event, ItemKind},
+        http::{
+            error::{ApiError, Status},
+            SuccessResponse,
+        },
+    },
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+#[utoipa::path(
+    get,
+    path = "/libraries",
+    responses(
+        (status = 200, description = "List containing libraries", body = LibrariesResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_libraries(
+    State(state): State<AppState>,
+) -> Result<Json<SuccessResponse<Vec<entity::library::Model>>>, ApiError> {
+    let libraries = services::library::get_libraries(&state.database).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: libraries,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/libraries",
+    request_body = LibraryRequest,
+    responses(
+        (status = 200, description = "Created library", body = LibraryResponseOpenApi),
+        (status = 400, description = "Invalid library", body = ErrorResponse<String>),
+        (status = 409, description = "Library name already exists", body = ErrorResponse<String>)
+    )
+)]
+pub async fn create_library(
+    State(state): State<AppState>,
+    Json(library): Json<LibraryRequest>,
+) -> Result<Json<SuccessResponse<entity::library::Model>>, ApiError> {
+    let library = services::library::create_library(&state.database, &library).await?;
+    state.services.events.publish(Event::LibraryItemAdded {
+        kind: ItemKind::Library,
+        id: library.id.clone(),
+        library_id: Some(library.id.clone()),
+    });
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: library,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/libraries/{library_id}",
+    params(
+        ("library_id" = String, Path, description = "Library Id")
+    ),
+    responses(
+        (status = 200, description = "Returns a library", body = LibraryResponseOpenApi),
+        (status = 404, description = "Library not found", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_library(
+    Path(library_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<SuccessResponse<entity::library::Model>>, ApiError> {
+    let library = services::library::get_library_by_id(&state.database, &library_id).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: library,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/libraries/{library_id}",
+    params(
+        ("library_id" = String, Path, description = "Library Id")
+    ),
+    request_body = LibraryRequest,
+    responses(
+        (status = 200, description = "Updated library", body = LibraryResponseOpenApi),
+        (status = 400, description = "Invalid library", body = ErrorResponse<String>),
+        (status = 404, description = "Library not found", body = ErrorResponse<String>)
+    )
+)]
+pub async fn update_library(
+    Path(library_id): Path<String>,
+    State(state): State<AppState>,
+    Json(library): Json<LibraryRequest>,
+) -> Result<Json<SuccessResponse<entity::library::Model>>, ApiError> {
+    let library = services::library::update_library(&state.database, &library_id, &library).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: library,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/libraries/{library_id}",
+    params(
+        ("library_id" = String, Path, description = "Library Id")
+    ),
+    responses(
+        (status = 200, description = "Deleted library with its songs and albums", body = MessageResponseOpenApi),
+        (status = 404, description = "Library not found", body = ErrorResponse<String>)
+    )
+)]
+pub async fn delete_library(
+    Path(library_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<SuccessResponse<String>>, ApiError> {
+    services::library::delete_library(&state.database, &library_id).await?;
+    state.services.events.publish(Event::LibraryItemRemoved {
+        kind: ItemKind::Library,
+        id: library_id.clone(),
+        library_id: Some(library_id.clone()),
+    });
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: format!("Deleted library {library_id}"),
+    }))
+}
@@ -1,11 +1,18 @@
 use crate::empty_string_as_none;
 use ::serde::{Deserialize, Serialize};
+use chrono::NaiveDate;
 use utoipa::{IntoParams, ToSchema};
 
+pub mod admin;
 pub mod albums;
 pub mod artists;
+pub mod events;
+pub mod genres;
+pub mod libraries;
 pub mod playlist;
+pub mod settings;
 pub mod songs;
+pub mod stats;
 pub mod streams;
 pub mod tasks;
 
@@ -23,6 +30,13 @@ pub struct AlbumResponse {
     pub album_description: String,
     pub year: i32,
     pub song_count: i32,
+    pub discs: Vec<AlbumDisc>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AlbumDisc {
+    pub disc: i32,
+    pub title: Option<String>,
     pub songs: Vec<entity::song::Model>,
 }
 
@@ -34,6 +48,8 @@ pub struct ArtistResponse {
     pub biography: String,
     pub links: ArtistLinks,
     pub albums: Vec<entity::album::Model>,
+    // Albums by other artists this artist is credited on
+    pub appearances: Vec<entity::album::Model>,
 }
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct ArtistLinks {
@@ -76,6 +92,21 @@ pub struct SongResponse {
     year: i32,
     album_id: String,
     liked: bool,
+    // ReplayGain in dB and linear peaks. Missing until tagged or measured by scan_loudness
+    track_gain: Option<f64>,
+    track_peak: Option<f64>,
+    album_gain: Option<f64>,
+    album_peak: Option<f64>,
+    // Gapless playback info. Missing until the song is rescanned
+    #[schema(example = 9151059)]
+    total_samples: Option<i64>,
+    sample_rate: Option<u32>,
+    // Samples to skip at the start and end of the decoded stream
+    encoder_delay: Option<i32>,
+    encoder_padding: Option<i32>,
+    // Exact duration where length is truncated to whole seconds
+    #[schema(example = 207507)]
+    duration_ms: Option<i64>,
 }
 
 #[derive(Deserialize, Clone, IntoParams, ToSchema)]
@@ -87,17 +118,52 @@ pub struct GetAllArtists {
     size: Option<u64>,
     #[serde(default, deserialize_with = "empty_string_as_none")]
     page: Option<u64>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    library: Option<String>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct GetAllGenres {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    size: Option<u64>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    page: Option<u64>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    library: Option<String>,
 }
 #[derive(Deserialize, Clone, IntoParams, ToSchema)]
 pub struct TaskQuery {
     #[serde(default, deserialize_with = "empty_string_as_none")]
-    // #[schema(example = "sort = name | latest")]
+    #[schema(example = "scan_library_full")]
     task: Option<String>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    library: Option<String>,
 }
 
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct EventsQuery {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    #[schema(example = "scan_finished,now_playing")]
+    types: Option<String>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct TranscodeQuery {
+    // Name of a transcoding profile from the runtime settings. The first profile when missing
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    #[schema(example = "mp3_128")]
+    profile: Option<String>,
+}
 #[derive(Serialize, ToSchema)]
 pub struct TaskResponse {
     status: String,
+    id: String,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct GetTasks {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    #[schema(example = "queued | running | succeeded | failed")]
+    status: Option<String>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    size: Option<u64>,
 }
 #[derive(Deserialize, Clone, IntoParams, ToSchema)]
 pub struct GetAllAlbums {
@@ -108,4 +174,81 @@ pub struct GetAllAlbums {
     size: Option<u64>,
     #[serde(default, deserialize_with = "empty_string_as_none")]
     page: Option<u64>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    library: Option<String>,
+    // Leaves out albums whose songs are all lower quality copies of another album
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    hide_duplicates: Option<bool>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct AlbumQuery {
+    // Leaves out lower quality copies of songs on the same album
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    hide_duplicates: Option<bool>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct DuplicatesQuery {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    library: Option<String>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    #[schema(example = "matched_by = musicbrainz | isrc | acoustid | fingerprint | metadata")]
+    matched_by: Option<String>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct StatsQuery {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    #[schema(example = "2023-01-01")]
+    from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    #[schema(example = "2023-12-31")]
+    to: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    limit: Option<u64>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    // Weeks are named after their Monday
+    #[schema(example = "interval = day | week | month | year")]
+    interval: Option<String>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct ScrobbleQuery {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    duration: Option<i32>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct LyricsQuery {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    #[schema(example = "en")]
+    language: Option<String>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct IssuesQuery {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    #[schema(example = "missing_tags")]
+    kind: Option<String>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    library: Option<String>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    size: Option<u64>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    page: Option<u64>,
+}
+#[derive(Deserialize, Clone, IntoParams, ToSchema)]
+pub struct LibraryQuery {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    library: Option<String>,
+}
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct LibraryRequest {
+    #[schema(example = "Music")]
+    pub name: String,
+    #[schema(example = "/mnt/music")]
+    pub path: String,
+    #[serde(default)]
+    #[schema(example = "6h")]
+    pub scan_schedule: Option<String>,
+    #[serde(default)]
+    #[schema(example = json!(["**/*.flac"]))]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
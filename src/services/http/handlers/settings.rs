@@ -0,0 +1,66 @@
+use crate::{
+    services::{
+        http::{
+            error::{ApiError, Status},
+            SuccessResponse,
+        },
+        settings::RuntimeSettings,
+    },
+    settings::Settings,
+    AppState, SETTINGS,
+};
+use axum::{extract::State, Json};
+
+#[utoipa::path(
+    get,
+    path = "/settings",
+    responses(
+        (status = 200, description = "Settings the server was started with after environment overrides", body = SettingsResponseOpenApi),
+    )
+)]
+pub async fn get_settings() -> Json<SuccessResponse<Settings>> {
+    Json(SuccessResponse {
+        status: Status::Success,
+        message: SETTINGS.clone(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/settings/runtime",
+    responses(
+        (status = 200, description = "Settings that can be changed without a restart", body = RuntimeSettingsResponseOpenApi),
+    )
+)]
+pub async fn get_runtime_settings(
+    State(state): State<AppState>,
+) -> Json<SuccessResponse<RuntimeSettings>> {
+    Json(SuccessResponse {
+        status: Status::Success,
+        message: state.services.settings.current(),
+    })
+}
+
+#[utoipa::path(
+    put,
+    path = "/settings/runtime",
+    request_body = RuntimeSettings,
+    responses(
+        (status = 200, description = "Stored settings. Running services use them straight away", body = RuntimeSettingsResponseOpenApi),
+        (status = 400, description = "Invalid settings", body = ErrorResponse<String>)
+    )
+)]
+pub async fn update_runtime_settings(
+    State(state): State<AppState>,
+    Json(settings): Json<RuntimeSettings>,
+) -> Result<Json<SuccessResponse<RuntimeSettings>>, ApiError> {
+    let settings = state
+        .services
+        .settings
+        .update(&state.database, settings)
+        .await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: settings,
+    }))
+}
@@ -5,6 +5,7 @@ use crate::{
             error::{ApiError, Status},
             SuccessResponse,
         },
+        lyrics::Lyrics,
     },
     AppState,
 };
@@ -13,7 +14,7 @@ use axum::{
     Json,
 };
 
-use super::{LikeResponse, SongResponse};
+use super::{LikeResponse, LyricsQuery, ScrobbleQuery, SongResponse};
 
 #[utoipa::path(
     get,
@@ -41,10 +42,19 @@ pub async fn get_song(
             disk: song.disk.unwrap_or_default(),
             artist: song.artist,
             album_name: song.album_name,
-            length: song.length,
+            length: song.length as u32,
             year: song.year.unwrap_or_default(),
             album_id: song.album_id.unwrap_or_default(),
             liked: song.liked,
+            track_gain: song.track_gain,
+            track_peak: song.track_peak,
+            album_gain: song.album_gain,
+            album_peak: song.album_peak,
+            total_samples: song.total_samples,
+            sample_rate: song.sample_rate.and_then(|rate| rate.parse().ok()),
+            encoder_delay: song.encoder_delay,
+            encoder_padding: song.encoder_padding,
+            duration_ms: song.duration_ms,
         },
     }))
 }
@@ -59,3 +69,52 @@ pub async fn like_song(
         message: LikeResponse { liked },
     }))
 }
+
+#[utoipa::path(
+    post,
+    path = "/songs/{song_id}/scrobble",
+    params(
+        ("song_id" = String, Path, description = "Song Id"),
+        ScrobbleQuery
+    ),
+    responses(
+        (status = 200, description = "Play recorded", body = entity::play::Model),
+        (status = 404, description = "Song not found", body = ErrorResponse<String>)
+    )
+)]
+pub async fn scrobble_song(
+    State(state): State<AppState>,
+    Path(song_id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<ScrobbleQuery>,
+) -> Result<Json<SuccessResponse<entity::play::Model>>, ApiError> {
+    let play = services::song::scrobble_song(&state.database, &song_id, params.duration).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: play,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/songs/{song_id}/lyrics",
+    params(
+        ("song_id" = String, Path, description = "Song Id"),
+        LyricsQuery
+    ),
+    responses(
+        (status = 200, description = "Embedded and sidecar lyrics of the song. Synced lyrics first", body = LyricsResponseOpenApi),
+        (status = 404, description = "Song not found", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_song_lyrics(
+    State(state): State<AppState>,
+    Path(song_id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<LyricsQuery>,
+) -> Result<Json<SuccessResponse<Vec<Lyrics>>>, ApiError> {
+    let lyrics =
+        services::lyrics::get_lyrics(&state.database, &song_id, params.language.as_deref()).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: lyrics,
+    }))
+}
@@ -0,0 +1,182 @@
+use super::StatsQuery;
+use crate::{
+    services::{
+        self,
+        http::{
+            error::{ApiError, Status},
+            SuccessResponse,
+        },
+        stats::{FormatBreakdown, LibraryGrowth, ListeningTime, StatsWindow, TopItem},
+    },
+    AppState,
+};
+use axum::{extract::State, Json};
+
+impl StatsQuery {
+    fn window(&self) -> StatsWindow {
+        StatsWindow {
+            from: self.from,
+            to: self.to,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/top/artists",
+    params(
+        StatsQuery
+    ),
+    responses(
+        (status = 200, description = "Most played artists", body = TopItemsResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_top_artists(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<StatsQuery>,
+) -> Result<Json<SuccessResponse<Vec<TopItem>>>, ApiError> {
+    let artists =
+        services::stats::get_top_artists(&state.database, &params.window(), params.limit).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: artists,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/top/albums",
+    params(
+        StatsQuery
+    ),
+    responses(
+        (status = 200, description = "Most played albums", body = TopItemsResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_top_albums(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<StatsQuery>,
+) -> Result<Json<SuccessResponse<Vec<TopItem>>>, ApiError> {
+    let albums =
+        services::stats::get_top_albums(&state.database, &params.window(), params.limit).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: albums,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/top/songs",
+    params(
+        StatsQuery
+    ),
+    responses(
+        (status = 200, description = "Most played songs", body = TopItemsResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_top_songs(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<StatsQuery>,
+) -> Result<Json<SuccessResponse<Vec<TopItem>>>, ApiError> {
+    let songs =
+        services::stats::get_top_songs(&state.database, &params.window(), params.limit).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: songs,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/top/genres",
+    params(
+        StatsQuery
+    ),
+    responses(
+        (status = 200, description = "Most played genres", body = TopItemsResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_top_genres(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<StatsQuery>,
+) -> Result<Json<SuccessResponse<Vec<TopItem>>>, ApiError> {
+    let genres =
+        services::stats::get_top_genres(&state.database, &params.window(), params.limit).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: genres,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/listening",
+    params(
+        StatsQuery
+    ),
+    responses(
+        (status = 200, description = "Listening time per interval", body = ListeningTimeResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_listening_time(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<StatsQuery>,
+) -> Result<Json<SuccessResponse<Vec<ListeningTime>>>, ApiError> {
+    let listening = services::stats::get_listening_time(
+        &state.database,
+        &params.window(),
+        params.interval.as_deref(),
+    )
+    .await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: listening,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/growth",
+    params(
+        StatsQuery
+    ),
+    responses(
+        (status = 200, description = "Library growth per interval", body = LibraryGrowthResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_library_growth(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<StatsQuery>,
+) -> Result<Json<SuccessResponse<Vec<LibraryGrowth>>>, ApiError> {
+    let growth =
+        services::stats::get_library_growth(&state.database, params.interval.as_deref()).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: growth,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/formats",
+    responses(
+        (status = 200, description = "Songs grouped by codec, sample rate and bit depth", body = FormatBreakdownResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_format_breakdown(
+    State(state): State<AppState>,
+) -> Result<Json<SuccessResponse<Vec<FormatBreakdown>>>, ApiError> {
+    let formats = services::stats::get_format_breakdown(&state.database).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: formats,
+    }))
+}
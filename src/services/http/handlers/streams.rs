@@ -1,19 +1,26 @@
 use std::{process::Stdio, str::FromStr};
 
 use crate::{
-    services::{self, http::error::ApiError},
-    AppState,
+    services::{
+        self,
+        archive::{self, ArchiveEntry},
+        events::Event,
+        hls,
+        http::error::ApiError,
+        settings::TranscodingProfile,
+    },
+    AppState, SETTINGS,
 };
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::Request,
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, Method, Request},
     response::{IntoResponse, Response},
     Json,
 };
 
-use super::TestResponse;
+use super::{TestResponse, TranscodeQuery};
 use futures::StreamExt;
 use hyper::StatusCode;
 use rust_cast::{
@@ -37,35 +44,336 @@ const DEFAULT_DESTINATION_ID: &str = "receiver-0";
     ),
     responses(
         (status = 200, description = "Returns a song stream", body = BoxBody),
-        (status = 404, description = "Song not found", body = String)
+        (status = 206, description = "Returns the requested byte range of the song", body = BoxBody),
+        (status = 304, description = "Song unchanged since the ETag or date the client sent"),
+        (status = 404, description = "Song not found", body = String),
+        (status = 416, description = "Requested range is outside the song")
 
     )
 )]
 pub async fn stream_handler(
     Path(song_id): Path<String>,
     State(state): State<AppState>,
+    request: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let song = services::song::get_song_by_id(&state.database, &song_id).await?;
+    let starts_playback = starts_playback(&request);
+    let response = serve_file(&song.path, request).await?;
+    if starts_playback && response.status().is_success() {
+        state.services.events.publish(Event::NowPlaying {
+            song_id: song.id,
+            device_id: None,
+            user_id: None,
+        });
+    }
+    Ok(response)
+}
+
+// Players fetch a song with many range requests while seeking and buffering. Only a GET of the start of
+// the file counts as playback starting
+fn starts_playback(request: &Request<Body>) -> bool {
+    request.method() == Method::GET
+        && request
+            .headers()
+            .get(header::RANGE)
+            .map_or(true, |range| range.as_bytes().starts_with(b"bytes=0-"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/songs/{song_id}/download",
+    params(
+        ("song_id" = String, Path, description = "Song Id")
+    ),
+    responses(
+        (status = 200, description = "Returns the song file as an attachment", body = BoxBody),
+        (status = 206, description = "Returns the requested byte range of the song", body = BoxBody),
+        (status = 404, description = "Song not found", body = String)
+    )
+)]
+pub async fn download_song(
+    Path(song_id): Path<String>,
+    State(state): State<AppState>,
+    request: Request<Body>,
 ) -> Result<Response<Body>, ApiError> {
-    let res: Request<Body> = Request::builder().uri("/").body(Body::empty()).unwrap();
     let song = services::song::get_song_by_id(&state.database, &song_id).await?;
+    let mut response = serve_file(&song.path, request).await?;
+    let extension = std::path::Path::new(&song.path)
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    response.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        attachment(&format!("{} - {}{}", song.artist, song.title, extension)),
+    );
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/albums/{album_id}/download",
+    params(
+        ("album_id" = String, Path, description = "Album Id")
+    ),
+    responses(
+        (status = 200, description = "Returns a zip of the album songs", body = BoxBody),
+        (status = 404, description = "Album or one of its files not found", body = String),
+        (status = 413, description = "Album is too large for a zip archive", body = String)
+    )
+)]
+pub async fn download_album(
+    Path(album_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response<Body>, ApiError> {
+    let (album, songs) = services::album::get_album_by_id(&state.database, &album_id).await?;
+    let discs = services::album::group_discs(songs);
+    let paths: Vec<(i32, Vec<&str>)> = discs
+        .iter()
+        .map(|disc| {
+            (
+                disc.disc,
+                disc.songs.iter().map(|song| song.path.as_str()).collect(),
+            )
+        })
+        .collect();
+    let mut entries = Vec::new();
+    for (name, path) in archive::album_entry_names(&paths)
+        .into_iter()
+        .zip(paths.iter().flat_map(|(_, paths)| paths))
+    {
+        let entry = ArchiveEntry::new(name, path.to_string())
+            .await
+            .map_err(|err| {
+                ApiError(
+                    StatusCode::NOT_FOUND,
+                    anyhow!("Unable to read {}: {}", path, err),
+                )
+            })?;
+        entries.push(entry);
+    }
+    let size = archive::archive_size(&entries)
+        .map_err(|err| ApiError(StatusCode::PAYLOAD_TOO_LARGE, err))?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_LENGTH, size)
+        .header(
+            header::CONTENT_DISPOSITION,
+            attachment(&format!("{} - {}.zip", album.artist_name, album.name)),
+        )
+        .body(Body::from_stream(archive::stream(entries)))
+        .unwrap())
+}
 
-    match ServeFile::new(&song.path).oneshot(res).await {
+const HLS_PLAYLIST: &str = "application/vnd.apple.mpegurl";
+
+fn playlist_response(playlist: String) -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, HLS_PLAYLIST)
+        .body(Body::from(playlist))
+        .unwrap()
+}
+
+// Segments are cut from the song duration so songs of unknown length can't be streamed over HLS
+fn hls_duration(song: &entity::song::Model) -> Result<u64, ApiError> {
+    hls::duration_ms(song).ok_or_else(|| {
+        ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow!("Length of song {} is unknown. Scan it again", song.id),
+        )
+    })
+}
+
+fn hls_profile(state: &AppState, name: &str) -> Result<TranscodingProfile, ApiError> {
+    let settings = state.services.settings.current();
+    hls::variants(&settings.transcoding_profiles)
+        .into_iter()
+        .find(|profile| profile.name == name)
+        .cloned()
+        .ok_or_else(|| {
+            ApiError(
+                StatusCode::NOT_FOUND,
+                anyhow!("No HLS transcoding profile named: {}", name),
+            )
+        })
+}
+
+#[utoipa::path(
+    get,
+    path = "/stream/hls/{song_id}/master.m3u8",
+    params(
+        ("song_id" = String, Path, description = "Song Id")
+    ),
+    responses(
+        (status = 200, description = "HLS master playlist with a variant per transcoding profile", body = String),
+        (status = 404, description = "Song not found or no transcoding profile usable with HLS", body = String)
+    )
+)]
+pub async fn hls_master_playlist(
+    Path(song_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response<Body>, ApiError> {
+    services::song::get_song_by_id(&state.database, &song_id).await?;
+    let settings = state.services.settings.current();
+    let variants = hls::variants(&settings.transcoding_profiles);
+    if variants.is_empty() {
+        return Err(ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow!("No transcoding profile uses a codec HLS supports"),
+        ));
+    }
+    Ok(playlist_response(hls::master_playlist(&variants)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stream/hls/{song_id}/{profile}/playlist.m3u8",
+    params(
+        ("song_id" = String, Path, description = "Song Id"),
+        ("profile" = String, Path, description = "Transcoding profile name")
+    ),
+    responses(
+        (status = 200, description = "HLS media playlist of the song segments", body = String),
+        (status = 404, description = "Song or profile not found or the song length is unknown", body = String)
+    )
+)]
+pub async fn hls_media_playlist(
+    Path((song_id, profile)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Response<Body>, ApiError> {
+    let song = services::song::get_song_by_id(&state.database, &song_id).await?;
+    hls_profile(&state, &profile)?;
+    Ok(playlist_response(hls::media_playlist(hls_duration(&song)?)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stream/hls/{song_id}/{profile}/{segment}",
+    params(
+        ("song_id" = String, Path, description = "Song Id"),
+        ("profile" = String, Path, description = "Transcoding profile name"),
+        ("segment" = String, Path, description = "Segment file such as 0.ts")
+    ),
+    responses(
+        (status = 200, description = "MPEG-TS segment of the song", body = BoxBody),
+        (status = 404, description = "Song, profile or segment not found", body = String)
+    )
+)]
+pub async fn hls_segment(
+    Path((song_id, profile, segment)): Path<(String, String, String)>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let song = services::song::get_song_by_id(&state.database, &song_id).await?;
+    let profile = hls_profile(&state, &profile)?;
+    let duration_ms = hls_duration(&song)?;
+    let Some(index) = segment
+        .strip_suffix(".ts")
+        .and_then(|index| index.parse::<u32>().ok())
+        .filter(|index| *index < hls::segment_count(duration_ms))
+    else {
+        return Err(ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow!("Unknown segment {} of song {}", segment, song_id),
+        ));
+    };
+    let path = hls::segment(&song, duration_ms, &profile, index).await?;
+    let mut response = serve_file(&path.to_string_lossy(), request).await?;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("video/mp2t"));
+    Ok(response)
+}
+
+// Serves a file for the client request so Range, HEAD and conditional requests work. ServeFile handles
+// dates and ranges, ETags are checked here
+async fn serve_file(path: &str, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+    let not_found = || ApiError(StatusCode::NOT_FOUND, anyhow!("File not found: {}", path));
+    let metadata = tokio::fs::metadata(path).await.map_err(|_| not_found())?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let etag = HeaderValue::from_str(&format!(
+        "\"{:x}-{:x}\"",
+        metadata.len(),
+        modified.as_nanos()
+    ))
+    .unwrap();
+    if if_none_match(&request, &etag) {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    let (parts, _) = request.into_parts();
+    match ServeFile::new(path)
+        .oneshot(Request::from_parts(parts, Body::empty()))
+        .await
+    {
         Ok(res) => {
             if res.status() == StatusCode::NOT_FOUND {
-                Err(ApiError(
-                    StatusCode::NOT_FOUND,
-                    anyhow!("File not found: {}", song.path),
-                ))
+                Err(not_found())
             } else {
-                Ok(Body::new(res).into_response())
+                let mut res = res.map(Body::new);
+                if res.status().is_success() {
+                    res.headers_mut().insert(header::ETAG, etag);
+                    res.headers_mut()
+                        .insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+                }
+                Ok(res)
             }
         }
         Err(err) => Err(ApiError(
             StatusCode::INTERNAL_SERVER_ERROR,
-            anyhow!("Unable to play song: {}. Err: {}", song.path, err),
+            anyhow!("Unable to play song: {}. Err: {}", path, err),
         )),
     }
 }
 
+fn if_none_match(request: &Request<Body>, etag: &HeaderValue) -> bool {
+    let Some(value) = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let etag = etag.to_str().unwrap_or_default();
+    value.split(',').map(str::trim).any(|tag| {
+        // Weak comparison as RFC 9110 asks for If-None-Match
+        tag == "*" || tag.trim_start_matches("W/") == etag
+    })
+}
+
+// Content-Disposition with an ASCII fallback name and the UTF-8 name for clients that understand RFC 6266
+pub fn attachment(filename: &str) -> HeaderValue {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' | '/' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    ))
+    .unwrap()
+}
+
 pub async fn cast_handler(
     Path(device_id): Path<String>,
     State(state): State<AppState>,
@@ -77,6 +385,7 @@ pub async fn cast_handler(
         .get_cast_device_by_id(&device_id)
         .await?;
     let song = services::song::get_song_by_id(&state.database, song_id).await?;
+    let song_id = song.id.clone();
     let cast_device = match CastDevice::connect_without_host_verification(device.address_v4, 8009) {
         Ok(cast_device) => cast_device,
         Err(err) => panic!("Could not establish connection with Cast Device: {:?}", err),
@@ -95,11 +404,20 @@ pub async fn cast_handler(
     play_media(
         &cast_device,
         &CastDeviceApp::from_str("default").unwrap(),
-        "http://192.168.1.2:3030/stream/8e2c1c9c-9797-41de-b667-d8dc6ae40c83".to_owned(),
+        format!("{}/stream/{}", SETTINGS.public_base_url(), song_id),
         media_type.to_owned(),
         media_stream_type,
         song,
     );
+    state.services.events.publish(Event::CastStatus {
+        device_id: device_id.clone(),
+        status: String::from("playing"),
+    });
+    state.services.events.publish(Event::NowPlaying {
+        song_id,
+        device_id: Some(device_id),
+        user_id: None,
+    });
 
     Ok(Json(TestResponse {
         state: "test".to_owned(),
@@ -153,28 +471,47 @@ fn play_media(
 
 pub async fn transcode_stream_handler(
     Path(song_id): Path<String>,
+    Query(params): Query<TranscodeQuery>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let settings = state.services.settings.current();
+    let Some(profile) = settings.transcoding_profile(params.profile.as_deref()) else {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            anyhow!(
+                "Unknown transcoding profile: {}",
+                params.profile.unwrap_or_default()
+            ),
+        ));
+    };
     //"G:\\aa\\B\\Billie Eilish\\Billie Eilish - Happier Than Ever [2021] - WEB FLAC\\07. Lost Cause.flac"
     let song = services::song::get_song_by_id(&state.database, &song_id).await?;
-    let mut child = Command::new("ffmpeg")
+    let gain = profile
+        .replaygain
+        .and_then(|mode| services::loudness::playback_gain(mode, &song));
+    let mut command = Command::new(&SETTINGS.ffmpeg_path);
+    command
         .stdout(Stdio::piped())
         .stdin(Stdio::piped())
         .arg("-v")
         .arg("0")
         .arg("-i")
-        .arg(song.path)
+        .arg(&song.path)
         .arg("-map")
-        .arg("0:a:0")
+        .arg("0:a:0");
+    if let Some(gain) = gain {
+        command.arg("-af").arg(format!("volume={:.2}dB", gain));
+    }
+    let mut child = command
         .arg("-codec:a")
-        .arg("libmp3lame")
+        .arg(&profile.codec)
         .arg("-b:a")
-        .arg("128k")
+        .arg(&profile.bitrate)
         .arg("-f")
-        .arg("mp3")
+        .arg(&profile.format)
         .arg("-")
         .spawn()
-        .unwrap();
+        .with_context(|| format!("Failed to run {}", SETTINGS.ffmpeg_path))?;
 
     //    let mut stdin = child.stdin.take().unwrap();
     let stdout = child.stdout.take().unwrap();
@@ -1,56 +1,55 @@
 use crate::{
-    services::{http::error::ApiError, scanner::ScanType, task::TaskType},
+    services::{
+        self,
+        http::{
+            error::{ApiError, Status},
+            SuccessResponse,
+        },
+        task::{history::TaskStatus, TaskCommand, TaskType},
+    },
     AppState,
 };
 use anyhow::anyhow;
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
 use hyper::StatusCode;
-use tokio::sync::mpsc::Sender;
 
-use super::{TaskQuery, TaskResponse};
+use super::{GetTasks, TaskQuery, TaskResponse};
+
+#[utoipa::path(
+    post,
+    path = "/tasks",
+    params(TaskQuery),
+    responses(
+        (status = 200, description = "Queued task", body = TaskResponse),
+        (status = 400, description = "Invalid task", body = ErrorResponse<String>),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
 pub async fn handle_task(
-    axum::extract::Query(params): axum::extract::Query<TaskQuery>,
+    Query(params): Query<TaskQuery>,
     State(state): State<AppState>,
 ) -> Result<Json<TaskResponse>, ApiError> {
-    match params.task {
-        Some(task) => match task.as_str() {
-            "scan_library_full" => {
-                send_task(
-                    TaskType::ScanLibrary(ScanType::FullScan),
-                    state.services.task,
-                )
-                .await
-            }
-            "scan_library_partial" => {
-                send_task(
-                    TaskType::ScanLibrary(ScanType::PartialScan),
-                    state.services.task,
-                )
-                .await
-            }
-            "scan_metadata" => send_task(TaskType::PopulateMetadata, state.services.task).await,
-
-            _ => Err(ApiError(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                anyhow!("Invalid task type: {}", task),
-            )),
-        },
-        None => Err(ApiError(
-            StatusCode::INTERNAL_SERVER_ERROR,
+    let Some(task) = params.task else {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
             anyhow!("Missing param type"),
-        )),
-    }
-}
-
-async fn send_task(
-    task: TaskType,
-    task_service: Sender<TaskType>,
-) -> Result<Json<TaskResponse>, ApiError> {
-    match task_service.send(task).await {
-        Ok(_) => {
+        ));
+    };
+    let Some(task_type) = TaskType::from_name(&task, params.library) else {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Invalid task type: {}", task),
+        ));
+    };
+    match services::task::enqueue(&state.database, &state.services.task, task_type).await {
+        Ok(id) => {
             tracing::info!("Command sent to TaskService");
             Ok(Json(TaskResponse {
                 status: r#"sent"#.to_owned(),
+                id,
             }))
         }
         Err(err) => {
@@ -62,3 +61,82 @@ async fn send_task(
         }
     }
 }
+
+#[utoipa::path(
+    get,
+    path = "/tasks",
+    params(GetTasks),
+    responses(
+        (status = 200, description = "Task history, most recent first", body = TasksResponseOpenApi),
+        (status = 500, description = "Error occured", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_tasks(
+    Query(params): Query<GetTasks>,
+    State(state): State<AppState>,
+) -> Result<Json<SuccessResponse<Vec<entity::task::Model>>>, ApiError> {
+    let tasks =
+        services::task::history::get_tasks(&state.database, params.status, params.size).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: tasks,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tasks/{task_id}",
+    params(
+        ("task_id" = String, Path, description = "Task Id")
+    ),
+    responses(
+        (status = 200, description = "Task with status and progress", body = TaskModelResponseOpenApi),
+        (status = 404, description = "Task not found", body = ErrorResponse<String>)
+    )
+)]
+pub async fn get_task(
+    Path(task_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<SuccessResponse<entity::task::Model>>, ApiError> {
+    let task = services::task::history::get_task_by_id(&state.database, &task_id).await?;
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: task,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tasks/{task_id}",
+    params(
+        ("task_id" = String, Path, description = "Task Id")
+    ),
+    responses(
+        (status = 200, description = "Cancellation requested", body = MessageResponseOpenApi),
+        (status = 404, description = "Task not found", body = ErrorResponse<String>),
+        (status = 409, description = "Task already finished", body = ErrorResponse<String>)
+    )
+)]
+pub async fn cancel_task(
+    Path(task_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<SuccessResponse<String>>, ApiError> {
+    let task = services::task::history::get_task_by_id(&state.database, &task_id).await?;
+    if task.status != TaskStatus::Queued.as_str() && task.status != TaskStatus::Running.as_str() {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            anyhow!("Task {} has already finished", task_id),
+        ));
+    }
+    // Queued tasks are dropped straight away. Running tasks stop at the next directory or artist
+    if let Err(err) = state.services.task.send(TaskCommand::Cancel(task.id)).await {
+        return Err(ApiError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            anyhow!("Task error: {}", err),
+        ));
+    }
+    Ok(Json(SuccessResponse {
+        status: Status::Success,
+        message: format!("Cancellation of task {} requested", task_id),
+    }))
+}
@@ -1,7 +1,5 @@
-use std::time::Duration;
-
 use axum::{
-    extract::State,
+    extract::{DefaultBodyLimit, State},
     response::Html,
     routing::{get, post},
     Router,
@@ -13,12 +11,13 @@ use tower_http::trace::{self, TraceLayer};
 use tracing::Level;
 use utoipa::ToSchema;
 
-use crate::AppState;
+use crate::{AppState, SETTINGS};
 
 use self::{
     error::Status,
     handlers::{AlbumResponse, ArtistResponse, PlayListResponse},
 };
+pub mod auth;
 pub mod error;
 pub mod handlers;
 pub struct Server {}
@@ -28,7 +27,24 @@ pub struct Server {}
     AlbumsResponseOpenApi = SuccessResponse<Vec<entity::album::Model>>,
     ArtistResponseOpenApi = SuccessResponse<ArtistResponse>,
     ArtistsResponseOpenApi = SuccessResponse<Vec<entity::artist::Model>>,
+    GenresResponseOpenApi = SuccessResponse<Vec<entity::genre::Model>>,
+    SongsResponseOpenApi = SuccessResponse<Vec<entity::song::Model>>,
+    LibrariesResponseOpenApi = SuccessResponse<Vec<entity::library::Model>>,
+    LibraryResponseOpenApi = SuccessResponse<entity::library::Model>,
+    MessageResponseOpenApi = SuccessResponse<String>,
+    TasksResponseOpenApi = SuccessResponse<Vec<entity::task::Model>>,
+    TaskModelResponseOpenApi = SuccessResponse<entity::task::Model>,
     PlayListResponseOpenApi = SuccessResponse<PlayListResponse>,
+    SettingsResponseOpenApi = SuccessResponse<crate::settings::Settings>,
+    RuntimeSettingsResponseOpenApi = SuccessResponse<crate::services::settings::RuntimeSettings>,
+    TopItemsResponseOpenApi = SuccessResponse<Vec<crate::services::stats::TopItem>>,
+    ListeningTimeResponseOpenApi = SuccessResponse<Vec<crate::services::stats::ListeningTime>>,
+    LibraryGrowthResponseOpenApi = SuccessResponse<Vec<crate::services::stats::LibraryGrowth>>,
+    FormatBreakdownResponseOpenApi = SuccessResponse<Vec<crate::services::stats::FormatBreakdown>>,
+    ImportReportResponseOpenApi = SuccessResponse<crate::services::backup::ImportReport>,
+    IssuesResponseOpenApi = SuccessResponse<crate::services::issues::IssuesResponse>,
+    DuplicatesResponseOpenApi = SuccessResponse<Vec<crate::services::duplicates::DuplicateGroup>>,
+    LyricsResponseOpenApi = SuccessResponse<Vec<crate::services::lyrics::Lyrics>>,
 
 )]
 pub struct SuccessResponse<T> {
@@ -39,36 +55,104 @@ pub struct SuccessResponse<T> {
 impl Server {
     pub async fn run(state: AppState) -> Result<(), anyhow::Error> {
         // Build app router
-        let app = Router::new()
-            .route("/", get(_handler))
+        // Streams, downloads and backups last as long as the client keeps reading so they skip the request timeout
+        let streams = Router::new()
             .route("/stream/:id", get(handlers::streams::stream_handler))
-            .route("/stream/cast/:id", get(handlers::streams::cast_handler))
             .route(
                 "/stream/transcode/:id",
                 get(handlers::streams::transcode_stream_handler),
             )
+            .route(
+                "/stream/hls/:id/master.m3u8",
+                get(handlers::streams::hls_master_playlist),
+            )
+            .route(
+                "/stream/hls/:id/:profile/playlist.m3u8",
+                get(handlers::streams::hls_media_playlist),
+            )
+            .route(
+                "/stream/hls/:id/:profile/:segment",
+                get(handlers::streams::hls_segment),
+            )
+            .route("/songs/:id/download", get(handlers::streams::download_song))
+            .route(
+                "/albums/:id/download",
+                get(handlers::streams::download_album),
+            )
+            .route("/admin/backup", get(handlers::admin::get_backup))
+            .route("/admin/export", get(handlers::admin::get_export));
+        let app = Router::new()
+            .route("/", get(_handler))
+            .route("/stream/cast/:id", get(handlers::streams::cast_handler))
             .route("/songs/:id", get(handlers::songs::get_song))
             .route("/songs/:id/like", post(handlers::songs::like_song))
+            .route("/songs/:id/scrobble", post(handlers::songs::scrobble_song))
+            .route("/songs/:id/lyrics", get(handlers::songs::get_song_lyrics))
             .route("/albums", get(handlers::albums::get_albums))
             .route("/albums/:id", get(handlers::albums::get_album))
             .route("/albums/:id/cover", get(handlers::albums::get_cover))
             .route("/artists", get(handlers::artists::get_artists))
             .route("/artists/:id", get(handlers::artists::get_artist))
+            .route("/genres", get(handlers::genres::get_genres))
+            .route(
+                "/genres/:id/albums",
+                get(handlers::genres::get_genre_albums),
+            )
+            .route("/genres/:id/songs", get(handlers::genres::get_genre_songs))
+            .route(
+                "/libraries",
+                get(handlers::libraries::get_libraries).post(handlers::libraries::create_library),
+            )
+            .route(
+                "/libraries/:id",
+                get(handlers::libraries::get_library)
+                    .put(handlers::libraries::update_library)
+                    .delete(handlers::libraries::delete_library),
+            )
             .route("/playlists/:id", get(handlers::playlist::get_playlist))
-            .route("/tasks", get(handlers::tasks::handle_task))
-            .layer((
+            .route("/events", get(handlers::events::events))
+            .route("/settings", get(handlers::settings::get_settings))
+            .route(
+                "/settings/runtime",
+                get(handlers::settings::get_runtime_settings)
+                    .put(handlers::settings::update_runtime_settings),
+            )
+            .route(
+                "/tasks",
+                get(handlers::tasks::get_tasks).post(handlers::tasks::handle_task),
+            )
+            .route(
+                "/tasks/:id",
+                get(handlers::tasks::get_task).delete(handlers::tasks::cancel_task),
+            )
+            .route("/stats/top/artists", get(handlers::stats::get_top_artists))
+            .route("/stats/top/albums", get(handlers::stats::get_top_albums))
+            .route("/stats/top/songs", get(handlers::stats::get_top_songs))
+            .route("/stats/top/genres", get(handlers::stats::get_top_genres))
+            .route("/stats/listening", get(handlers::stats::get_listening_time))
+            .route("/stats/growth", get(handlers::stats::get_library_growth))
+            .route("/stats/formats", get(handlers::stats::get_format_breakdown))
+            .route("/admin/issues", get(handlers::admin::get_issues))
+            .route("/admin/duplicates", get(handlers::admin::get_duplicates))
+            .route(
+                "/admin/import",
+                post(handlers::admin::import_user_state)
+                    .layer(DefaultBodyLimit::max(handlers::admin::IMPORT_BODY_LIMIT)),
+            )
+            // Graceful shutdown will wait for outstanding requests to complete. Add a timeout so
+            // requests don't hang forever.
+            .layer(TimeoutLayer::new(SETTINGS.request_timeout()))
+            .merge(streams)
+            .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                     .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
-                // Graceful shutdown will wait for outstanding requests to complete. Add a timeout so
-                // requests don't hang forever.
-                TimeoutLayer::new(Duration::from_secs(10)),
-            ))
+            )
             .with_state(state)
             .into_make_service();
 
         // Starting listening
-        let listener = tokio::net::TcpListener::bind("0.0.0.0:3030").await.unwrap();
+        let listener = tokio::net::TcpListener::bind(SETTINGS.listen_addr()).await?;
         tracing::debug!("Binding to socket");
         let _ = axum::serve(listener, app)
             .with_graceful_shutdown(shutdown_signal())
@@ -0,0 +1,481 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::Path,
+};
+
+use chrono::Utc;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, QueryTrait, Set,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{
+    http::error::ApiError,
+    library::LibraryScan,
+    scanner::tag_helper::{
+        AudioMetadata, PLACEHOLDER_ALBUM, PLACEHOLDER_ARTIST, PLACEHOLDER_TITLE,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    // Files that couldn't be parsed and were skipped
+    UnreadableFile,
+    MissingTags,
+    PlaceholderTitle,
+    MixedYears,
+    MissingCover,
+    // Songs of one album sharing the same disc and track number
+    DuplicateTrack,
+    // Directories without songs that aren't part of an album, such as folders left after deleting an album
+    OrphanedDirectory,
+}
+
+impl IssueKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueKind::UnreadableFile => "unreadable_file",
+            IssueKind::MissingTags => "missing_tags",
+            IssueKind::PlaceholderTitle => "placeholder_title",
+            IssueKind::MixedYears => "mixed_years",
+            IssueKind::MissingCover => "missing_cover",
+            IssueKind::DuplicateTrack => "duplicate_track",
+            IssueKind::OrphanedDirectory => "orphaned_directory",
+        }
+    }
+}
+
+// Kinds found by check_library. The others are recorded while files are scanned
+const LIBRARY_KINDS: [IssueKind; 4] = [
+    IssueKind::MixedYears,
+    IssueKind::MissingCover,
+    IssueKind::DuplicateTrack,
+    IssueKind::OrphanedDirectory,
+];
+
+#[derive(Serialize, ToSchema)]
+pub struct IssueCount {
+    pub kind: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IssuesResponse {
+    // Issues matching the filters
+    pub total: u64,
+    // Issues of every kind in the library, or every library when none is given
+    pub counts: Vec<IssueCount>,
+    pub issues: Vec<entity::library_issue::Model>,
+}
+
+fn new_issue(
+    library_id: &str,
+    kind: IssueKind,
+    path: &str,
+    message: String,
+) -> entity::library_issue::ActiveModel {
+    entity::library_issue::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        library_id: Set(Some(library_id.to_owned())),
+        kind: Set(kind.as_str().to_owned()),
+        path: Set(path.to_owned()),
+        song_id: Set(None),
+        album_id: Set(None),
+        message: Set(message),
+        created_at: Set(Utc::now().naive_local()),
+    }
+}
+
+async fn insert_issues(
+    db: &impl ConnectionTrait,
+    issues: Vec<entity::library_issue::ActiveModel>,
+) -> Result<(), anyhow::Error> {
+    for chunk in issues.chunks(500) {
+        entity::library_issue::Entity::insert_many(chunk.to_vec())
+            .exec_without_returning(db)
+            .await?;
+    }
+    Ok(())
+}
+
+// Tags the scanner replaced with placeholders or defaults
+fn tag_issues(metadata: &AudioMetadata) -> Vec<(IssueKind, String)> {
+    let mut issues = Vec::new();
+    if metadata.name == PLACEHOLDER_TITLE {
+        issues.push((
+            IssueKind::PlaceholderTitle,
+            String::from("Missing TITLE tag. The song is listed with a placeholder title"),
+        ));
+    }
+    let missing: Vec<&str> = [
+        (metadata.artist == PLACEHOLDER_ARTIST, "ARTIST"),
+        (metadata.album_name == PLACEHOLDER_ALBUM, "ALBUM"),
+        (metadata.track == 0, "TRACKNUMBER"),
+        (metadata.year == 0, "DATE"),
+    ]
+    .into_iter()
+    .filter_map(|(missing, tag)| missing.then_some(tag))
+    .collect();
+    if !missing.is_empty() {
+        issues.push((
+            IssueKind::MissingTags,
+            format!("Missing {} tags", missing.join(", ")),
+        ));
+    }
+    issues
+}
+
+pub async fn record_song_issues(
+    db: &impl ConnectionTrait,
+    library_id: &str,
+    song_id: &str,
+    metadata: &AudioMetadata,
+) -> Result<(), anyhow::Error> {
+    let issues = tag_issues(metadata)
+        .into_iter()
+        .map(|(kind, message)| {
+            let mut issue = new_issue(library_id, kind, &metadata.path, message);
+            issue.song_id = Set(Some(song_id.to_owned()));
+            issue
+        })
+        .collect();
+    insert_issues(db, issues).await
+}
+
+pub async fn record_unreadable_file(
+    db: &impl ConnectionTrait,
+    library_id: &str,
+    path: &str,
+    err: &anyhow::Error,
+) -> Result<(), anyhow::Error> {
+    insert_issues(
+        db,
+        vec![new_issue(
+            library_id,
+            IssueKind::UnreadableFile,
+            path,
+            format!("{:#}", err),
+        )],
+    )
+    .await
+}
+
+// Full scans visit every file again so they start from an empty list
+pub async fn clear_library(
+    db: &impl ConnectionTrait,
+    library_id: &str,
+) -> Result<(), anyhow::Error> {
+    entity::library_issue::Entity::delete_many()
+        .filter(entity::library_issue::Column::LibraryId.eq(library_id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+// Replaces the album and directory issues of a library after a scan
+pub async fn check_library(
+    db: &impl ConnectionTrait,
+    library: &LibraryScan,
+) -> Result<usize, anyhow::Error> {
+    let albums: Vec<(String, String, Option<String>)> = entity::album::Entity::find()
+        .select_only()
+        .columns([
+            entity::album::Column::Id,
+            entity::album::Column::Path,
+            entity::album::Column::Cover,
+        ])
+        .filter(entity::album::Column::LibraryId.eq(library.id.as_str()))
+        .into_tuple()
+        .all(db)
+        .await?;
+    let songs: Vec<SongRow> = entity::song::Entity::find()
+        .select_only()
+        .columns([
+            entity::song::Column::Id,
+            entity::song::Column::Path,
+            entity::song::Column::AlbumId,
+            entity::song::Column::Year,
+            entity::song::Column::Disk,
+            entity::song::Column::Track,
+        ])
+        .filter(entity::song::Column::LibraryId.eq(library.id.as_str()))
+        .order_by_asc(entity::song::Column::Path)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let directories: Vec<String> = entity::directory::Entity::find()
+        .select_only()
+        .column(entity::directory::Column::Path)
+        .filter(entity::directory::Column::LibraryId.eq(library.id.as_str()))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut issues = album_issues(&library.id, &albums, &songs);
+    for path in orphaned_directories(&library.path, &directories, &songs) {
+        issues.push(new_issue(
+            &library.id,
+            IssueKind::OrphanedDirectory,
+            &path,
+            String::from("Directory has no songs"),
+        ));
+    }
+    let found = issues.len();
+
+    entity::library_issue::Entity::delete_many()
+        .filter(entity::library_issue::Column::LibraryId.eq(library.id.as_str()))
+        .filter(entity::library_issue::Column::Kind.is_in(LIBRARY_KINDS.map(|kind| kind.as_str())))
+        .exec(db)
+        .await?;
+    insert_issues(db, issues).await?;
+    Ok(found)
+}
+
+// id, path, album_id, year, disc, track
+type SongRow = (
+    String,
+    String,
+    Option<String>,
+    Option<i32>,
+    Option<i32>,
+    Option<i32>,
+);
+
+fn album_issues(
+    library_id: &str,
+    albums: &[(String, String, Option<String>)],
+    songs: &[SongRow],
+) -> Vec<entity::library_issue::ActiveModel> {
+    let mut album_songs: HashMap<&str, Vec<&SongRow>> = HashMap::new();
+    for song in songs {
+        if let Some(album_id) = &song.2 {
+            album_songs.entry(album_id).or_default().push(song);
+        }
+    }
+    let mut issues = Vec::new();
+    for (album_id, path, cover) in albums {
+        let album_dir = Path::new(path)
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone());
+        let mut album_issue = |kind, message| {
+            let mut issue = new_issue(library_id, kind, &album_dir, message);
+            issue.album_id = Set(Some(album_id.clone()));
+            issues.push(issue);
+        };
+        if cover.is_none() {
+            album_issue(
+                IssueKind::MissingCover,
+                String::from("No cover image found next to the album"),
+            );
+        }
+        let songs = album_songs.remove(album_id.as_str()).unwrap_or_default();
+        let years: BTreeSet<i32> = songs
+            .iter()
+            .filter_map(|song| song.3)
+            .filter(|year| *year > 0)
+            .collect();
+        if years.len() > 1 {
+            let years: Vec<String> = years.iter().map(i32::to_string).collect();
+            album_issue(
+                IssueKind::MixedYears,
+                format!("Songs are tagged with the years {}", years.join(", ")),
+            );
+        }
+
+        let mut positions: HashMap<(i32, i32), Vec<&SongRow>> = HashMap::new();
+        for song in songs {
+            if let Some(track) = song.5.filter(|track| *track > 0) {
+                positions
+                    .entry((song.4.unwrap_or(1), track))
+                    .or_default()
+                    .push(song);
+            }
+        }
+        let mut duplicates: Vec<_> = positions
+            .into_iter()
+            .filter(|(_, songs)| songs.len() > 1)
+            .collect();
+        duplicates.sort_by_key(|(position, _)| *position);
+        for ((disc, track), songs) in duplicates {
+            for song in &songs {
+                let others: Vec<&str> = songs
+                    .iter()
+                    .filter(|other| other.0 != song.0)
+                    .map(|other| other.1.as_str())
+                    .collect();
+                let mut issue = new_issue(
+                    library_id,
+                    IssueKind::DuplicateTrack,
+                    &song.1,
+                    format!(
+                        "Disc {} track {} is also {}",
+                        disc,
+                        track,
+                        others.join(", ")
+                    ),
+                );
+                issue.song_id = Set(Some(song.0.clone()));
+                issue.album_id = Set(Some(album_id.clone()));
+                issues.push(issue);
+            }
+        }
+    }
+    issues
+}
+
+// Directories without songs anywhere below them. Folders inside an album directory such as scans are
+// skipped and only the topmost directory of an empty tree is reported
+fn orphaned_directories(root: &str, directories: &[String], songs: &[SongRow]) -> Vec<String> {
+    let root = Path::new(root);
+    let mut with_songs: HashSet<&Path> = HashSet::new();
+    let mut album_dirs: HashSet<&Path> = HashSet::new();
+    for song in songs {
+        let path = Path::new(&song.1);
+        if let Some(parent) = path.parent() {
+            album_dirs.insert(parent);
+        }
+        for dir in path.ancestors().skip(1) {
+            if !with_songs.insert(dir) || dir == root {
+                break;
+            }
+        }
+    }
+    let mut orphaned: Vec<String> = directories
+        .iter()
+        .filter(|dir| {
+            let dir = Path::new(dir.as_str());
+            if dir == root || !dir.starts_with(root) || with_songs.contains(dir) {
+                return false;
+            }
+            match dir.parent() {
+                Some(parent) => {
+                    !album_dirs.contains(parent) && (parent == root || with_songs.contains(parent))
+                }
+                None => false,
+            }
+        })
+        .cloned()
+        .collect();
+    orphaned.sort();
+    orphaned
+}
+
+pub async fn get_issues(
+    db: &DatabaseConnection,
+    kind: Option<String>,
+    library_id: Option<String>,
+    page: Option<u64>,
+    size: Option<u64>,
+) -> Result<IssuesResponse, ApiError> {
+    let counts = entity::library_issue::Entity::find()
+        .select_only()
+        .column(entity::library_issue::Column::Kind)
+        .column_as(
+            Expr::col(entity::library_issue::Column::Id).count(),
+            "count",
+        )
+        .apply_if(library_id.as_deref(), |query, library_id| {
+            query.filter(entity::library_issue::Column::LibraryId.eq(library_id))
+        })
+        .group_by(entity::library_issue::Column::Kind)
+        .order_by_asc(entity::library_issue::Column::Kind)
+        .into_tuple::<(String, i64)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(kind, count)| IssueCount { kind, count })
+        .collect();
+    let paginator = entity::library_issue::Entity::find()
+        .apply_if(library_id.as_deref(), |query, library_id| {
+            query.filter(entity::library_issue::Column::LibraryId.eq(library_id))
+        })
+        .apply_if(kind, |query, kind| {
+            query.filter(entity::library_issue::Column::Kind.eq(kind))
+        })
+        .order_by_asc(entity::library_issue::Column::Kind)
+        .order_by_asc(entity::library_issue::Column::Path)
+        .paginate(db, size.unwrap_or(100));
+    Ok(IssuesResponse {
+        total: paginator.num_items().await?,
+        counts,
+        issues: paginator.fetch_page(page.unwrap_or(0)).await?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(id: &str, path: &str, year: i32, track: i32) -> SongRow {
+        (
+            String::from(id),
+            String::from(path),
+            Some(String::from("album")),
+            Some(year),
+            Some(1),
+            Some(track),
+        )
+    }
+
+    #[test]
+    fn test_album_issues() {
+        let albums = vec![(
+            String::from("album"),
+            String::from("/music/Akon/Konvicted/01.flac"),
+            None,
+        )];
+        let songs = vec![
+            song("a", "/music/Akon/Konvicted/01.flac", 2006, 1),
+            song("b", "/music/Akon/Konvicted/02.flac", 2007, 2),
+            song("c", "/music/Akon/Konvicted (copy)/02.flac", 2007, 2),
+        ];
+        let issues = album_issues("library", &albums, &songs);
+        let kinds: Vec<String> = issues
+            .iter()
+            .map(|issue| issue.kind.clone().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "missing_cover",
+                "mixed_years",
+                "duplicate_track",
+                "duplicate_track"
+            ]
+        );
+        assert_eq!(
+            issues[1].message.clone().unwrap(),
+            "Songs are tagged with the years 2006, 2007"
+        );
+        assert_eq!(
+            issues[2].message.clone().unwrap(),
+            "Disc 1 track 2 is also /music/Akon/Konvicted (copy)/02.flac"
+        );
+    }
+
+    #[test]
+    fn test_orphaned_directories() {
+        let directories: Vec<String> = [
+            "/music",
+            "/music/Akon",
+            "/music/Akon/Konvicted",
+            "/music/Akon/Konvicted/Scans",
+            "/music/Akon/Trouble",
+            "/music/Akon/Trouble/CD1",
+            "/music/Deleted",
+            "/music/Deleted/Album",
+        ]
+        .map(String::from)
+        .to_vec();
+        let songs = vec![song("a", "/music/Akon/Konvicted/01.flac", 2006, 1)];
+        assert_eq!(
+            orphaned_directories("/music", &directories, &songs),
+            vec!["/music/Akon/Trouble", "/music/Deleted"]
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use std::path::{Path, MAIN_SEPARATOR};
+
+use anyhow::anyhow;
+use chrono::Utc;
+use glob::{MatchOptions, Pattern};
+use hyper::StatusCode;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    DbErr, EntityTrait, QueryFilter, QueryOrder, QueryTrait, Set, TransactionTrait,
+};
+use uuid::Uuid;
+
+use crate::SETTINGS;
+
+use super::{
+    http::{error::ApiError, handlers::LibraryRequest},
+    scanner::inside_dir,
+    scheduler::Schedule,
+};
+
+// Globs are stored in a single column separated by ;
+fn join_globs(globs: &[String]) -> Option<String> {
+    let globs: Vec<&str> = globs
+        .iter()
+        .map(|g| g.trim())
+        .filter(|g| !g.is_empty())
+        .collect();
+    (!globs.is_empty()).then(|| globs.join(";"))
+}
+
+fn split_globs(globs: Option<&str>) -> Vec<&str> {
+    globs
+        .unwrap_or_default()
+        .split(';')
+        .map(|g| g.trim())
+        .filter(|g| !g.is_empty())
+        .collect()
+}
+
+fn parse_globs(globs: Option<&str>) -> Result<Vec<Pattern>, glob::PatternError> {
+    split_globs(globs).into_iter().map(Pattern::new).collect()
+}
+
+// Library as seen by the scanner. Globs are matched against paths relative to the library root
+pub struct LibraryScan {
+    pub id: String,
+    pub path: String,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl LibraryScan {
+    pub fn new(
+        id: String,
+        path: String,
+        include: Option<&str>,
+        exclude: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        Ok(LibraryScan {
+            id,
+            path,
+            include: parse_globs(include)?,
+            exclude: parse_globs(exclude)?,
+        })
+    }
+
+    // Returns true when the file should be scanned. No include globs means everything is included
+    pub fn accepts(&self, file: &Path) -> bool {
+        let relative = file.strip_prefix(&self.path).unwrap_or(file);
+        let options = MatchOptions {
+            case_sensitive: false,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        };
+        let matches = |patterns: &[Pattern]| {
+            patterns
+                .iter()
+                .any(|p| p.matches_path_with(relative, options))
+        };
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+}
+
+// Returns the libraries the scanner should walk. Limited to library_id when provided
+pub async fn get_scan_libraries(
+    db: &impl ConnectionTrait,
+    library_id: Option<&str>,
+) -> Result<Vec<LibraryScan>, anyhow::Error> {
+    entity::library::Entity::find()
+        .apply_if(library_id, |query, library_id| {
+            query.filter(entity::library::Column::Id.eq(library_id))
+        })
+        .order_by_asc(entity::library::Column::Name)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|library| {
+            LibraryScan::new(
+                library.id,
+                library.path,
+                library.include.as_deref(),
+                library.exclude.as_deref(),
+            )
+        })
+        .collect()
+}
+
+// Inserts the libraries from settings.toml that don't exist yet. Libraries are matched on name
+pub async fn sync_settings_libraries(db: &DatabaseConnection) -> Result<(), anyhow::Error> {
+    for library in SETTINGS.libraries() {
+        let existing = entity::library::Entity::find()
+            .filter(entity::library::Column::Name.eq(&library.name))
+            .one(db)
+            .await?;
+        if existing.is_none() {
+            tracing::info!("Adding library \"{:}\" at {:}", library.name, library.path);
+            insert_library(
+                db,
+                &LibraryRequest {
+                    name: library.name,
+                    path: library.path,
+                    scan_schedule: library.scan_schedule,
+                    include: library.include,
+                    exclude: library.exclude,
+                },
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_library(library: &LibraryRequest) -> Result<(), ApiError> {
+    if library.name.trim().is_empty() || library.path.trim().is_empty() {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Library name and path are required"),
+        ));
+    }
+    if let Some(schedule) = &library.scan_schedule {
+        Schedule::parse(schedule).map_err(|e| ApiError(StatusCode::BAD_REQUEST, e))?;
+    }
+    for glob in library.include.iter().chain(library.exclude.iter()) {
+        Pattern::new(glob).map_err(|e| {
+            ApiError(
+                StatusCode::BAD_REQUEST,
+                anyhow!("Invalid glob {}: {}", glob, e),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+// Assigns songs, albums and directories without a library to the library whose root they are in.
+// Rows scanned before libraries existed have no library_id and would otherwise be scanned again as new
+async fn adopt_unassigned(
+    db: &impl ConnectionTrait,
+    library: &entity::library::Model,
+) -> Result<(), DbErr> {
+    let root = library.path.trim_end_matches(MAIN_SEPARATOR);
+    entity::song::Entity::update_many()
+        .col_expr(entity::song::Column::LibraryId, Expr::value(&library.id))
+        .filter(entity::song::Column::LibraryId.is_null())
+        .filter(Expr::col(entity::song::Column::Path).like(inside_dir(root)))
+        .exec(db)
+        .await?;
+    entity::album::Entity::update_many()
+        .col_expr(entity::album::Column::LibraryId, Expr::value(&library.id))
+        .filter(entity::album::Column::LibraryId.is_null())
+        .filter(Expr::col(entity::album::Column::Path).like(inside_dir(root)))
+        .exec(db)
+        .await?;
+    entity::directory::Entity::update_many()
+        .col_expr(
+            entity::directory::Column::LibraryId,
+            Expr::value(&library.id),
+        )
+        .filter(entity::directory::Column::LibraryId.is_null())
+        .filter(
+            Condition::any()
+                .add(entity::directory::Column::Path.eq(root))
+                .add(Expr::col(entity::directory::Column::Path).like(inside_dir(root))),
+        )
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+async fn insert_library(
+    db: &DatabaseConnection,
+    library: &LibraryRequest,
+) -> Result<entity::library::Model, DbErr> {
+    let init_time = Utc::now().naive_local();
+    let library = entity::library::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        name: Set(library.name.trim().to_string()),
+        path: Set(library.path.clone()),
+        scan_schedule: Set(library.scan_schedule.clone()),
+        include: Set(join_globs(&library.include)),
+        exclude: Set(join_globs(&library.exclude)),
+        created_at: Set(init_time),
+        updated_at: Set(init_time),
+    };
+    let tx = db.begin().await?;
+    let library = library.insert(&tx).await?;
+    adopt_unassigned(&tx, &library).await?;
+    tx.commit().await?;
+    Ok(library)
+}
+
+// Creates a library. Names must be unique
+pub async fn create_library(
+    db: &DatabaseConnection,
+    library: &LibraryRequest,
+) -> Result<entity::library::Model, ApiError> {
+    validate_library(library)?;
+    ensure_unique_name(db, library, None).await?;
+    Ok(insert_library(db, library).await?)
+}
+
+// Returns CONFLICT when another library already uses the name. library_id is the library being updated
+async fn ensure_unique_name(
+    db: &DatabaseConnection,
+    library: &LibraryRequest,
+    library_id: Option<&str>,
+) -> Result<(), ApiError> {
+    let existing = entity::library::Entity::find()
+        .filter(entity::library::Column::Name.eq(library.name.trim()))
+        .apply_if(library_id, |query, library_id| {
+            query.filter(entity::library::Column::Id.ne(library_id))
+        })
+        .one(db)
+        .await?;
+    if existing.is_some() {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            anyhow!("Library with name {} already exists", library.name),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn get_library_by_id(
+    db: &DatabaseConnection,
+    library_id: &str,
+) -> Result<entity::library::Model, ApiError> {
+    match entity::library::Entity::find_by_id(library_id)
+        .one(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })? {
+        Some(library) => Ok(library),
+        None => Err(ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow!("Unable to find Library with id: {}", library_id),
+        )),
+    }
+}
+
+// Returns every library sorted by name
+pub async fn get_libraries(
+    db: &DatabaseConnection,
+) -> Result<Vec<entity::library::Model>, ApiError> {
+    Ok(entity::library::Entity::find()
+        .order_by_asc(entity::library::Column::Name)
+        .all(db)
+        .await?)
+}
+
+// Updates the library. Changes to path or globs are picked up by the next scan
+pub async fn update_library(
+    db: &DatabaseConnection,
+    library_id: &str,
+    library: &LibraryRequest,
+) -> Result<entity::library::Model, ApiError> {
+    validate_library(library)?;
+    let mut model: entity::library::ActiveModel = get_library_by_id(db, library_id).await?.into();
+    ensure_unique_name(db, library, Some(library_id)).await?;
+    model.name = Set(library.name.trim().to_string());
+    model.path = Set(library.path.clone());
+    model.scan_schedule = Set(library.scan_schedule.clone());
+    model.include = Set(join_globs(&library.include));
+    model.exclude = Set(join_globs(&library.exclude));
+    model.updated_at = Set(Utc::now().naive_local());
+    Ok(model.update(db).await?)
+}
+
+// Deletes the library along with its songs, albums and directories
+pub async fn delete_library(db: &DatabaseConnection, library_id: &str) -> Result<(), ApiError> {
+    let library = get_library_by_id(db, library_id).await?;
+    let tx = db.begin().await?;
+    entity::song::Entity::delete_many()
+        .filter(entity::song::Column::LibraryId.eq(&library.id))
+        .exec(&tx)
+        .await?;
+    entity::album::Entity::delete_many()
+        .filter(entity::album::Column::LibraryId.eq(&library.id))
+        .exec(&tx)
+        .await?;
+    entity::directory::Entity::delete_many()
+        .filter(entity::directory::Column::LibraryId.eq(&library.id))
+        .exec(&tx)
+        .await?;
+    entity::library::Entity::delete_by_id(library.id)
+        .exec(&tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_globs_round_trip() {
+        let globs = vec![
+            String::from("**/*.flac"),
+            String::from(" "),
+            String::from("*.cue "),
+        ];
+        let joined = join_globs(&globs);
+        assert_eq!(joined.as_deref(), Some("**/*.flac;*.cue"));
+        assert_eq!(split_globs(joined.as_deref()), vec!["**/*.flac", "*.cue"]);
+        assert_eq!(join_globs(&[]), None);
+    }
+
+    #[test]
+    fn test_library_accepts() {
+        let library = LibraryScan::new(
+            String::from("id"),
+            String::from("/music"),
+            Some("**/*.flac"),
+            Some("Podcasts/**;**/*sample*"),
+        )
+        .unwrap();
+        assert!(library.accepts(Path::new("/music/Akon/Freedom/01.flac")));
+        assert!(library.accepts(Path::new("/music/Akon/Freedom/01.FLAC")));
+        assert!(!library.accepts(Path::new("/music/Akon/Freedom/01.mp3")));
+        assert!(!library.accepts(Path::new("/music/Podcasts/Show/01.flac")));
+        assert!(!library.accepts(Path::new("/music/Akon/sample-01.flac")));
+
+        let library =
+            LibraryScan::new(String::from("id"), String::from("/music"), None, None).unwrap();
+        assert!(library.accepts(Path::new("/music/anything.txt")));
+    }
+}
@@ -0,0 +1,303 @@
+use std::{collections::HashMap, process::Stdio};
+
+use anyhow::{anyhow, Context, Result};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter,
+    QueryOrder, QuerySelect, QueryTrait,
+};
+use tokio::process::Command;
+
+use crate::{
+    services::{settings::ReplayGainMode, task::history::TaskProgress},
+    SETTINGS,
+};
+
+// ReplayGain 2.0 reference loudness. Gains bring songs to this integrated loudness
+pub const REFERENCE_LOUDNESS: f64 = -18.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    // Integrated loudness in LUFS
+    pub integrated: f64,
+    // Linear true peak where 1.0 is full scale
+    pub peak: f64,
+}
+
+impl Loudness {
+    pub fn gain(&self) -> f64 {
+        REFERENCE_LOUDNESS - self.integrated
+    }
+}
+
+// Gain in dB to play the song at
+pub fn playback_gain(mode: ReplayGainMode, song: &entity::song::Model) -> Option<f64> {
+    let track = song.track_gain.map(|gain| (gain, song.track_peak));
+    let album = song.album_gain.map(|gain| (gain, song.album_peak));
+    let (gain, peak) = match mode {
+        ReplayGainMode::Track => track,
+        ReplayGainMode::Album => album.or(track),
+    }?;
+    Some(limit_clipping(gain, peak))
+}
+
+// Lowers the gain when it would push the peak past full scale
+fn limit_clipping(gain: f64, peak: Option<f64>) -> f64 {
+    match peak.filter(|peak| *peak > 0.0) {
+        Some(peak) => gain.min(-20.0 * peak.log10()),
+        None => gain,
+    }
+}
+
+// Reads the summary ffmpeg's ebur128 filter prints once the whole file is measured
+fn parse_ebur128_summary(output: &str) -> Option<Loudness> {
+    let summary = &output[output.rfind("Summary:")?..];
+    let value = |label: &str| {
+        summary
+            .lines()
+            .map(str::trim)
+            .find_map(|line| line.strip_prefix(label))?
+            .split_whitespace()
+            .next()?
+            .parse::<f64>()
+            .ok()
+    };
+    let integrated = value("I:")?;
+    let peak = value("Peak:")?;
+    Some(Loudness {
+        integrated,
+        peak: 10f64.powf(peak / 20.0),
+    })
+}
+
+pub async fn measure(path: &str) -> Result<Loudness> {
+    let output = Command::new(&SETTINGS.ffmpeg_path)
+        .stdin(Stdio::null())
+        .arg("-hide_banner")
+        .arg("-nostats")
+        .arg("-i")
+        .arg(path)
+        .arg("-map")
+        .arg("0:a:0")
+        .arg("-af")
+        .arg("ebur128=peak=true")
+        .arg("-f")
+        .arg("null")
+        .arg("-")
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}", SETTINGS.ffmpeg_path))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match (output.status.success(), parse_ebur128_summary(&stderr)) {
+        (true, Some(loudness)) => Ok(loudness),
+        _ => Err(anyhow!(
+            "ffmpeg failed to measure {}: {}",
+            path,
+            stderr.lines().last().unwrap_or_default().trim()
+        )),
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct AlbumTrack {
+    id: String,
+    album_id: String,
+    length: i32,
+    track_gain: Option<f64>,
+    track_peak: Option<f64>,
+    album_gain: Option<f64>,
+}
+
+// Album gain and peak. The gain comes from the length weighted energy average of the tracks, close to
+// measuring the album as one file without decoding it again. None until every track has been measured
+// R128_TRACK_GAIN and many REPLAYGAIN tags come without a peak. The album peak is unknown when a track lacks one
+fn album_loudness(tracks: &[&AlbumTrack]) -> Option<(f64, Option<f64>)> {
+    let mut energy = 0.0;
+    let mut length = 0.0;
+    let mut peak = Some(0.0f64);
+    for track in tracks {
+        let integrated = REFERENCE_LOUDNESS - track.track_gain?;
+        let weight = track.length.max(1) as f64;
+        energy += weight * 10f64.powf(integrated / 10.0);
+        length += weight;
+        peak = peak
+            .zip(track.track_peak)
+            .map(|(peak, track)| peak.max(track));
+    }
+    if length == 0.0 {
+        return None;
+    }
+    let integrated = 10.0 * (energy / length).log10();
+    Some((REFERENCE_LOUDNESS - integrated, peak))
+}
+
+// Sets the album gain of songs on albums without one once all tracks of the album have a track gain
+async fn update_album_gains(db: &DatabaseConnection, library_id: Option<&str>) -> Result<()> {
+    let tracks = entity::song::Entity::find()
+        .select_only()
+        .columns([
+            entity::song::Column::Id,
+            entity::song::Column::AlbumId,
+            entity::song::Column::Length,
+            entity::song::Column::TrackGain,
+            entity::song::Column::TrackPeak,
+            entity::song::Column::AlbumGain,
+        ])
+        .filter(entity::song::Column::AlbumId.is_not_null())
+        .apply_if(library_id, |query, library_id| {
+            query.filter(entity::song::Column::LibraryId.eq(library_id))
+        })
+        .into_model::<AlbumTrack>()
+        .all(db)
+        .await?;
+    let mut albums: HashMap<&str, Vec<&AlbumTrack>> = HashMap::new();
+    for track in &tracks {
+        albums.entry(&track.album_id).or_default().push(track);
+    }
+    for tracks in albums.values() {
+        if tracks.iter().all(|track| track.album_gain.is_some()) {
+            continue;
+        }
+        let Some((gain, peak)) = album_loudness(tracks) else {
+            continue;
+        };
+        entity::song::Entity::update_many()
+            .col_expr(entity::song::Column::AlbumGain, Expr::value(gain))
+            .col_expr(entity::song::Column::AlbumPeak, Expr::value(peak))
+            .filter(
+                entity::song::Column::Id.is_in(
+                    tracks
+                        .iter()
+                        .filter(|track| track.album_gain.is_none())
+                        .map(|track| track.id.as_str()),
+                ),
+            )
+            .exec(db)
+            .await?;
+    }
+    Ok(())
+}
+
+// Measures every song without a track gain with ffmpeg. Tagged ReplayGain and R128 values are kept
+// Songs ffmpeg can't decode are skipped and retried on the next run
+pub async fn analyze_songs(
+    db: &DatabaseConnection,
+    library_id: Option<&str>,
+    progress: &TaskProgress,
+) -> Result<()> {
+    let songs: Vec<(String, String)> = entity::song::Entity::find()
+        .select_only()
+        .columns([entity::song::Column::Id, entity::song::Column::Path])
+        .filter(entity::song::Column::TrackGain.is_null())
+        .apply_if(library_id, |query, library_id| {
+            query.filter(entity::song::Column::LibraryId.eq(library_id))
+        })
+        .order_by_asc(entity::song::Column::Path)
+        .into_tuple()
+        .all(db)
+        .await?;
+    tracing::info!("Measuring loudness of {:} songs", songs.len());
+
+    for (id, path) in songs {
+        progress.check_cancelled()?;
+        let loudness = match measure(&path).await {
+            Ok(loudness) => loudness,
+            // ffmpeg is missing. Every other song would fail the same way
+            Err(err) if err.downcast_ref::<std::io::Error>().is_some() => return Err(err),
+            Err(err) => {
+                tracing::warn!("Failed to measure loudness of {:}: {:}", path, err);
+                continue;
+            }
+        };
+        entity::song::Entity::update_many()
+            .col_expr(
+                entity::song::Column::TrackGain,
+                Expr::value(loudness.gain()),
+            )
+            .col_expr(entity::song::Column::TrackPeak, Expr::value(loudness.peak))
+            .filter(entity::song::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        progress.song_updated();
+    }
+    update_album_gains(db, library_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ebur128_summary() {
+        let output =
+            "[Parsed_ebur128_0 @ 0x5581] t: 1.2 TARGET:-23 LUFS M: -15.1 S:-120.7 I: -15.1 LUFS\n\
+            [Parsed_ebur128_0 @ 0x5581] Summary:\n\
+            \n\
+            \x20 Integrated loudness:\n\
+            \x20   I:         -12.4 LUFS\n\
+            \x20   Threshold: -22.6 LUFS\n\
+            \n\
+            \x20 Loudness range:\n\
+            \x20   LRA:         6.1 LU\n\
+            \n\
+            \x20 True peak:\n\
+            \x20   Peak:        0.0 dBFS\n";
+        let loudness = parse_ebur128_summary(output).unwrap();
+        assert_eq!(loudness.integrated, -12.4);
+        assert_eq!(loudness.peak, 1.0);
+        assert!((loudness.gain() - -5.6).abs() < 1e-9);
+        assert_eq!(parse_ebur128_summary("Invalid data found"), None);
+    }
+
+    #[test]
+    fn test_limit_clipping() {
+        assert_eq!(limit_clipping(-6.5, Some(0.9)), -6.5);
+        assert_eq!(limit_clipping(3.0, None), 3.0);
+        // +6 dB would push a peak of 0.8 past full scale
+        assert!((limit_clipping(6.0, Some(0.8)) - 1.938).abs() < 0.001);
+    }
+
+    fn track(length: i32, track_gain: Option<f64>, track_peak: Option<f64>) -> AlbumTrack {
+        AlbumTrack {
+            id: String::new(),
+            album_id: String::new(),
+            length,
+            track_gain,
+            track_peak,
+            album_gain: None,
+        }
+    }
+
+    #[test]
+    fn test_album_loudness() {
+        let same = [
+            track(200, Some(-5.0), Some(0.9)),
+            track(100, Some(-5.0), Some(0.7)),
+        ];
+        let (gain, peak) = album_loudness(&same.iter().collect::<Vec<_>>()).unwrap();
+        assert!((gain - -5.0).abs() < 1e-9);
+        assert_eq!(peak, Some(0.9));
+
+        // A quiet interlude barely changes the gain of a loud album
+        let mixed = [
+            track(300, Some(-8.0), Some(1.0)),
+            track(30, Some(10.0), Some(0.1)),
+        ];
+        let (gain, _) = album_loudness(&mixed.iter().collect::<Vec<_>>()).unwrap();
+        assert!(gain < -7.5 && gain > -8.0);
+
+        // Tracks tagged with a gain but no peak still give the album a gain
+        let no_peak = [
+            track(200, Some(-5.0), Some(0.9)),
+            track(100, Some(-5.0), None),
+        ];
+        let (gain, peak) = album_loudness(&no_peak.iter().collect::<Vec<_>>()).unwrap();
+        assert!((gain - -5.0).abs() < 1e-9);
+        assert_eq!(peak, None);
+
+        let unmeasured = [
+            track(200, Some(-5.0), Some(0.9)),
+            track(100, None, Some(0.7)),
+        ];
+        assert_eq!(album_loudness(&unmeasured.iter().collect::<Vec<_>>()), None);
+    }
+}
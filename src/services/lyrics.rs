@@ -0,0 +1,390 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use hyper::StatusCode;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::http::error::ApiError;
+
+pub const EMBEDDED: &str = "embedded";
+pub const SIDECAR: &str = "sidecar";
+
+const SIDECAR_EXTENSIONS: [&str; 2] = ["lrc", "txt"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct LyricLine {
+    // Milliseconds from the start of the song with the offset applied. Missing for unsynced lyrics
+    pub start: Option<u64>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Lyrics {
+    // embedded | sidecar
+    pub source: String,
+    pub language: Option<String>,
+    pub synced: bool,
+    // [offset:] of the LRC in milliseconds. Already applied to the start of the lines
+    pub offset: i64,
+    pub lines: Vec<LyricLine>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ParsedLyrics {
+    language: Option<String>,
+    offset: i64,
+    synced: bool,
+    lines: Vec<LyricLine>,
+}
+
+// [mm:ss], [mm:ss.xx] or [mm:ss.xxx] in milliseconds. Some taggers write [mm:ss:xx]
+fn parse_timestamp(tag: &str) -> Option<i64> {
+    let (minutes, rest) = tag.split_once(':')?;
+    let (seconds, fraction) = match rest.split_once(['.', ':']) {
+        Some((seconds, fraction)) => (seconds, fraction),
+        None => (rest, ""),
+    };
+    let numeric = |value: &str| !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit());
+    if !numeric(minutes.trim()) || !numeric(seconds) || !(fraction.is_empty() || numeric(fraction))
+    {
+        return None;
+    }
+    let fraction = match fraction.len() {
+        0 => 0,
+        // Hundredths or milliseconds. Anything more precise is cut to milliseconds
+        len => fraction[..len.min(3)].parse::<i64>().ok()? * 10i64.pow(3 - len.min(3) as u32),
+    };
+    Some(
+        minutes.trim().parse::<i64>().ok()? * 60_000
+            + seconds.parse::<i64>().ok()? * 1000
+            + fraction,
+    )
+}
+
+// Drops the <mm:ss.xx> word timings of enhanced LRC
+fn strip_word_timings(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        match rest[start..].find('>') {
+            Some(end) if parse_timestamp(&rest[start + 1..start + end]).is_some() => {
+                result.push_str(&rest[..start]);
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                result.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Parses LRC. Lyrics without any timestamps are returned as unsynced lines
+fn parse_lrc(content: &str) -> ParsedLyrics {
+    let mut parsed = ParsedLyrics::default();
+    let mut timed: Vec<(i64, String)> = Vec::new();
+    let mut plain: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        let mut tagged = false;
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some(end) = tag.find(']') else {
+                break;
+            };
+            let (tag, after) = (&tag[..end], &tag[end + 1..]);
+            if let Some(time) = parse_timestamp(tag) {
+                times.push(time);
+            } else if let Some((key, value)) = tag.split_once(':') {
+                let value = value.trim();
+                match key.trim().to_lowercase().as_str() {
+                    "offset" => parsed.offset = value.trim_start_matches('+').parse().unwrap_or(0),
+                    "la" | "lang" | "language" if !value.is_empty() => {
+                        parsed.language = Some(value.to_owned())
+                    }
+                    _ => (),
+                }
+                tagged = true;
+            } else {
+                break;
+            }
+            rest = after.trim_start();
+        }
+        match (times.is_empty(), tagged) {
+            (false, _) => {
+                let text = strip_word_timings(rest);
+                timed.extend(times.into_iter().map(|time| (time, text.clone())));
+            }
+            // Lines with only id tags such as [ar:Akon]
+            (true, true) if rest.is_empty() => (),
+            _ => plain.push(line.trim_end().to_owned()),
+        }
+    }
+
+    parsed.synced = !timed.is_empty();
+    parsed.lines = match parsed.synced {
+        true => {
+            // Lines with several timestamps are repeated. Sorting is stable so lines sharing a time keep their order
+            timed.sort_by_key(|(time, _)| *time);
+            timed
+                .into_iter()
+                .map(|(time, text)| LyricLine {
+                    // A positive offset shows lines earlier
+                    start: Some((time - parsed.offset).max(0) as u64),
+                    text,
+                })
+                .collect()
+        }
+        false => {
+            let first = plain.iter().position(|line| !line.trim().is_empty());
+            let last = plain.iter().rposition(|line| !line.trim().is_empty());
+            match (first, last) {
+                (Some(first), Some(last)) => plain[first..=last]
+                    .iter()
+                    .map(|text| LyricLine {
+                        start: None,
+                        text: text.clone(),
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }
+    };
+    parsed
+}
+
+// Language of a sidecar named like the song with a language before the extension. Song.en.lrc is English
+fn sidecar_language(song: &Path, sidecar: &Path) -> Option<Option<String>> {
+    let extension = sidecar.extension()?.to_str()?.to_lowercase();
+    if !SIDECAR_EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+    let song_stem = song.file_stem()?.to_str()?;
+    let sidecar_stem = sidecar.file_stem()?.to_str()?;
+    if sidecar_stem == song_stem {
+        return Some(None);
+    }
+    let language = sidecar_stem.strip_prefix(song_stem)?.strip_prefix('.')?;
+    match (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic()) {
+        true => Some(Some(language.to_lowercase())),
+        false => None,
+    }
+}
+
+// Files of a directory that may be lyrics of its songs. Directories are read once per scan and the
+// candidates are matched against every song
+pub fn sidecar_candidates(paths: &[PathBuf]) -> Vec<PathBuf> {
+    paths
+        .iter()
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    SIDECAR_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                })
+        })
+        .cloned()
+        .collect()
+}
+
+// .lrc and .txt files among the candidates named after the song with their language
+fn find_sidecars(song: &Path, candidates: &[PathBuf]) -> Vec<(String, Option<String>, String)> {
+    let mut sidecars: Vec<(String, Option<String>, String)> = candidates
+        .iter()
+        .filter_map(|path| {
+            let language = sidecar_language(song, path)?;
+            let content = fs::read(path).ok()?;
+            Some((
+                path.to_string_lossy().to_string(),
+                language,
+                String::from_utf8_lossy(&content).to_string(),
+            ))
+        })
+        .collect();
+    sidecars.sort();
+    sidecars
+}
+
+// Replaces the lyrics of the song at path with the embedded lyrics and the sidecar files next to it.
+// sidecars are the sidecar_candidates of the directory of the song
+pub async fn scan_song_lyrics(
+    db: &impl ConnectionTrait,
+    song_id: &str,
+    path: &str,
+    embedded: &[String],
+    sidecars: &[PathBuf],
+) -> Result<(), anyhow::Error> {
+    entity::lyrics::Entity::delete_many()
+        .filter(entity::lyrics::Column::SongId.eq(song_id))
+        .exec(db)
+        .await?;
+    let mut found: Vec<(&str, Option<String>, Option<String>, String)> = Vec::new();
+    for content in embedded {
+        // LYRICS is often a copy of SYNCEDLYRICS or UNSYNCEDLYRICS
+        if !found.iter().any(|(_, _, _, other)| other == content) {
+            found.push((EMBEDDED, None, None, content.clone()));
+        }
+    }
+    for (path, language, content) in find_sidecars(Path::new(path), sidecars) {
+        found.push((SIDECAR, Some(path), language, content));
+    }
+    let models: Vec<entity::lyrics::ActiveModel> = found
+        .into_iter()
+        .filter_map(|(source, path, language, content)| {
+            let parsed = parse_lrc(&content);
+            if parsed.lines.is_empty() {
+                return None;
+            }
+            Some(entity::lyrics::ActiveModel {
+                id: Set(Uuid::new_v4().to_string()),
+                song_id: Set(song_id.to_owned()),
+                source: Set(source.to_owned()),
+                path: Set(path),
+                language: Set(parsed.language.or(language)),
+                synced: Set(parsed.synced),
+                content: Set(content),
+            })
+        })
+        .collect();
+    if !models.is_empty() {
+        entity::lyrics::Entity::insert_many(models).exec(db).await?;
+    }
+    Ok(())
+}
+
+// Lyrics of the song with synced lyrics first. Filtered to a language when one is given
+pub async fn get_lyrics(
+    db: &DatabaseConnection,
+    song_id: &str,
+    language: Option<&str>,
+) -> Result<Vec<Lyrics>, ApiError> {
+    if entity::song::Entity::find_by_id(song_id)
+        .one(db)
+        .await?
+        .is_none()
+    {
+        return Err(ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow!("Unable to find song with id: {}", song_id),
+        ));
+    }
+    let lyrics = entity::lyrics::Entity::find()
+        .filter(entity::lyrics::Column::SongId.eq(song_id))
+        .order_by_desc(entity::lyrics::Column::Synced)
+        .order_by_asc(entity::lyrics::Column::Source)
+        .order_by_asc(entity::lyrics::Column::Path)
+        .all(db)
+        .await?;
+    Ok(lyrics
+        .into_iter()
+        .filter(|lyrics| {
+            language.map_or(true, |language| {
+                lyrics
+                    .language
+                    .as_deref()
+                    .is_some_and(|other| other.eq_ignore_ascii_case(language))
+            })
+        })
+        .map(|lyrics| {
+            let parsed = parse_lrc(&lyrics.content);
+            Lyrics {
+                source: lyrics.source,
+                language: lyrics.language,
+                synced: parsed.synced,
+                offset: parsed.offset,
+                lines: parsed.lines,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(start: Option<u64>, text: &str) -> LyricLine {
+        LyricLine {
+            start,
+            text: String::from(text),
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("01:02.5"), Some(62_500));
+        assert_eq!(parse_timestamp("01:02.50"), Some(62_500));
+        assert_eq!(parse_timestamp("01:02.503"), Some(62_503));
+        assert_eq!(parse_timestamp("01:02:50"), Some(62_500));
+        assert_eq!(parse_timestamp("61:00"), Some(3_660_000));
+        assert_eq!(parse_timestamp("ar:Akon"), None);
+        assert_eq!(parse_timestamp("offset:500"), None);
+    }
+
+    #[test]
+    fn test_parse_lrc() {
+        let parsed = parse_lrc(
+            "[ar:Akon]\n\
+             [ti:Smack That]\n\
+             [la:en]\n\
+             [offset:+500]\n\
+             [00:12.00][01:12.00]Shady, Convict\n\
+             [00:10.20]<00:10.20>Akon <00:10.80>Slim\n\
+             [00:00.20]\n\
+             Not a lyric line\n",
+        );
+        assert!(parsed.synced);
+        assert_eq!(parsed.language.as_deref(), Some("en"));
+        assert_eq!(parsed.offset, 500);
+        assert_eq!(
+            parsed.lines,
+            vec![
+                line(Some(0), ""),
+                line(Some(9_700), "Akon Slim"),
+                line(Some(11_500), "Shady, Convict"),
+                line(Some(71_500), "Shady, Convict"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_unsynced() {
+        let parsed =
+            parse_lrc("\nI see you winding and grinding up on that pole\n\nI know you see me\n\n");
+        assert!(!parsed.synced);
+        assert_eq!(parsed.offset, 0);
+        assert_eq!(
+            parsed.lines,
+            vec![
+                line(None, "I see you winding and grinding up on that pole"),
+                line(None, ""),
+                line(None, "I know you see me"),
+            ]
+        );
+        assert!(parse_lrc("  \n").lines.is_empty());
+    }
+
+    #[test]
+    fn test_sidecar_language() {
+        let song = Path::new("/music/Akon/Konvicted/02 Smack That.flac");
+        let sidecar = |name: &str| sidecar_language(song, &song.with_file_name(name));
+        assert_eq!(sidecar("02 Smack That.lrc"), Some(None));
+        assert_eq!(sidecar("02 Smack That.TXT"), Some(None));
+        assert_eq!(
+            sidecar("02 Smack That.es.lrc"),
+            Some(Some(String::from("es")))
+        );
+        assert_eq!(sidecar("02 Smack That (Remix).lrc"), None);
+        assert_eq!(sidecar("02 Smack That.flac"), None);
+        assert_eq!(sidecar("cover.txt"), None);
+    }
+}
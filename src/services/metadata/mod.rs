@@ -1,12 +1,18 @@
 use anyhow::{anyhow, Context, Result};
 use chrono::Utc;
-use futures::TryStreamExt;
 use musicbrainz_rs::{
     entity::{artist::Artist, relations::RelationContent},
     Fetch,
 };
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use std::collections::HashSet;
 
-use sqlx::{Pool, Row, Sqlite};
+use crate::services::{
+    settings::{RuntimeSettings, ALLMUSIC, MUSICBRAINZ},
+    task::history::TaskProgress,
+};
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct ArtistMetadata {
@@ -133,44 +139,61 @@ impl Default for ArtistMetadata {
         ArtistMetadata::new()
     }
 }
-pub async fn scrap_metadata(sqlite_pool: &Pool<Sqlite>) {
-    // Get all artists that have a mb_artist_id and DONT have a entry in the artist_metadata table
-    let mut rows =
-        sqlx::query("SELECT * FROM \"artists\" WHERE \"artists\".\"mb_artist_id\" NOT NULL GROUP BY \"artists\".\"mb_artist_id\"")
-            .fetch(sqlite_pool);
+pub async fn scrap_metadata(
+    db: &DatabaseConnection,
+    settings: &RuntimeSettings,
+    progress: &TaskProgress,
+) -> Result<()> {
+    // Links, including the allmusic page of the biography, come from MusicBrainz
+    if !settings.metadata_provider_enabled(MUSICBRAINZ) {
+        tracing::info!("MusicBrainz metadata provider is disabled. Skipping metadata");
+        return Ok(());
+    }
+    let biographies = settings.metadata_provider_enabled(ALLMUSIC);
+    // Every artist with a mb_artist_id. Artists sharing a mb_artist_id are only looked up once
+    let artists = entity::artist::Entity::find()
+        .filter(entity::artist::Column::MbArtistId.is_not_null())
+        .order_by_asc(entity::artist::Column::CreatedAt)
+        .all(db)
+        .await?;
+    let mut seen = HashSet::new();
 
-    while let Some(row) = rows.try_next().await.unwrap() {
-        // map the row into a user-defined domain type
-        let mb_artist_id: &str = row.try_get("mb_artist_id").unwrap();
+    for artist in artists {
+        progress.check_cancelled()?;
+        let Some(mb_artist_id) = artist.mb_artist_id else {
+            continue;
+        };
+        if !seen.insert(mb_artist_id.clone()) {
+            continue;
+        }
         let mb_artist_relations = Artist::fetch()
-            .id(mb_artist_id)
+            .id(&mb_artist_id)
             .with_url_relations()
             .execute()
             .await
             .unwrap()
             .relations;
-        let artist_id: &str = row.try_get("id").unwrap();
-        let artist_metadata = ArtistMetadata::default()
-            .get_links(&mb_artist_relations)
-            .get_allmusic_biography()
-            .await
-            .cloned();
+        let artist_id = artist.id;
+        let mut artist_metadata = ArtistMetadata::default();
+        artist_metadata.get_links(&mb_artist_relations);
+        let artist_metadata = match biographies {
+            true => artist_metadata.get_allmusic_biography().await.cloned(),
+            false => Ok(artist_metadata),
+        };
         match artist_metadata {
             Ok(metadata) => {
-                let init_time: String = Utc::now().naive_local().to_string();
-
-                sqlx::query(
-                    "UPDATE artists SET biography=?,link_discogs=?,link_wiki=?,link_youtube=?,updated_at=? WHERE id=?",
-                )
-                .bind(&metadata.biography)
-                .bind(&metadata.discogs)
-                .bind(&metadata.wiki)
-                .bind(&metadata.youtube)
-                .bind(&init_time)
-                .bind(artist_id)
-                .execute(sqlite_pool)
-                .await
-                .unwrap();
+                entity::artist::ActiveModel {
+                    id: Set(artist_id),
+                    biography: Set(metadata.biography),
+                    link_discogs: Set(metadata.discogs),
+                    link_wiki: Set(metadata.wiki),
+                    link_youtube: Set(metadata.youtube),
+                    updated_at: Set(Utc::now().naive_local()),
+                    ..Default::default()
+                }
+                .update(db)
+                .await?;
+                progress.artist_updated();
             }
             Err(err) => {
                 tracing::error!(
@@ -182,4 +205,5 @@ pub async fn scrap_metadata(sqlite_pool: &Pool<Sqlite>) {
             }
         }
     }
+    Ok(())
 }
@@ -1,16 +1,35 @@
 use tokio::sync::mpsc::Sender;
 
-use self::{casting::device::DeviceService, http::handlers::ArtistResponse, task::TaskType};
+use self::{
+    casting::device::DeviceService, events::EventBus, http::handlers::ArtistResponse,
+    settings::SettingsService, task::TaskCommand,
+};
 
 pub mod album;
+pub mod archive;
 pub mod artist;
+pub mod backup;
+pub mod cache;
 pub mod casting;
+pub mod duplicates;
+pub mod events;
+pub mod fingerprint;
+pub mod genre;
+pub mod hls;
 pub mod http;
+pub mod issues;
+pub mod library;
+pub mod loudness;
+pub mod lyrics;
 pub mod metadata;
 pub mod playlist;
 pub mod scanner;
+pub mod scheduler;
+pub mod settings;
 pub mod song;
+pub mod stats;
 pub mod task;
+pub mod user;
 // Rewrite DbArtist to ArtistResponse
 pub type DbArtist = ArtistResponse;
 // Convert sea_orm::DbErr into our custom ServiceError allows ? to be called on sea_orm querys such as find_by_id().await? etc. Pushing up the error to the caller.
@@ -18,5 +37,7 @@ pub type DbArtist = ArtistResponse;
 #[derive(Clone)]
 pub struct DeaftoneService {
     pub device: DeviceService,
-    pub task: Sender<TaskType>,
+    pub task: Sender<TaskCommand>,
+    pub events: EventBus,
+    pub settings: SettingsService,
 }
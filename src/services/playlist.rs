@@ -2,12 +2,17 @@ use anyhow::anyhow;
 use chrono::{NaiveDateTime, Utc};
 use hyper::StatusCode;
 use sea_orm::{DatabaseConnection, EntityTrait, Set};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
-use super::http::error::ApiError;
+use super::{
+    events::{Event, EventBus},
+    http::error::ApiError,
+};
 
 pub async fn create_playlist(
     db: &DatabaseConnection,
+    events: &EventBus,
     playlist_name: &str,
 ) -> Result<String, ApiError> {
     let id: String = Uuid::new_v4().to_string();
@@ -19,6 +24,9 @@ pub async fn create_playlist(
         updated_at: Set(init_time),
     };
     entity::playlist::Entity::insert(playlist).exec(db).await?;
+    events.publish(Event::PlaylistChanged {
+        playlist_id: id.clone(),
+    });
     Ok(id)
 }
 pub async fn get_playlist_by_id_slim(
@@ -74,6 +82,7 @@ pub async fn get_playlist_by_id(
 
 pub async fn add_song_to_playlist(
     db: &DatabaseConnection,
+    events: &EventBus,
     playlist_id: &str,
     song_id: &str,
 ) -> Result<String, ApiError> {
@@ -90,6 +99,9 @@ pub async fn add_song_to_playlist(
                     .exec(db)
                     .await?
                     .last_insert_id;
+                events.publish(Event::PlaylistChanged {
+                    playlist_id: playlist_id.to_owned(),
+                });
                 Ok(insert)
             }
 
@@ -115,6 +127,7 @@ pub async fn get_playlists(
 }
 pub async fn delete_playlist(
     db: &DatabaseConnection,
+    events: &EventBus,
     playlist_id: &str,
 ) -> anyhow::Result<(), ApiError> {
     entity::playlist::Entity::delete_by_id(playlist_id)
@@ -124,5 +137,88 @@ pub async fn delete_playlist(
             tracing::error!("Failed to execute query: {:?}", e);
             e
         })?;
+    events.publish(Event::PlaylistChanged {
+        playlist_id: playlist_id.to_owned(),
+    });
     Ok(())
 }
+
+pub struct PlaylistImport {
+    pub playlist_id: String,
+    pub added: usize,
+    // Entries of the playlist file that don't match a scanned song
+    pub missing: Vec<String>,
+}
+
+// Returns the song paths of an M3U or M3U8 playlist. Relative paths are resolved against base_dir
+pub fn parse_m3u(contents: &str, base_dir: &Path) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .map(|line| line.trim().trim_start_matches('\u{feff}'))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let path = PathBuf::from(line);
+            match path.is_absolute() {
+                true => path,
+                false => base_dir.join(path),
+            }
+        })
+        .collect()
+}
+
+// Creates a playlist from an M3U file matching its entries to scanned songs by path
+pub async fn import_playlist(
+    db: &DatabaseConnection,
+    events: &EventBus,
+    file: &Path,
+    name: Option<&str>,
+) -> anyhow::Result<PlaylistImport> {
+    let contents = std::fs::read_to_string(file)
+        .map_err(|e| anyhow!("Failed to read playlist {}: {}", file.display(), e))?;
+    let base_dir = file.parent().unwrap_or(Path::new("."));
+    let name = match name {
+        Some(name) => name.to_owned(),
+        None => file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| String::from("Imported playlist")),
+    };
+    let playlist_id = create_playlist(db, events, &name)
+        .await
+        .map_err(|err| err.1)?;
+    let mut import = PlaylistImport {
+        playlist_id,
+        added: 0,
+        missing: Vec::new(),
+    };
+    for path in parse_m3u(&contents, base_dir) {
+        let path = path.to_string_lossy().to_string();
+        match crate::services::song::get_song_by_path(db, path.clone()).await? {
+            Some(song) => {
+                add_song_to_playlist(db, events, &import.playlist_id, &song.id)
+                    .await
+                    .map_err(|err| err.1)?;
+                import.added += 1;
+            }
+            None => import.missing.push(path),
+        }
+    }
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_m3u() {
+        let playlist = "\u{feff}#EXTM3U\n#EXTINF:123,Akon - Smack That\n/music/Akon/01 Smack That.flac\n\nKonvicted/02 Blown Away.flac\r\n";
+        assert_eq!(
+            parse_m3u(playlist, Path::new("/music/Akon")),
+            vec![
+                PathBuf::from("/music/Akon/01 Smack That.flac"),
+                PathBuf::from("/music/Akon/Konvicted/02 Blown Away.flac"),
+            ]
+        );
+    }
+}
@@ -1,17 +1,22 @@
-use crate::{services, SCAN_STATUS, SETTINGS};
+use crate::services::events::{Event, ItemKind};
+use crate::services::library::LibraryScan;
+use crate::services::task::history::TaskProgress;
+use crate::{services, SCAN_STATUS};
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, NaiveDateTime, Utc};
-use sqlx::sqlite::SqliteQueryResult;
-use sqlx::{Pool, Row, Sqlite};
+use sea_orm::sea_query::{Expr, LikeExpr, OnConflict};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, QuerySelect, QueryTrait, Set, TransactionTrait,
+};
 use std::result::Result::Ok;
 use std::sync::atomic::Ordering;
 use std::time::SystemTime;
 use std::{
     fs::{self},
-    path::PathBuf,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
     time::Instant,
 };
-use tokio_stream::StreamExt;
 use uuid::Uuid;
 use walkdir::WalkDir;
 
@@ -34,62 +39,74 @@ pub enum ScanType {
     PartialScan,
 }
 
-pub async fn start_scan(scan_type: ScanType, sqlite_pool: &Pool<Sqlite>) {
+// Scans every library or only library_id when provided
+pub async fn start_scan(
+    scan_type: ScanType,
+    library_id: Option<String>,
+    db: &DatabaseConnection,
+    progress: &TaskProgress,
+) -> Result<()> {
     // Set global SCAN_STATUS to true
     SCAN_STATUS.store(true, Ordering::Release);
-    sqlx::query("pragma temp_store = memory;")
-        .execute(sqlite_pool)
-        .await
-        .unwrap();
-    sqlx::query("pragma mmap_size = 30000000000;")
-        .execute(sqlite_pool)
-        .await
-        .unwrap();
-    sqlx::query("pragma page_size = 4096;")
-        .execute(sqlite_pool)
-        .await
-        .unwrap();
     let before: Instant = Instant::now();
-    let current_dir = SETTINGS.media_path.clone();
+    let result = scan(scan_type, library_id.as_deref(), db, progress).await;
+    tracing::info!("Scan completed in: {:.2?}", before.elapsed());
 
+    // Set global SCAN_STATUS to false
+    SCAN_STATUS.store(false, Ordering::Release);
+    result
+}
+
+async fn scan(
+    scan_type: ScanType,
+    library_id: Option<&str>,
+    db: &DatabaseConnection,
+    progress: &TaskProgress,
+) -> Result<()> {
     match scan_type {
         ScanType::FullScan => {
             tracing::info!("Starting full scan");
-            walk_full_initial(sqlite_pool, current_dir).await.unwrap();
+            let libraries = services::library::get_scan_libraries(db, library_id).await?;
+            for library in libraries {
+                tracing::info!("Scanning library {:}", library.path);
+                walk_full_initial(db, &library, progress).await?;
+            }
         }
         ScanType::PartialScan => {
             tracing::info!("Starting partial scan");
-            walk_partial(sqlite_pool).await.unwrap();
+            walk_partial(db, library_id, progress).await?;
         }
     }
-    /*     match sqlx::query!("SELECT value FROM settings WHERE name = 'scanned'")
-        .fetch_one(sqlite_pool)
-        .await
-    {
-        Err(sqlx::Error::RowNotFound) => walk_full_initial(sqlite_pool, current_dir).await.unwrap(),
-        value => match value.unwrap().value == "1" {
-            true => {
-                tracing::info!("Starting partial scan");
-                walk_partial(&sqlite_pool).await.unwrap();
-            }
-            _ => {
-                tracing::info!("Starting full scan");
-                walk_full_initial(sqlite_pool, current_dir).await.unwrap()
-            }
-        },
-    } */
-    tracing::info!("Scan completed in: {:.2?}", before.elapsed());
+    services::duplicates::detect(db).await?;
+    Ok(())
+}
 
-    // Set global SCAN_STATUS to false
-    SCAN_STATUS.store(false, Ordering::Release);
+// Matches paths inside the directory. LIKE wildcards in the directory name are escaped
+pub(crate) fn inside_dir(dir: &str) -> LikeExpr {
+    let escaped = format!("{dir}{MAIN_SEPARATOR}")
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    LikeExpr::new(format!("{escaped}%")).escape('\\')
 }
 
-pub async fn walk_partial(connection: &Pool<sqlx::Sqlite>) -> Result<()> {
-    let mut rows = sqlx::query("SELECT * FROM directories").fetch(connection);
+pub async fn walk_partial(
+    db: &DatabaseConnection,
+    library_id: Option<&str>,
+    progress: &TaskProgress,
+) -> Result<()> {
+    let directories = entity::directory::Entity::find()
+        .apply_if(library_id, |query, library_id| {
+            query.filter(entity::directory::Column::LibraryId.eq(library_id))
+        })
+        .all(db)
+        .await?;
 
-    while let Some(row) = rows.try_next().await? {
-        let path: String = row.get("path");
-        let ddirectory_mtime: NaiveDateTime = row.get("mtime");
+    for directory in directories {
+        progress.check_cancelled()?;
+        let path = directory.path;
+        progress.directory_walked();
+        let ddirectory_mtime: NaiveDateTime = directory.mtime;
         let meta = fs::metadata(&path);
         let is_empty = PathBuf::from(&path)
             .read_dir()
@@ -112,18 +129,51 @@ pub async fn walk_partial(connection: &Pool<sqlx::Sqlite>) -> Result<()> {
             }
         } else {
             tracing::info!("Dropping all items for path {}", &path);
-            sqlx::query("DELETE FROM directories WHERE path LIKE ?")
-                .bind(&path)
-                .persistent(true)
-                .execute(connection)
+            let tx = db.begin().await?;
+            entity::directory::Entity::delete_many()
+                .filter(
+                    Condition::any()
+                        .add(entity::directory::Column::Path.eq(path.as_str()))
+                        .add(Expr::col(entity::directory::Column::Path).like(inside_dir(&path))),
+                )
+                .exec(&tx)
                 .await?;
-            sqlx::query("DELETE FROM songs WHERE path LIKE ?")
-                .bind(&path)
-                .persistent(true)
-                .execute(connection)
+            let removed: Vec<String> = entity::song::Entity::find()
+                .select_only()
+                .column(entity::song::Column::Id)
+                .filter(Expr::col(entity::song::Column::Path).like(inside_dir(&path)))
+                .into_tuple()
+                .all(&tx)
                 .await?;
+            entity::song::Entity::delete_many()
+                .filter(entity::song::Column::Id.is_in(removed.iter().map(String::as_str)))
+                .exec(&tx)
+                .await?;
+            // Issues of removed songs go with them. Unreadable files and directories are matched on path
+            entity::library_issue::Entity::delete_many()
+                .filter(
+                    Condition::any()
+                        .add(entity::library_issue::Column::Path.eq(path.as_str()))
+                        .add(
+                            Expr::col(entity::library_issue::Column::Path).like(inside_dir(&path)),
+                        ),
+                )
+                .exec(&tx)
+                .await?;
+            tx.commit().await?;
+            progress.songs_removed(removed.len() as u64);
+            for id in removed {
+                progress.publish(Event::LibraryItemRemoved {
+                    kind: ItemKind::Song,
+                    id,
+                    library_id: directory.library_id.clone(),
+                });
+            }
         }
     }
+    for library in services::library::get_scan_libraries(db, library_id).await? {
+        services::issues::check_library(db, &library).await?;
+    }
     /*  let mut dirs_stream = entity::directorie::Entity::find().stream(db).await?;
     while let Some(item) = dirs_stream.next().await {
         let item: entity::directorie::Model = item?;
@@ -160,8 +210,13 @@ pub async fn walk_partial(connection: &Pool<sqlx::Sqlite>) -> Result<()> {
     Ok(())
 }
 // This is only run on the first initital scan of Deaftone. Since we dont need to checking if the directory exists or has been modified in the database
-pub async fn walk_full_initial(db: &Pool<sqlx::Sqlite>, current_dir: String) -> Result<()> {
-    for entry in WalkDir::new(current_dir)
+pub async fn walk_full_initial(
+    db: &DatabaseConnection,
+    library: &LibraryScan,
+    progress: &TaskProgress,
+) -> Result<()> {
+    services::issues::clear_library(db, &library.id).await?;
+    for entry in WalkDir::new(&library.path)
         .follow_links(true)
         .into_iter()
         .filter_map(|e| e.ok())
@@ -171,15 +226,23 @@ pub async fn walk_full_initial(db: &Pool<sqlx::Sqlite>, current_dir: String) ->
                 .any(|s| s.to_str().map(|x| x.starts_with('.')).unwrap_or(false))
         })
     {
+        progress.check_cancelled()?;
         if entry.file_type().is_dir() {
             let fmtime: SystemTime = entry.metadata().unwrap().modified().unwrap();
             let mtime: DateTime<Utc> = fmtime.into();
             let path: String = entry.path().to_string_lossy().to_string();
-            insert_directory(&path, &mtime, db).await?;
+            insert_directory(&path, &mtime, &library.id, db).await?;
             tracing::debug!("Created directory {:}", &path);
-            skip_fail!(scan_dir(&path, db).await);
+            progress.directory_walked();
+            skip_fail!(scan_dir(&path, library, db, progress).await);
         }
     }
+    let issues = services::issues::check_library(db, library).await?;
+    tracing::info!(
+        "Found {} album and directory issues in {}",
+        issues,
+        library.path
+    );
     update_scan_state(true, db).await?;
     Ok(())
 }
@@ -187,139 +250,218 @@ pub async fn walk_full_initial(db: &Pool<sqlx::Sqlite>, current_dir: String) ->
 async fn _scan_dir_partial(
     _entry: &walkdir::DirEntry,
     _path: &str,
-    _sqlite_pool: &Pool<sqlx::Sqlite>,
+    _db: &DatabaseConnection,
 ) -> Result<()> {
     Ok(())
 }
 
+// Album and artists shared by the songs of a directory. Resolved by the first song stored
+#[derive(Clone, Default)]
+struct DirectoryAlbum {
+    artist_ids: Option<Vec<String>>,
+    album_id: Option<String>,
+}
+
+enum FileOutcome {
+    Unreadable,
+    Added,
+    Updated,
+}
+
+struct ScannedFile {
+    directory: DirectoryAlbum,
+    outcome: FileOutcome,
+    // Published once the file is committed
+    events: Vec<Event>,
+}
+
 // Scan dir function for a full directory scan missing check for seen songs
-async fn scan_dir(path: &str, sqlite_pool: &Pool<sqlx::Sqlite>) -> Result<()> {
-    let mut tx = sqlite_pool
+async fn scan_dir(
+    path: &str,
+    library: &LibraryScan,
+    db: &DatabaseConnection,
+    progress: &TaskProgress,
+) -> Result<()> {
+    let tx = db
         .begin()
         .await
         .map_err(|e| anyhow!("Error beginning transaction: {}", e))?;
     tracing::debug!("Scanning dir {:}", path);
-    let mut create_album = true;
-    let mut create_artist = true;
-    let mut album_id = String::new();
-    let mut artist_id = String::new();
+    let mut directory = DirectoryAlbum::default();
+    let paths = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    let sidecars = services::lyrics::sidecar_candidates(&paths);
 
-    for entry in fs::read_dir(path)? {
-        // Is assigning here bad? Since in a large collection it could be alot of allocations
-        let path = entry?.path();
-        let path_parent = path.parent().unwrap().to_string_lossy().to_string();
-
-        if path.extension() == Some(std::ffi::OsStr::new("flac")) {
-            let metadata = skip_fail!(tag_helper::get_metadata_flac(path));
-            // Check if album has been created. This is a nice speedup since we can assume that when we are in a folder of tracks the they are all from the same album
-            if create_artist {
-                let artists_exists = sqlx::query("SELECT * FROM artists WHERE name = ?")
-                    .bind(&metadata.album_artist)
-                    .persistent(true)
-                    .fetch_one(sqlite_pool)
-                    .await;
-                // Check if artist exists on this loop
-                match artists_exists {
-                    Err(sqlx::Error::RowNotFound) => {
-                        artist_id = skip_fail!(
-                            services::artist::create_artist(
-                                &mut tx,
-                                &metadata.album_artist,
-                                &metadata.mb_artist_id
-                            )
-                            .await
-                        );
-                        // Set create artist to false since we know its created now. This can later be used to to skip a db query
-                        create_artist = false;
-                        // Set artist_id here since on the first run of a scan it wont be found since we have the create_album inside the transaction
-                        tracing::info!("Creating artists \"{:}\"", metadata.album_artist)
-                    }
-                    value => {
-                        artist_id = value.unwrap().get("id");
-                    }
+    for path in paths {
+        if path.extension() != Some(std::ffi::OsStr::new("flac")) || !library.accepts(&path) {
+            continue;
+        }
+        // A failed statement aborts the whole transaction on PostgreSQL. Each file gets a savepoint so
+        // only the failed file is rolled back and the rest of the directory is still stored
+        let savepoint = tx.begin().await?;
+        match scan_file(&savepoint, &path, library, &directory, &sidecars).await {
+            Ok(scanned) => {
+                savepoint.commit().await?;
+                directory = scanned.directory;
+                match scanned.outcome {
+                    FileOutcome::Added => progress.song_added(),
+                    FileOutcome::Updated => progress.song_updated(),
+                    FileOutcome::Unreadable => {}
+                }
+                for event in scanned.events {
+                    progress.publish(event);
                 }
             }
-            // Check if album has been created before inside this folder
-            if create_album {
-                let album_exists = sqlx::query("SELECT * FROM albums WHERE name = ?")
-                    .bind(&metadata.album_name)
-                    .persistent(true)
-                    .fetch_one(sqlite_pool)
-                    .await;
-                match album_exists {
-                    Err(sqlx::Error::RowNotFound) => {
-                        // Searching for cover here allows us to not have to check every iteration of the album to find the cover. Rather we search the dir once. Which should already be cached by the system
-                        let mut cover: Option<String> = None;
-                        for entry in fs::read_dir(&path_parent)? {
-                            let f_name = entry?.path().to_string_lossy().to_string().clone();
-                            if f_name.contains("cover.") {
-                                cover = Some(f_name);
-                            }
-                        }
-                        let id = skip_fail!(
-                            services::album::create_album(&mut tx, cover, &artist_id, &metadata)
-                                .await
-                        );
+            Err(err) => {
+                savepoint.rollback().await?;
+                tracing::error!("Failed to scan {}: {}; skipped.", path.display(), err);
+            }
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
 
-                        // Set create album to false since we know its created now
-                        create_album = false;
-                        // Set album_id here since on the first run of a scan it wont be found since we have the create_album inside the transaction
-                        album_id = id;
-                        tracing::info!("Creating album \"{:}\"", metadata.album_name)
+async fn scan_file(
+    tx: &DatabaseTransaction,
+    path: &Path,
+    library: &LibraryScan,
+    directory: &DirectoryAlbum,
+    sidecars: &[PathBuf],
+) -> Result<ScannedFile> {
+    let mut directory = directory.clone();
+    let mut events = Vec::new();
+    let path_parent = path.parent().unwrap().to_string_lossy().to_string();
+    let metadata = match tag_helper::get_metadata_flac(path.to_path_buf()) {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            tracing::error!("Failed to read {}: {}; skipped.", path.display(), err);
+            services::issues::record_unreadable_file(
+                tx,
+                &library.id,
+                &path.to_string_lossy(),
+                &err,
+            )
+            .await?;
+            return Ok(ScannedFile {
+                directory,
+                outcome: FileOutcome::Unreadable,
+                events,
+            });
+        }
+    };
+    // Artists are resolved once per folder. This is a nice speedup since we can assume that when we are in a folder of tracks the they are all from the same album
+    let artist_ids = match directory.artist_ids.clone() {
+        Some(artist_ids) => artist_ids,
+        None => services::artist::resolve_album_artists(tx, &metadata).await?,
+    };
+    directory.artist_ids = Some(artist_ids.clone());
+    // The first album artist owns the album. Others are linked through artist_credits
+    let artist_id = artist_ids.first().cloned().unwrap_or_default();
+    // Check if album has been created before inside this folder
+    let album_id = match directory.album_id.clone() {
+        Some(album_id) => album_id,
+        None => match services::album::find_album(tx, &artist_id, &library.id, &metadata).await? {
+            Some(id) => id,
+            None => {
+                // Searching for cover here allows us to not have to check every iteration of the album to find the cover. Rather we search the dir once. Which should already be cached by the system
+                let mut cover: Option<String> = None;
+                // Disc folders usually keep the cover next to them in the album folder
+                let album_dir = services::album::album_dir(&path_parent);
+                for dir in [path_parent.as_str(), album_dir] {
+                    for entry in fs::read_dir(dir)? {
+                        let f_name = entry?.path().to_string_lossy().to_string().clone();
+                        if f_name.contains("cover.") {
+                            cover = Some(f_name);
+                        }
                     }
-                    value => {
-                        album_id = value.unwrap().get("id");
+                    if cover.is_some() {
+                        break;
                     }
                 }
+                let id =
+                    services::album::create_album(tx, cover, &artist_id, &library.id, &metadata)
+                        .await?;
+                services::genre::link_album(tx, &id, &metadata).await?;
+                services::artist::credit_album(tx, &id, &artist_ids).await?;
+                tracing::info!("Creating album \"{:}\"", metadata.album_name);
+                events.push(Event::LibraryItemAdded {
+                    kind: ItemKind::Album,
+                    id: id.clone(),
+                    library_id: Some(library.id.clone()),
+                });
+                id
             }
-            tracing::info!("Creating song \"{:}\"", metadata.name);
-            // Create song. Skip loop iteration of failed
-            skip_fail!(services::song::create_song(&mut tx, &album_id, &metadata).await);
+        },
+    };
+    // Every track in this folder belongs to the same album
+    directory.album_id = Some(album_id.clone());
+
+    tracing::info!("Creating song \"{:}\"", metadata.name);
+    let existing = services::song::find_song_id(tx, &metadata.path).await?;
+    let song_id =
+        services::song::create_song(tx, existing.as_deref(), &album_id, &library.id, &metadata)
+            .await?;
+    let outcome = match existing.is_some() {
+        true => FileOutcome::Updated,
+        false => {
+            events.push(Event::LibraryItemAdded {
+                kind: ItemKind::Song,
+                id: song_id.clone(),
+                library_id: Some(library.id.clone()),
+            });
+            FileOutcome::Added
         }
-    }
-    tx.commit().await.unwrap();
-    Ok(())
+    };
+    services::genre::link_song(tx, &song_id, &metadata).await?;
+    services::artist::credit_song(tx, &song_id, &metadata).await?;
+    services::lyrics::scan_song_lyrics(
+        tx,
+        &song_id,
+        &metadata.path,
+        &metadata.embedded_lyrics,
+        sidecars,
+    )
+    .await?;
+    services::issues::record_song_issues(tx, &library.id, &song_id, &metadata).await?;
+    Ok(ScannedFile {
+        directory,
+        outcome,
+        events,
+    })
 }
 
-// Inserts a directory into the database with provided path and mtime
+// Inserts a directory into the database with provided path and mtime. Updating the mtime of a directory scanned before
 async fn insert_directory(
     path: &str,
     mtime: &DateTime<Utc>,
-    tx: &Pool<sqlx::Sqlite>,
-) -> Result<SqliteQueryResult, anyhow::Error> {
-    let init_time: String = Utc::now().naive_local().to_string();
-    Ok(sqlx::query(
-        "INSERT OR REPLACE INTO directories (
-                    id,
-                    path,
-                    mtime,
-                    created_at,
-                    updated_at
-                )
-                VALUES (?,?,?,?,?)",
+    library_id: &str,
+    db: &impl ConnectionTrait,
+) -> Result<(), anyhow::Error> {
+    let init_time = Utc::now().naive_local();
+    entity::directory::Entity::insert(entity::directory::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        path: Set(path.to_owned()),
+        mtime: Set(mtime.naive_utc()),
+        created_at: Set(init_time),
+        updated_at: Set(init_time),
+        library_id: Set(Some(library_id.to_owned())),
+    })
+    .on_conflict(
+        OnConflict::column(entity::directory::Column::Path)
+            .update_columns([
+                entity::directory::Column::Mtime,
+                entity::directory::Column::UpdatedAt,
+                entity::directory::Column::LibraryId,
+            ])
+            .to_owned(),
     )
-    .bind(Uuid::new_v4().to_string())
-    .bind(path)
-    .bind(mtime.naive_utc())
-    .bind(&init_time)
-    .bind(&init_time)
-    .execute(tx)
-    .await?)
+    .exec_without_returning(db)
+    .await?;
+    Ok(())
 }
 
-async fn update_scan_state(
-    value: bool,
-    db: &Pool<sqlx::Sqlite>,
-) -> Result<SqliteQueryResult, anyhow::Error> {
-    Ok(sqlx::query(
-        "INSERT OR REPLACE INTO settings (
-                    name,
-                    value
-                )
-                VALUES (?,?)",
-    )
-    .bind("scanned".to_string())
-    .bind(value)
-    .execute(db)
-    .await?)
+async fn update_scan_state(value: bool, db: &impl ConnectionTrait) -> Result<(), anyhow::Error> {
+    Ok(services::settings::set_setting(db, "scanned", &value).await?)
 }
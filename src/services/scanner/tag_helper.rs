@@ -2,13 +2,18 @@ use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 use metaflac::{block::VorbisComment, Tag};
-#[derive(Debug, PartialEq, Eq, Clone)]
+
+use crate::SETTINGS;
+#[derive(Debug, PartialEq, Clone)]
 pub struct AudioMetadata {
     pub name: String,
     pub artist: String,
     pub artist_sort: Option<String>,
     pub album_name: String,
     pub album_artist: String,
+    pub artists: Vec<String>,
+    pub featured_artists: Vec<String>,
+    pub album_artists: Vec<String>,
     pub album_sort: Option<String>,
     pub genre: Option<Vec<String>>,
     pub style: Option<Vec<String>>,
@@ -21,16 +26,24 @@ pub struct AudioMetadata {
     pub work: Option<String>,
     pub mb_workid: Option<String>,
     pub arranger: Option<String>,
+    pub composers: Vec<String>,
+    pub lyricists: Vec<String>,
+    pub arrangers: Vec<String>,
     pub grouping: Option<String>,
     pub year: i32,
     pub lyrics: Option<String>,
+    // Every LYRICS, UNSYNCEDLYRICS and SYNCEDLYRICS value. Synced lyrics are LRC
+    pub embedded_lyrics: Vec<String>,
     pub comments: Option<String>,
     pub bpm: Option<i32>,
-    pub compilation: Option<String>,
+    pub compilation: bool,
     pub mb_track_id: Option<String>,
+    pub mb_recording_id: Option<String>,
     pub mb_album_id: Option<String>,
     pub mb_artist_id: Option<String>,
     pub mb_albumartist_id: Option<String>,
+    pub mb_artist_ids: Vec<String>,
+    pub mb_albumartist_ids: Vec<String>,
     pub mb_releasetrack_id: Option<String>,
     pub mb_releasegroup_id: Option<String>,
     pub trackdisambig: Option<String>,
@@ -54,17 +67,33 @@ pub struct AudioMetadata {
     pub channels: Option<u8>,
     pub bit_depth: Option<u8>,
     pub sample_rate: Option<u32>,
+    pub codec: Option<String>,
     pub track: u32,
     pub disc: u32,
+    pub disc_total: Option<u32>,
+    pub disc_subtitle: Option<String>,
     pub length: u32,
+    pub total_samples: Option<u64>,
+    pub encoder_delay: Option<u32>,
+    pub encoder_padding: Option<u32>,
+    pub duration_ms: Option<u64>,
     pub label: Option<String>,
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
     pub path: String,
     pub parent_path: String,
 }
 
+// Stored instead of missing tags so the song still shows up. Scans report songs using them as library issues
+pub const PLACEHOLDER_TITLE: &str = "FAILED TO READ TITLE DEAFTONE";
+pub const PLACEHOLDER_ARTIST: &str = "FAILED TO READ ARTIST DEAFTONE";
+pub const PLACEHOLDER_ALBUM: &str = "FAILED TO READ ALBUM DEAFTONE";
+
 pub struct StreamInfo {
     length: Option<u32>,
-    _total_samples: Option<u64>,
+    total_samples: Option<u64>,
     sample_rate: Option<u32>,
     bits_per_sample: Option<u8>,
     /*     bit_rate: Option<i64>,
@@ -82,7 +111,7 @@ pub fn get_metadata_flac(path: PathBuf) -> Result<AudioMetadata> {
     let stream_info = match file_stream_info.next() {
         Some(metaflac::Block::StreamInfo(s)) => StreamInfo {
             length: Some(s.total_samples as u32 / s.sample_rate),
-            _total_samples: Some(s.total_samples),
+            total_samples: Some(s.total_samples),
             sample_rate: Some(s.sample_rate),
             bits_per_sample: Some(s.bits_per_sample),
             /*             bit_rate: Some((meta.len() * (s.total_samples as u64 / s.sample_rate as u64)) as i64),
@@ -93,31 +122,63 @@ pub fn get_metadata_flac(path: PathBuf) -> Result<AudioMetadata> {
         _ => anyhow::bail!("Failed to read stream info"),
     };
 
+    // FLAC is lossless so nothing needs trimming unless it was decoded from a lossy file that recorded its
+    // iTunes gapless info
+    let (encoder_delay, encoder_padding) = vorbis
+        .get("ITUNSMPB")
+        .and_then(|v| parse_itunsmpb(&v[0]))
+        .unwrap_or((0, 0));
+
+    let separators = &SETTINGS.artist_separators;
+    let (artists, featured_artists) =
+        get_artists(vorbis.artist(), vorbis.get("ARTISTS"), separators);
+    let album_artists = get_album_artists(
+        vorbis.album_artist(),
+        vorbis.get("ALBUMARTISTS"),
+        &artists,
+        separators,
+    );
+    let id_separators = [String::from(";")];
+    let compilation = is_compilation(
+        vorbis.get("COMPILATION").map(|v| v[0].as_str()),
+        vorbis.album_artist().map(|v| v[0].as_str()),
+    );
+    let (album_artist, album_artists) =
+        get_album_artist(compilation, vorbis.album_artist(), album_artists);
+
+    let (disc, disc_total) = parse_disc(
+        vorbis.get("DISCNUMBER").map(|v| v[0].as_str()),
+        vorbis
+            .get("DISCTOTAL")
+            .or_else(|| vorbis.get("TOTALDISCS"))
+            .map(|v| v[0].as_str()),
+    );
+
     let metadata: AudioMetadata = AudioMetadata {
         name: vorbis
             .title()
             .map(|v| v[0].clone())
-            .unwrap_or_else(|| "FAILED TO READ TITLE DEAFTONE".to_string()),
+            .unwrap_or_else(|| PLACEHOLDER_TITLE.to_string()),
         artist: vorbis
             .artist()
             .map(|v| v[0].clone())
-            .unwrap_or_else(|| "FAILED TO READ ARTIST DEAFTONE".to_string()),
+            .unwrap_or_else(|| PLACEHOLDER_ARTIST.to_string()),
         artist_sort: vorbis
             .get("ARTISTSORT")
             .and_then(|d| d[0].parse::<String>().ok()),
         album_name: vorbis
             .album()
             .map(|v| v[0].clone())
-            .unwrap_or_else(|| "FAILED TO READ ALBUM DEAFTONE".to_string()),
-        album_artist: vorbis
-            .album_artist()
-            .map(|v| v[0].clone())
-            .unwrap_or_else(|| "FAILED TO READ ALBUM DEAFTONE".to_string()),
+            .unwrap_or_else(|| PLACEHOLDER_ALBUM.to_string()),
+        album_artist,
+        artists,
+        featured_artists,
+        album_artists,
         album_sort: vorbis
             .get("ALBUMSORT")
             .and_then(|d| d[0].parse::<String>().ok()),
-        genre: vorbis.genre().cloned(),
-        style: vorbis.get("STYLE").cloned(),
+        genre: split_multi_value(vorbis.genre(), &SETTINGS.multi_value_separators),
+        style: split_multi_value(vorbis.get("STYLE"), &SETTINGS.multi_value_separators),
         discogs_albumid: vorbis
             .get("DISCOGS_ALBUMID")
             .and_then(|d| d[0].parse::<String>().ok()),
@@ -144,23 +205,35 @@ pub fn get_metadata_flac(path: PathBuf) -> Result<AudioMetadata> {
         arranger: vorbis
             .get("ARRANGER")
             .and_then(|d| d[0].parse::<String>().ok()),
+        composers: split_multi_value(vorbis.get("COMPOSER"), separators).unwrap_or_default(),
+        lyricists: split_multi_value(vorbis.get("LYRICIST"), separators).unwrap_or_default(),
+        arrangers: split_multi_value(vorbis.get("ARRANGER"), separators).unwrap_or_default(),
         grouping: vorbis
             .get("GROUPING")
             .and_then(|d| d[0].parse::<String>().ok()),
         year: get_year(vorbis).with_context(|| "Failed to read year")?,
         lyrics: vorbis
             .get("LYRICS")
-            .and_then(|d| d[0].parse::<String>().ok()),
+            .or_else(|| vorbis.get("UNSYNCEDLYRICS"))
+            .and_then(|d| d[0].parse::<String>().ok()),
+        embedded_lyrics: ["SYNCEDLYRICS", "UNSYNCEDLYRICS", "LYRICS"]
+            .iter()
+            .filter_map(|key| vorbis.get(key))
+            .flatten()
+            .filter(|lyrics| !lyrics.trim().is_empty())
+            .cloned()
+            .collect(),
         comments: vorbis
             .get("COMMENTS")
             .and_then(|d| d[0].parse::<String>().ok()),
         bpm: vorbis.get("BPM").and_then(|d| d[0].parse::<i32>().ok()),
-        compilation: vorbis
-            .get("COMPILATION")
-            .and_then(|d| d[0].parse::<String>().ok()),
+        compilation,
         mb_track_id: vorbis
             .get("MUSICBRAINZ_RELEASETRACKID")
             .and_then(|d| d[0].parse::<String>().ok()),
+        mb_recording_id: vorbis
+            .get("MUSICBRAINZ_TRACKID")
+            .and_then(|d| d[0].parse::<String>().ok()),
         mb_album_id: vorbis
             .get("MUSICBRAINZ_ALBUMID")
             .and_then(|d| d[0].parse::<String>().ok()),
@@ -170,6 +243,13 @@ pub fn get_metadata_flac(path: PathBuf) -> Result<AudioMetadata> {
         mb_albumartist_id: vorbis
             .get("MUSICBRAINZ_ALBUMARTISTID")
             .and_then(|d| d[0].parse::<String>().ok()),
+        mb_artist_ids: split_multi_value(vorbis.get("MUSICBRAINZ_ARTISTID"), &id_separators)
+            .unwrap_or_default(),
+        mb_albumartist_ids: split_multi_value(
+            vorbis.get("MUSICBRAINZ_ALBUMARTISTID"),
+            &id_separators,
+        )
+        .unwrap_or_default(),
         mb_releasetrack_id: vorbis
             .get("MUSICBRAINZ_RELEASETRACKID")
             .and_then(|d| d[0].parse::<String>().ok()),
@@ -211,7 +291,9 @@ pub fn get_metadata_flac(path: PathBuf) -> Result<AudioMetadata> {
         release_group_disambig: vorbis
             .get("release_group_disambig")
             .and_then(|d| d[0].parse::<String>().ok()),
-        // TODO disctitle
+        disc_subtitle: vorbis
+            .get("DISCSUBTITLE")
+            .and_then(|d| d[0].parse::<String>().ok()),
         encodedby: vorbis
             .get("ENCODEDBY")
             .and_then(|d| d[0].parse::<String>().ok()),
@@ -219,7 +301,11 @@ pub fn get_metadata_flac(path: PathBuf) -> Result<AudioMetadata> {
             .get("ORIGINALYEAR")
             .and_then(|d| d[0].parse::<String>().ok()),
         initial_key: vorbis.get("KEY").and_then(|d| d[0].parse::<String>().ok()),
-        bit_rate: Some(0),
+        bit_rate: average_bit_rate(
+            std::fs::metadata(&path).map(|meta| meta.len()).ok(),
+            stream_info.total_samples,
+            stream_info.sample_rate,
+        ),
         sample_rate: stream_info.sample_rate,
         /*             bitrate_mode: vorbis
                     .get("CATALOGNUMBER")
@@ -236,15 +322,32 @@ pub fn get_metadata_flac(path: PathBuf) -> Result<AudioMetadata> {
         channels: stream_info.num_channels,
         bit_depth: stream_info.bits_per_sample,
         track: vorbis.track().unwrap_or(0),
-        disc: vorbis
-            .get("DISCNUMBER")
-            .and_then(|d| d[0].parse::<u32>().ok())
-            .unwrap_or_default(),
-        // TODO codec
+        disc: disc.unwrap_or_default(),
+        disc_total,
+        codec: Some(String::from("FLAC")),
         length: stream_info.length.unwrap_or_default(),
+        total_samples: stream_info.total_samples,
+        encoder_delay: Some(encoder_delay),
+        encoder_padding: Some(encoder_padding),
+        duration_ms: duration_ms(
+            stream_info.total_samples,
+            stream_info.sample_rate,
+            encoder_delay,
+            encoder_padding,
+        ),
         label: vorbis
             .get("LABEL")
             .and_then(|d| d[0].parse::<String>().ok()),
+        track_gain: parse_gain(
+            vorbis.get("REPLAYGAIN_TRACK_GAIN").map(|v| v[0].as_str()),
+            vorbis.get("R128_TRACK_GAIN").map(|v| v[0].as_str()),
+        ),
+        track_peak: parse_peak(vorbis.get("REPLAYGAIN_TRACK_PEAK").map(|v| v[0].as_str())),
+        album_gain: parse_gain(
+            vorbis.get("REPLAYGAIN_ALBUM_GAIN").map(|v| v[0].as_str()),
+            vorbis.get("R128_ALBUM_GAIN").map(|v| v[0].as_str()),
+        ),
+        album_peak: parse_peak(vorbis.get("REPLAYGAIN_ALBUM_PEAK").map(|v| v[0].as_str())),
         // TODO sample_rate
         // TODO bits_per_sample
         // TODO albumtypes
@@ -253,6 +356,206 @@ pub fn get_metadata_flac(path: PathBuf) -> Result<AudioMetadata> {
     };
     Ok(metadata)
 }
+// Average bit rate in kbps from the file size. Includes the tags and pictures which is close enough to compare copies
+fn average_bit_rate(
+    file_size: Option<u64>,
+    total_samples: Option<u64>,
+    sample_rate: Option<u32>,
+) -> Option<i64> {
+    let seconds = total_samples? as f64 / sample_rate.filter(|rate| *rate > 0)? as f64;
+    if seconds <= 0.0 {
+        return None;
+    }
+    Some((file_size? as f64 * 8.0 / seconds / 1000.0).round() as i64)
+}
+
+// Encoder delay and padding from iTunSMPB such as " 00000000 00000840 000001CA 00000000003F31F6 ..."
+fn parse_itunsmpb(value: &str) -> Option<(u32, u32)> {
+    let mut fields = value.split_whitespace().skip(1);
+    let delay = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let padding = u32::from_str_radix(fields.next()?, 16).ok()?;
+    Some((delay, padding))
+}
+
+// Exact duration in milliseconds of the samples left after trimming the encoder delay and padding
+fn duration_ms(
+    total_samples: Option<u64>,
+    sample_rate: Option<u32>,
+    encoder_delay: u32,
+    encoder_padding: u32,
+) -> Option<u64> {
+    let sample_rate = sample_rate.filter(|rate| *rate > 0)? as u64;
+    // FLAC stores 0 when the encoder didn't know the number of samples
+    let samples = total_samples.filter(|samples| *samples > 0)?;
+    let samples = samples.saturating_sub(encoder_delay as u64 + encoder_padding as u64);
+    Some((samples * 1000 + sample_rate / 2) / sample_rate)
+}
+
+// ReplayGain gain in dB from REPLAYGAIN_*_GAIN such as "-6.54 dB". Falls back to R128_*_GAIN which is
+// a Q7.8 number relative to -23 LUFS instead of the -18 LUFS of ReplayGain
+fn parse_gain(replaygain: Option<&str>, r128: Option<&str>) -> Option<f64> {
+    let replaygain = replaygain.and_then(|gain| {
+        let gain = gain.trim();
+        let gain = match gain.len().checked_sub(2) {
+            Some(unit)
+                if gain.is_char_boundary(unit) && gain[unit..].eq_ignore_ascii_case("db") =>
+            {
+                &gain[..unit]
+            }
+            _ => gain,
+        };
+        gain.trim().parse::<f64>().ok()
+    });
+    replaygain
+        .or_else(|| {
+            r128.and_then(|gain| gain.trim().parse::<i16>().ok())
+                .map(|gain| gain as f64 / 256.0 + 5.0)
+        })
+        .filter(|gain| gain.is_finite())
+}
+
+fn parse_peak(peak: Option<&str>) -> Option<f64> {
+    peak.and_then(|peak| peak.trim().parse::<f64>().ok())
+        .filter(|peak| peak.is_finite() && *peak >= 0.0)
+}
+
+// Splits multi-valued tags such as GENRE=Rock;Pop on any of the separators
+// Values are trimmed and deduplicated case insensitively keeping the tag order
+pub fn split_multi_value(
+    values: Option<&Vec<String>>,
+    separators: &[String],
+) -> Option<Vec<String>> {
+    let mut result: Vec<String> = Vec::new();
+    for value in values? {
+        let mut parts: Vec<&str> = vec![value.as_str()];
+        for separator in separators.iter().filter(|s| !s.is_empty()) {
+            parts = parts
+                .into_iter()
+                .flat_map(|p| p.split(separator.as_str()))
+                .collect();
+        }
+        for part in parts.into_iter().map(str::trim) {
+            if !part.is_empty() && !result.iter().any(|r| r.eq_ignore_ascii_case(part)) {
+                result.push(part.to_string());
+            }
+        }
+    }
+    (!result.is_empty()).then_some(result)
+}
+
+// Parses DISCNUMBER which is either "1" or "1/2" along with DISCTOTAL. DISCTOTAL wins when both are tagged
+fn parse_disc(disc_number: Option<&str>, disc_total: Option<&str>) -> (Option<u32>, Option<u32>) {
+    let (number, total) = match disc_number.map(|d| d.split_once('/')) {
+        Some(Some((number, total))) => (Some(number), Some(total)),
+        Some(None) => (disc_number, None),
+        None => (None, None),
+    };
+    let parse = |s: Option<&str>| s.and_then(|s| s.trim().parse::<u32>().ok());
+    (parse(number), parse(disc_total).or(parse(total)))
+}
+
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+// Albums are compilations when COMPILATION is set or the album artist is Various Artists
+fn is_compilation(compilation: Option<&str>, album_artist: Option<&str>) -> bool {
+    matches!(compilation.map(str::trim), Some("1") | Some("true"))
+        || album_artist.is_some_and(|a| a.trim().eq_ignore_ascii_case(VARIOUS_ARTISTS))
+}
+
+const FEATURING_MARKERS: [&str; 8] = [
+    "(feat. ",
+    "(ft. ",
+    "(featuring ",
+    "[feat. ",
+    " feat. ",
+    " ft. ",
+    " featuring ",
+    " feat ",
+];
+
+// Splits an artist credit such as "A & B feat. C" into the main artists and the featured artists
+pub fn parse_artist_credit(credit: &str, separators: &[String]) -> (Vec<String>, Vec<String>) {
+    // Lowercasing ascii keeps byte offsets so the index can be used on the original credit
+    let lower = credit.to_ascii_lowercase();
+    let marker = FEATURING_MARKERS
+        .iter()
+        .filter_map(|m| lower.find(m).map(|index| (index, m.len())))
+        .min();
+    let (main, featured) = match marker {
+        Some((index, len)) => (
+            &credit[..index],
+            credit[index + len..].trim_end_matches([')', ']']),
+        ),
+        None => (credit, ""),
+    };
+    let split =
+        |s: &str| split_multi_value(Some(&vec![s.to_string()]), separators).unwrap_or_default();
+    (split(main), split(featured))
+}
+
+// Returns every credited track artist in tag order along with the subset that is featured
+// The ARTISTS tag is preferred since it doesn't need any guessing. Otherwise the ARTIST credit is parsed
+fn get_artists(
+    artist: Option<&Vec<String>>,
+    artists: Option<&Vec<String>>,
+    separators: &[String],
+) -> (Vec<String>, Vec<String>) {
+    let credit = artist.map(|v| v[0].as_str()).unwrap_or_default();
+    let (main, featured) = parse_artist_credit(credit, separators);
+    match split_multi_value(artists, &[]) {
+        Some(tagged) => {
+            let featured = tagged
+                .iter()
+                .filter(|a| featured.iter().any(|f| f.eq_ignore_ascii_case(a)))
+                .cloned()
+                .collect();
+            (tagged, featured)
+        }
+        None => ([main, featured.clone()].concat(), featured),
+    }
+}
+
+// Returns the album artists from ALBUMARTISTS or the parsed ALBUMARTIST credit. Falling back to the track artists
+fn get_album_artists(
+    album_artist: Option<&Vec<String>>,
+    album_artists: Option<&Vec<String>>,
+    artists: &[String],
+    separators: &[String],
+) -> Vec<String> {
+    if let Some(tagged) = split_multi_value(album_artists, &[]) {
+        return tagged;
+    }
+    let (main, _) = parse_artist_credit(
+        album_artist.map(|v| v[0].as_str()).unwrap_or_default(),
+        separators,
+    );
+    match main.is_empty() {
+        true => artists.to_vec(),
+        false => main,
+    }
+}
+
+// Returns the album artist credit with the album artists. Compilations without an album artist are grouped
+// under Various Artists instead of the first track artist. Other albums without one are credited to the
+// album artists, which fall back to the track artists
+fn get_album_artist(
+    compilation: bool,
+    album_artist: Option<&Vec<String>>,
+    album_artists: Vec<String>,
+) -> (String, Vec<String>) {
+    match (compilation, album_artist) {
+        (true, None) => (
+            VARIOUS_ARTISTS.to_string(),
+            vec![VARIOUS_ARTISTS.to_string()],
+        ),
+        (_, Some(album_artist)) => (album_artist[0].clone(), album_artists),
+        (false, None) if album_artists.is_empty() => {
+            (PLACEHOLDER_ARTIST.to_string(), album_artists)
+        }
+        (false, None) => (album_artists.join("; "), album_artists),
+    }
+}
+
 // This is ugly. But why is there 3 different tags for date?
 // Returns year tag from VorbisComment block
 // YEAR -> DATE -> ORIGINALYEAR
@@ -298,6 +601,20 @@ fn parse_year(year: String) -> Result<i32> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_average_bit_rate() {
+        // 10 seconds of 44.1kHz audio in 1.2MB
+        assert_eq!(
+            average_bit_rate(Some(1_200_000), Some(441_000), Some(44_100)),
+            Some(960)
+        );
+        assert_eq!(
+            average_bit_rate(Some(1_200_000), Some(0), Some(44_100)),
+            None
+        );
+        assert_eq!(average_bit_rate(None, Some(441_000), Some(44_100)), None);
+    }
+
     #[test]
     fn test_parse_year_valid() {
         // Test case with a valid 10-character string
@@ -318,4 +635,166 @@ mod tests {
         // Test case with an empty string
         assert_eq!(parse_year(String::from("")).unwrap(), 0);
     }
+
+    #[test]
+    fn test_split_multi_value() {
+        let separators = vec![String::from(";"), String::from("/"), String::from(",")];
+        let values = vec![
+            String::from("Hip Hop; R&B/Soul"),
+            String::from("Pop, hip hop"),
+        ];
+        assert_eq!(
+            split_multi_value(Some(&values), &separators).unwrap(),
+            vec!["Hip Hop", "R&B", "Soul", "Pop"]
+        );
+
+        // Values without separators are kept as is
+        let values = vec![String::from("Electronic")];
+        assert_eq!(
+            split_multi_value(Some(&values), &separators).unwrap(),
+            vec!["Electronic"]
+        );
+
+        // Empty values are dropped
+        let values = vec![String::from(" ; ")];
+        assert_eq!(split_multi_value(Some(&values), &separators), None);
+        assert_eq!(split_multi_value(None, &separators), None);
+    }
+
+    #[test]
+    fn test_parse_artist_credit() {
+        let separators = vec![String::from(";"), String::from(" & ")];
+        assert_eq!(
+            parse_artist_credit("Artist A & Artist B", &separators),
+            (
+                vec![String::from("Artist A"), String::from("Artist B")],
+                vec![]
+            )
+        );
+        assert_eq!(
+            parse_artist_credit("Akon feat. Mali Music", &separators),
+            (vec![String::from("Akon")], vec![String::from("Mali Music")])
+        );
+        assert_eq!(
+            parse_artist_credit("Akon (Feat. Pitbull & Mali Music)", &separators),
+            (
+                vec![String::from("Akon")],
+                vec![String::from("Pitbull"), String::from("Mali Music")]
+            )
+        );
+        assert_eq!(parse_artist_credit("", &separators), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_get_artists_prefers_artists_tag() {
+        let separators = vec![String::from(";")];
+        let artist = vec![String::from("Simon & Garfunkel feat. Guest")];
+        let artists = vec![String::from("Simon & Garfunkel"), String::from("Guest")];
+        assert_eq!(
+            get_artists(Some(&artist), Some(&artists), &separators),
+            (artists.clone(), vec![String::from("Guest")])
+        );
+        // Duos stay together unless " & " is configured as a separator
+        assert_eq!(
+            get_artists(Some(&artist), None, &separators),
+            (artists.clone(), vec![String::from("Guest")])
+        );
+        let separators = vec![String::from(";"), String::from(" & ")];
+        assert_eq!(
+            get_artists(Some(&artist), None, &separators),
+            (
+                vec![
+                    String::from("Simon"),
+                    String::from("Garfunkel"),
+                    String::from("Guest")
+                ],
+                vec![String::from("Guest")]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_disc() {
+        assert_eq!(parse_disc(Some("1"), None), (Some(1), None));
+        assert_eq!(parse_disc(Some("2/3"), None), (Some(2), Some(3)));
+        assert_eq!(parse_disc(Some("2/3"), Some("4")), (Some(2), Some(4)));
+        assert_eq!(parse_disc(None, Some("2")), (None, Some(2)));
+        assert_eq!(parse_disc(Some("A"), None), (None, None));
+    }
+
+    #[test]
+    fn test_parse_gain() {
+        assert_eq!(parse_gain(Some("-6.54 dB"), None), Some(-6.54));
+        assert_eq!(parse_gain(Some("+1.20dB"), Some("0")), Some(1.2));
+        assert_eq!(parse_gain(Some("loud"), Some("-1024")), Some(1.0));
+        assert_eq!(parse_gain(None, Some("256")), Some(6.0));
+        assert_eq!(parse_gain(None, None), None);
+        assert_eq!(parse_peak(Some("0.988123")), Some(0.988123));
+        assert_eq!(parse_peak(Some("-1")), None);
+    }
+
+    #[test]
+    fn test_gapless() {
+        assert_eq!(
+            parse_itunsmpb(" 00000000 00000840 000001CA 00000000003F31F6 00000000 00000000"),
+            Some((2112, 458))
+        );
+        assert_eq!(parse_itunsmpb("garbage"), None);
+        // 3:27.507 at 44.1 kHz is longer than the 207 seconds of songs.length
+        assert_eq!(
+            duration_ms(Some(9_151_059), Some(44_100), 0, 0),
+            Some(207_507)
+        );
+        assert_eq!(
+            duration_ms(Some(4_140_534), Some(44_100), 2112, 458),
+            Some(93_831)
+        );
+        assert_eq!(duration_ms(Some(0), Some(44_100), 0, 0), None);
+        assert_eq!(duration_ms(Some(1000), None, 0, 0), None);
+    }
+
+    #[test]
+    fn test_is_compilation() {
+        assert!(is_compilation(Some("1"), None));
+        assert!(is_compilation(None, Some("various artists")));
+        assert!(!is_compilation(Some("0"), Some("Akon")));
+        assert!(!is_compilation(None, None));
+    }
+
+    #[test]
+    fn test_get_album_artists() {
+        let separators = vec![String::from(" & ")];
+        let artists = vec![String::from("Akon")];
+        let album_artist = vec![String::from("Artist A & Artist B")];
+        assert_eq!(
+            get_album_artists(Some(&album_artist), None, &artists, &separators),
+            vec![String::from("Artist A"), String::from("Artist B")]
+        );
+        assert_eq!(
+            get_album_artists(None, None, &artists, &separators),
+            artists
+        );
+    }
+
+    #[test]
+    fn test_get_album_artist() {
+        let artists = vec![String::from("Artist A"), String::from("Artist B")];
+        assert_eq!(
+            get_album_artist(false, None, artists.clone()),
+            (String::from("Artist A; Artist B"), artists.clone())
+        );
+        assert_eq!(
+            get_album_artist(true, None, artists.clone()).0,
+            VARIOUS_ARTISTS
+        );
+        let album_artist = vec![String::from("Artist A & Artist B")];
+        assert_eq!(
+            get_album_artist(true, Some(&album_artist), artists.clone()),
+            (String::from("Artist A & Artist B"), artists)
+        );
+        assert_eq!(
+            get_album_artist(false, None, Vec::new()).0,
+            PLACEHOLDER_ARTIST
+        );
+    }
 }
@@ -0,0 +1,403 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, Timelike};
+use sea_orm::{
+    sea_query::OnConflict, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+};
+use tokio::sync::{mpsc::Sender, watch};
+
+use crate::SETTINGS;
+
+use super::{
+    settings::RuntimeSettings,
+    task::{self, TaskCommand, TaskType},
+};
+
+// How often the scheduler checks for due tasks
+const TICK: Duration = Duration::from_secs(30);
+
+// A schedule is either a fixed interval such as "6h" or a 5 field cron expression such as "0 3 * * *"
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Interval(ChronoDuration),
+    Cron(Cron),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Cron runs on either day field matching when both are restricted
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+// Parses intervals such as 30s, 15m, 6h, 1d or 1w
+pub fn parse_interval(value: &str) -> Result<ChronoDuration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| anyhow!("Invalid interval: {}", value))?;
+    let duration = match unit {
+        "s" => ChronoDuration::seconds(amount),
+        "m" => ChronoDuration::minutes(amount),
+        "h" => ChronoDuration::hours(amount),
+        "d" => ChronoDuration::days(amount),
+        "w" => ChronoDuration::weeks(amount),
+        _ => bail!("Invalid interval unit: {}", value),
+    };
+    if duration <= ChronoDuration::zero() {
+        bail!("Interval must be positive: {}", value)
+    }
+    Ok(duration)
+}
+
+// Parses a single cron field into a bitmask. Supports *, lists, ranges and steps
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                // A step on a single value runs from that value to the end of the field
+                None if part.contains('/') => (range.parse()?, max),
+                None => (range.parse()?, range.parse()?),
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            bail!("Invalid cron field: {}", field)
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            bail!("Cron expressions need 5 fields: {}", expression)
+        };
+        let mut weekday_mask = parse_field(weekdays, 0, 7)?;
+        // Both 0 and 7 are sunday
+        if weekday_mask & (1 << 7) != 0 {
+            weekday_mask = (weekday_mask | 1) & !(1 << 7);
+        }
+        Ok(Cron {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_mask,
+            days_restricted: days != "*",
+            weekdays_restricted: weekdays != "*",
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    // Returns the first minute strictly after time matching the expression
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut next = time.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        // Bounded so an expression such as 31 2 never loops forever
+        let limit = time + ChronoDuration::days(366 * 5);
+        while next < limit {
+            if self.months & (1 << next.month()) == 0 {
+                let (year, month) = match next.month() {
+                    12 => (next.year() + 1, 1),
+                    month => (next.year(), month + 1),
+                };
+                next = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.matches_day(next.date()) {
+                next = next.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << next.hour()) == 0 {
+                next = next.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << next.minute()) == 0 {
+                next += ChronoDuration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+        None
+    }
+}
+
+impl Schedule {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.split_whitespace().count() {
+            1 => Ok(Schedule::Interval(parse_interval(value)?)),
+            _ => Ok(Schedule::Cron(Cron::parse(value)?)),
+        }
+    }
+
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Schedule::Interval(interval) => Some(time + *interval),
+            Schedule::Cron(cron) => cron.next_after(time),
+        }
+    }
+}
+
+// A task the scheduler enqueues whenever its schedule is due
+struct ScheduledTask {
+    // Name used to persist the last run in the settings table
+    key: String,
+    task: String,
+    library: Option<String>,
+    schedule: Schedule,
+}
+
+pub struct Scheduler {
+    sender: Sender<TaskCommand>,
+    database: DatabaseConnection,
+    configured: Vec<ScheduledTask>,
+    // The scan schedule of the runtime settings. Changes apply on the next tick
+    settings: watch::Receiver<RuntimeSettings>,
+}
+
+impl Scheduler {
+    pub fn new(
+        sender: Sender<TaskCommand>,
+        database: DatabaseConnection,
+        settings: watch::Receiver<RuntimeSettings>,
+    ) -> Self {
+        let configured = SETTINGS
+            .schedules
+            .iter()
+            .filter_map(|s| match Schedule::parse(&s.schedule) {
+                Ok(schedule) => Some(ScheduledTask {
+                    key: schedule_key(&s.task, s.library.as_deref()),
+                    task: s.task.clone(),
+                    library: s.library.clone(),
+                    schedule,
+                }),
+                Err(err) => {
+                    tracing::error!("Skipping schedule for {:}: {:}", s.task, err);
+                    None
+                }
+            })
+            .collect();
+        Scheduler {
+            sender,
+            database,
+            configured,
+            settings,
+        }
+    }
+
+    pub async fn run(self) {
+        tracing::info!("Started scheduler");
+        let mut ticker = tokio::time::interval(TICK);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = self.tick(Local::now().naive_local()).await {
+                tracing::error!("Scheduler tick failed: {:}", err);
+            }
+            if self.sender.is_closed() {
+                break;
+            }
+        }
+    }
+
+    // Library scan schedules are read on every tick so changes made through the API are picked up
+    async fn library_tasks(&self) -> Result<Vec<ScheduledTask>> {
+        let libraries = entity::library::Entity::find()
+            .filter(entity::library::Column::ScanSchedule.is_not_null())
+            .all(&self.database)
+            .await?;
+        Ok(libraries
+            .into_iter()
+            .filter_map(|library| {
+                let schedule = Schedule::parse(library.scan_schedule.as_deref()?).ok()?;
+                Some(ScheduledTask {
+                    key: schedule_key("scan_library_partial", Some(&library.id)),
+                    task: String::from("scan_library_partial"),
+                    library: Some(library.id),
+                    schedule,
+                })
+            })
+            .collect())
+    }
+
+    fn settings_task(&self) -> Option<ScheduledTask> {
+        let schedule = self.settings.borrow().scan_schedule.clone()?;
+        Some(ScheduledTask {
+            key: String::from("schedule_last_run:scan_schedule"),
+            task: String::from("scan_library_partial"),
+            library: None,
+            schedule: Schedule::parse(&schedule).ok()?,
+        })
+    }
+
+    async fn tick(&self, now: NaiveDateTime) -> Result<()> {
+        let library_tasks = self.library_tasks().await?;
+        let settings_task = self.settings_task();
+        for scheduled in self
+            .configured
+            .iter()
+            .chain(library_tasks.iter())
+            .chain(settings_task.iter())
+        {
+            match get_last_run(&self.database, &scheduled.key).await? {
+                // First time this schedule is seen. Start counting from now instead of running straight away
+                None => set_last_run(&self.database, &scheduled.key, now).await?,
+                Some(last_run) if is_due(&scheduled.schedule, last_run, now) => {
+                    let Some(task) =
+                        TaskType::from_name(&scheduled.task, scheduled.library.clone())
+                    else {
+                        tracing::error!("Unknown scheduled task: {:}", scheduled.task);
+                        continue;
+                    };
+                    tracing::info!("Scheduling task: {:?}", task);
+                    task::enqueue(&self.database, &self.sender, task).await?;
+                    set_last_run(&self.database, &scheduled.key, now).await?;
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn schedule_key(task: &str, library: Option<&str>) -> String {
+    match library {
+        Some(library) => format!("schedule_last_run:{task}:{library}"),
+        None => format!("schedule_last_run:{task}"),
+    }
+}
+
+fn is_due(schedule: &Schedule, last_run: NaiveDateTime, now: NaiveDateTime) -> bool {
+    schedule
+        .next_after(last_run)
+        .is_some_and(|next| next <= now)
+}
+
+async fn get_last_run(db: &DatabaseConnection, key: &str) -> Result<Option<NaiveDateTime>> {
+    let setting = entity::setting::Entity::find_by_id(key).one(db).await?;
+    Ok(setting.and_then(|setting| setting.value.parse().ok()))
+}
+
+async fn set_last_run(db: &DatabaseConnection, key: &str, time: NaiveDateTime) -> Result<()> {
+    entity::setting::Entity::insert(entity::setting::ActiveModel {
+        name: Set(key.to_owned()),
+        value: Set(time.to_string()),
+    })
+    .on_conflict(
+        OnConflict::column(entity::setting::Column::Name)
+            .update_column(entity::setting::Column::Value)
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("30s").unwrap(), ChronoDuration::seconds(30));
+        assert_eq!(parse_interval("6h").unwrap(), ChronoDuration::hours(6));
+        assert_eq!(parse_interval("1w").unwrap(), ChronoDuration::weeks(1));
+        assert!(parse_interval("6").is_err());
+        assert!(parse_interval("0h").is_err());
+        assert!(parse_interval("h").is_err());
+    }
+
+    #[test]
+    fn test_parse_cron() {
+        assert!(Cron::parse("0 3 * * *").is_ok());
+        assert!(Cron::parse("*/15 1-5 1,15 * 1-5").is_ok());
+        assert!(Cron::parse("0 3 * *").is_err());
+        assert!(Cron::parse("60 3 * * *").is_err());
+        assert!(Cron::parse("*/0 3 * * *").is_err());
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let daily = Cron::parse("0 3 * * *").unwrap();
+        assert_eq!(
+            daily.next_after(time("2023-01-01 02:59")),
+            Some(time("2023-01-01 03:00"))
+        );
+        assert_eq!(
+            daily.next_after(time("2023-01-01 03:00")),
+            Some(time("2023-01-02 03:00"))
+        );
+
+        let quarter = Cron::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            quarter.next_after(time("2023-01-01 10:16")),
+            Some(time("2023-01-01 10:30"))
+        );
+
+        // 2023-01-01 is a sunday
+        let weekly = Cron::parse("30 4 * * 7").unwrap();
+        assert_eq!(
+            weekly.next_after(time("2023-01-01 05:00")),
+            Some(time("2023-01-08 04:30"))
+        );
+
+        let yearly = Cron::parse("0 0 1 1 *").unwrap();
+        assert_eq!(
+            yearly.next_after(time("2023-06-15 12:00")),
+            Some(time("2024-01-01 00:00"))
+        );
+
+        assert_eq!(
+            Cron::parse("0 0 31 2 *")
+                .unwrap()
+                .next_after(time("2023-01-01 00:00")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_due() {
+        let schedule = Schedule::parse("6h").unwrap();
+        assert!(!is_due(
+            &schedule,
+            time("2023-01-01 00:00"),
+            time("2023-01-01 05:59")
+        ));
+        assert!(is_due(
+            &schedule,
+            time("2023-01-01 00:00"),
+            time("2023-01-01 06:00")
+        ));
+        let schedule = Schedule::parse("0 3 * * *").unwrap();
+        assert!(is_due(
+            &schedule,
+            time("2023-01-01 02:00"),
+            time("2023-01-01 03:00")
+        ));
+    }
+}
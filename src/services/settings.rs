@@ -0,0 +1,260 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::anyhow;
+use hyper::StatusCode;
+use sea_orm::{
+    sea_query::OnConflict, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, Set,
+    TransactionTrait,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+use super::{http::error::ApiError, scheduler::Schedule};
+
+// Names of the rows in the settings table. Values are stored as JSON
+const SCAN_SCHEDULE: &str = "scan_schedule";
+const TRANSCODING_PROFILES: &str = "transcoding_profiles";
+const METADATA_PROVIDERS: &str = "metadata_providers";
+
+pub const MUSICBRAINZ: &str = "musicbrainz";
+pub const ALLMUSIC: &str = "allmusic";
+const KNOWN_PROVIDERS: [&str; 2] = [MUSICBRAINZ, ALLMUSIC];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TranscodingProfile {
+    #[schema(example = "mp3_128")]
+    pub name: String,
+    // ffmpeg audio codec
+    #[schema(example = "libmp3lame")]
+    pub codec: String,
+    #[schema(example = "128k")]
+    pub bitrate: String,
+    // ffmpeg output format
+    #[schema(example = "mp3")]
+    pub format: String,
+    // Applies the track or album gain of songs. Songs without a gain are transcoded unchanged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaygain: Option<ReplayGainMode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayGainMode {
+    Track,
+    // Falls back to the track gain for songs without an album gain
+    Album,
+}
+
+// Settings that can be changed while Deaftone is running. Library roots are edited through /libraries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RuntimeSettings {
+    // Partial scan of every library on a cron expression or interval. No scan when missing
+    #[schema(example = "6h")]
+    pub scan_schedule: Option<String>,
+    // The first profile is used when a transcode request doesn't name one
+    pub transcoding_profiles: Vec<TranscodingProfile>,
+    // Sources used by the metadata task. musicbrainz for links and allmusic for biographies
+    #[schema(example = json!(["musicbrainz", "allmusic"]))]
+    pub metadata_providers: Vec<String>,
+}
+
+impl Default for RuntimeSettings {
+    fn default() -> Self {
+        RuntimeSettings {
+            scan_schedule: None,
+            transcoding_profiles: vec![TranscodingProfile {
+                name: String::from("mp3_128"),
+                codec: String::from("libmp3lame"),
+                bitrate: String::from("128k"),
+                format: String::from("mp3"),
+                replaygain: None,
+            }],
+            metadata_providers: KNOWN_PROVIDERS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl RuntimeSettings {
+    // Profile with name or the default profile when name is missing
+    pub fn transcoding_profile(&self, name: Option<&str>) -> Option<&TranscodingProfile> {
+        match name {
+            Some(name) => self.transcoding_profiles.iter().find(|p| p.name == name),
+            None => self.transcoding_profiles.first(),
+        }
+    }
+
+    pub fn metadata_provider_enabled(&self, provider: &str) -> bool {
+        self.metadata_providers.iter().any(|p| p == provider)
+    }
+
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if let Some(schedule) = &self.scan_schedule {
+            Schedule::parse(schedule)
+                .map_err(|err| anyhow!("Invalid scan_schedule {}: {}", schedule, err))?;
+        }
+        if self.transcoding_profiles.is_empty() {
+            return Err(anyhow!("At least one transcoding profile is required"));
+        }
+        let mut names = HashSet::new();
+        for profile in &self.transcoding_profiles {
+            if [
+                &profile.name,
+                &profile.codec,
+                &profile.bitrate,
+                &profile.format,
+            ]
+            .iter()
+            .any(|value| value.trim().is_empty())
+            {
+                return Err(anyhow!(
+                    "Transcoding profiles need a name, codec, bitrate and format"
+                ));
+            }
+            // Values are passed to ffmpeg as arguments
+            if [&profile.codec, &profile.bitrate, &profile.format]
+                .iter()
+                .any(|value| value.starts_with('-'))
+            {
+                return Err(anyhow!("Invalid transcoding profile: {}", profile.name));
+            }
+            if !names.insert(profile.name.as_str()) {
+                return Err(anyhow!("Duplicate transcoding profile: {}", profile.name));
+            }
+        }
+        if let Some(provider) = self
+            .metadata_providers
+            .iter()
+            .find(|p| !KNOWN_PROVIDERS.contains(&p.as_str()))
+        {
+            return Err(anyhow!(
+                "Unknown metadata provider {}. Expected one of {}",
+                provider,
+                KNOWN_PROVIDERS.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Reads a setting stored as JSON. Returns None for missing or unreadable values
+pub async fn get_setting<T: DeserializeOwned>(
+    db: &impl ConnectionTrait,
+    name: &str,
+) -> Result<Option<T>, DbErr> {
+    let Some(setting) = entity::setting::Entity::find_by_id(name).one(db).await? else {
+        return Ok(None);
+    };
+    match serde_json::from_str(&setting.value) {
+        Ok(value) => Ok(Some(value)),
+        Err(err) => {
+            tracing::warn!("Ignoring invalid setting {:}: {:}", name, err);
+            Ok(None)
+        }
+    }
+}
+
+pub async fn set_setting<T: Serialize>(
+    db: &impl ConnectionTrait,
+    name: &str,
+    value: &T,
+) -> Result<(), DbErr> {
+    let value = serde_json::to_string(value).map_err(|err| DbErr::Custom(err.to_string()))?;
+    entity::setting::Entity::insert(entity::setting::ActiveModel {
+        name: Set(name.to_owned()),
+        value: Set(value),
+    })
+    .on_conflict(
+        OnConflict::column(entity::setting::Column::Name)
+            .update_column(entity::setting::Column::Value)
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+// Holds the runtime settings and sends every change to the services watching them
+#[derive(Clone, Debug)]
+pub struct SettingsService {
+    sender: Arc<watch::Sender<RuntimeSettings>>,
+}
+
+impl SettingsService {
+    // Loads the stored settings. Settings that were never changed use their defaults
+    pub async fn load(db: &DatabaseConnection) -> Result<Self, DbErr> {
+        let defaults = RuntimeSettings::default();
+        let settings = RuntimeSettings {
+            scan_schedule: get_setting(db, SCAN_SCHEDULE).await?.flatten(),
+            transcoding_profiles: get_setting(db, TRANSCODING_PROFILES)
+                .await?
+                .unwrap_or(defaults.transcoding_profiles),
+            metadata_providers: get_setting(db, METADATA_PROVIDERS)
+                .await?
+                .unwrap_or(defaults.metadata_providers),
+        };
+        let (sender, _) = watch::channel(settings);
+        Ok(SettingsService {
+            sender: Arc::new(sender),
+        })
+    }
+
+    pub fn current(&self) -> RuntimeSettings {
+        self.sender.borrow().clone()
+    }
+
+    // Receives the settings again whenever they change
+    pub fn subscribe(&self) -> watch::Receiver<RuntimeSettings> {
+        self.sender.subscribe()
+    }
+
+    // Validates and stores the settings before sending them to the running services
+    pub async fn update(
+        &self,
+        db: &DatabaseConnection,
+        settings: RuntimeSettings,
+    ) -> Result<RuntimeSettings, ApiError> {
+        settings
+            .validate()
+            .map_err(|err| ApiError(StatusCode::BAD_REQUEST, err))?;
+        let tx = db.begin().await?;
+        set_setting(&tx, SCAN_SCHEDULE, &settings.scan_schedule).await?;
+        set_setting(&tx, TRANSCODING_PROFILES, &settings.transcoding_profiles).await?;
+        set_setting(&tx, METADATA_PROVIDERS, &settings.metadata_providers).await?;
+        tx.commit().await?;
+        tracing::info!("Updated runtime settings: {:?}", settings);
+        self.sender.send_replace(settings.clone());
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut settings = RuntimeSettings::default();
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.transcoding_profile(None).unwrap().name, "mp3_128");
+        assert!(settings.transcoding_profile(Some("opus")).is_none());
+
+        settings.scan_schedule = Some(String::from("every day"));
+        assert!(settings.validate().is_err());
+        settings.scan_schedule = Some(String::from("0 3 * * *"));
+        assert!(settings.validate().is_ok());
+
+        settings.metadata_providers = vec![String::from("lastfm")];
+        assert!(settings.validate().is_err());
+        settings.metadata_providers = Vec::new();
+        assert!(settings.validate().is_ok());
+
+        settings
+            .transcoding_profiles
+            .push(settings.transcoding_profiles[0].clone());
+        assert!(settings.validate().is_err());
+        settings.transcoding_profiles[1].name = String::from("opus");
+        settings.transcoding_profiles[1].bitrate = String::from("-y");
+        assert!(settings.validate().is_err());
+    }
+}
@@ -1,8 +1,10 @@
 use anyhow::anyhow;
 use chrono::Utc;
 use hyper::StatusCode;
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
-use sqlx::{sqlite::SqliteQueryResult, Sqlite, Transaction};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ActiveValue::NotSet, ColumnTrait, ConnectionTrait,
+    DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, Set,
+};
 
 use uuid::Uuid;
 
@@ -45,7 +47,23 @@ pub async fn like_song(db: &DatabaseConnection, song_id: String) -> Result<bool,
     }
     Ok(song.update(db).await?.liked)
 }
-pub async fn _get_song_by_path(
+
+// Records a play of the song in the play history. Duration defaults to the full length of the song
+pub async fn scrobble_song(
+    db: &DatabaseConnection,
+    song_id: &str,
+    duration: Option<i32>,
+) -> Result<entity::play::Model, ApiError> {
+    let song = get_song_by_id(db, song_id).await?;
+    let play = entity::play::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        song_id: Set(Some(song.id)),
+        played_at: Set(Utc::now().naive_local()),
+        duration: Set(duration.unwrap_or(song.length)),
+    };
+    Ok(play.insert(db).await?)
+}
+pub async fn get_song_by_path(
     db: &DatabaseConnection,
     path: String,
 ) -> anyhow::Result<Option<entity::song::Model>> {
@@ -57,162 +75,142 @@ pub async fn _get_song_by_path(
     Ok(song)
 }
 
-// // Creates a song entry with with the passed album_id and AudioMetadata block
+// Returns the id of the song with path when it has been scanned before
+pub async fn find_song_id(
+    db: &impl ConnectionTrait,
+    path: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    Ok(entity::song::Entity::find()
+        .select_only()
+        .column(entity::song::Column::Id)
+        .filter(entity::song::Column::Path.eq(path))
+        .into_tuple()
+        .one(db)
+        .await?)
+}
+
+// Years are sometimes tagged as full dates such as 2019-03-08
+fn parse_year(value: &str) -> Option<i32> {
+    value.trim().split('-').next()?.parse().ok()
+}
+
+// Leaves the column alone for untagged files so values computed by tasks survive rescans
+fn tagged<T>(value: Option<T>) -> ActiveValue<Option<T>>
+where
+    Option<T>: Into<sea_orm::Value>,
+{
+    match value {
+        Some(value) => Set(Some(value)),
+        None => NotSet,
+    }
+}
+
+// Creates a song entry with the passed album_id and AudioMetadata block. Returning the id of the song
+// A song that was scanned before is updated in place so plays, likes and playlists keep pointing at it
 pub async fn create_song(
-    tx: &mut Transaction<'_, Sqlite>,
+    db: &impl ConnectionTrait,
+    existing_id: Option<&str>,
     album_id: &str,
+    library_id: &str,
     metadata: &AudioMetadata,
-) -> Result<SqliteQueryResult, anyhow::Error> {
-    let id: Uuid = Uuid::new_v4();
-    let init_time: String = Utc::now().naive_local().to_string();
-    Ok(sqlx::query(
-        "INSERT OR REPLACE INTO songs (
-            id,
-            path,
-            title,
-            artist,
-            artist_sort,
-            album_name,
-            album_artist,
-            album_sort,
-            discogs_albumid,
-            discogs_artistid,
-            discogs_labelid,
-            lyricist,
-            composer,
-            composer_sort,
-            work,
-            mb_workid,
-            arranger,
-            grouping,
-            year,
-            lyrics,
-            comments,
-            bpm,
-            comp,
-            mb_track_id,
-            mb_album_id,
-            mb_artist_id,
-            mb_albumartist_id,
-            mb_releasetrack_id,
-            mb_releasegroup_id,
-            track_disambig,
-            album_type,
-            acoustid_fingerprint,
-            acoustid_id,
-            asin,
-            isrc,
-            catalog_num,
-            script,
-            country,
-            album_status,
-            media,
-            album_disambig,
-            release_group_disambig,
-            encoder,
-            original_year,
-            initial_key,
-            bit_rate,
-            encoder_settings,
-            track,
-            disk,
-            length,
-            label,
-            sample_rate,
-            bits_per_sample,
-            created_at,
-            updated_at,
-            album_id,
-            liked
-         )
-    VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)",
-    )
-    .bind(id.to_string())
-    .bind(&metadata.path)
-    .bind(&metadata.name)
-    .bind(&metadata.artist)
-    .bind(&metadata.artist_sort)
-    .bind(&metadata.album_name)
-    .bind(&metadata.album_artist)
-    .bind(&metadata.album_sort)
-    /*     .bind(
-            &metadata
-                .genre
-                .into_iter()
-                .map(|i| String::from("{:?}", i))
-                .collect::<String>(),
-        ) */
-    /*     .bind(&metadata.style)
-     */
-    .bind(&metadata.discogs_albumid)
-    .bind(&metadata.discogs_artistid)
-    .bind(&metadata.discogs_labelid)
-    .bind(&metadata.lyricist)
-    .bind(&metadata.composer)
-    .bind(&metadata.composer_sort)
-    .bind(&metadata.work)
-    .bind(&metadata.mb_workid)
-    /*     .bind(&metadata.work_disambig)
-     */
-    .bind(&metadata.arranger)
-    .bind(&metadata.grouping)
-    .bind(metadata.year)
-    .bind(&metadata.lyrics)
-    .bind(&metadata.comments)
-    .bind(metadata.bpm)
-    .bind(&metadata.compilation)
-    .bind(&metadata.mb_track_id)
-    .bind(&metadata.mb_album_id)
-    .bind(&metadata.mb_artist_id)
-    .bind(&metadata.mb_albumartist_id)
-    .bind(&metadata.mb_releasetrack_id)
-    .bind(&metadata.mb_releasegroup_id)
-    .bind(&metadata.trackdisambig)
-    .bind(&metadata.album_type)
-    /*     .bind(&metadata.albumtypes)
-     */
-    .bind(&metadata.acoustid_fingerprint)
-    .bind(&metadata.acoustid_id)
-    .bind(&metadata.asin)
-    .bind(&metadata.isrc)
-    .bind(&metadata.catalog_num)
-    .bind(&metadata.script)
-    /*     .bind(&metadata.language)
-     */
-    .bind(&metadata.country)
-    .bind(&metadata.albumstatus)
-    .bind(&metadata.media)
-    .bind(&metadata.album_disambig)
-    .bind(&metadata.release_group_disambig)
-    /*     .bind(&metadata.disctitle)
-     */
-    .bind(&metadata.encodedby)
-    .bind(&metadata.original_year)
-    .bind(&metadata.initial_key)
-    .bind(metadata.bit_rate)
-    /*     .bind(&metadata.bitrate)
-        .bind(&metadata.bitrate_mode) */
-    /*     .bind(&metadata.encoder_info)
-     */
-    .bind(&metadata.encoder_settings)
-    /*     .bind(&metadata.format)
-    .bind(&metadata.bitdepth)
-    .bind(&metadata.channels) */
-    .bind(metadata.track)
-    .bind(metadata.disc)
-    /*     .bind(&metadata.codec)
-     */
-    .bind(metadata.length)
-    .bind(&metadata.label)
-    .bind(metadata.sample_rate)
-    .bind(metadata.bit_depth)
+) -> Result<String, anyhow::Error> {
+    let init_time = Utc::now().naive_local();
+    let mut song = entity::song::ActiveModel {
+        path: Set(metadata.path.clone()),
+        title: Set(metadata.name.clone()),
+        artist: Set(metadata.artist.clone()),
+        artist_sort: Set(metadata.artist_sort.clone()),
+        album_name: Set(metadata.album_name.clone()),
+        album_artist: Set(Some(metadata.album_artist.clone())),
+        album_sort: Set(metadata.album_sort.clone()),
+        genre: Set(metadata.genre.as_ref().map(|g| g.join("; "))),
+        style: Set(metadata.style.as_ref().map(|s| s.join("; "))),
+        discogs_albumid: Set(metadata.discogs_albumid.clone()),
+        discogs_artistid: Set(metadata.discogs_artistid.clone()),
+        discogs_labelid: Set(metadata.discogs_labelid.clone()),
+        lyricist: Set(metadata.lyricist.clone()),
+        composer: Set(metadata.composer.clone()),
+        composer_sort: Set(metadata.composer_sort.clone()),
+        work: Set(metadata.work.clone()),
+        mb_workid: Set(metadata.mb_workid.clone()),
+        arranger: Set(metadata.arranger.clone()),
+        grouping: Set(metadata.grouping.clone()),
+        year: Set(Some(metadata.year)),
+        lyrics: Set(metadata.lyrics.clone()),
+        comments: Set(metadata.comments.clone()),
+        bpm: Set(metadata.bpm),
+        comp: Set(Some(metadata.compilation as i32)),
+        mb_track_id: Set(metadata.mb_track_id.clone()),
+        mb_recording_id: Set(metadata.mb_recording_id.clone()),
+        mb_album_id: Set(metadata.mb_album_id.clone()),
+        mb_artist_id: Set(metadata.mb_artist_id.clone()),
+        mb_albumartist_id: Set(metadata.mb_albumartist_id.clone()),
+        mb_releasetrack_id: Set(metadata.mb_releasetrack_id.clone()),
+        mb_releasegroup_id: Set(metadata.mb_releasegroup_id.clone()),
+        track_disambig: Set(metadata.trackdisambig.clone()),
+        album_type: Set(metadata.album_type.clone()),
+        // Fingerprints computed by scan_fingerprints are kept when the file isn't tagged with one
+        acoustid_fingerprint: tagged(metadata.acoustid_fingerprint.clone()),
+        acoustid_id: Set(metadata.acoustid_id.clone()),
+        asin: Set(metadata.asin.clone()),
+        isrc: Set(metadata.isrc.clone()),
+        catalog_num: Set(metadata.catalog_num.clone()),
+        script: Set(metadata.script.clone()),
+        country: Set(metadata.country.clone()),
+        album_status: Set(metadata.albumstatus.clone()),
+        media: Set(metadata.media.clone()),
+        album_disambig: Set(metadata.album_disambig.clone()),
+        release_group_disambig: Set(metadata.release_group_disambig.clone()),
+        disc_title: Set(metadata.disc_subtitle.clone()),
+        encoder: Set(metadata.encodedby.clone()),
+        original_year: Set(metadata.original_year.as_deref().and_then(parse_year)),
+        initial_key: Set(metadata.initial_key.clone()),
+        bit_rate: Set(metadata.bit_rate),
+        encoder_settings: Set(metadata.encoder_settings.clone()),
+        track: Set(Some(metadata.track as i32)),
+        disk: Set(Some(metadata.disc as i32)),
+        disc_total: Set(metadata.disc_total.map(|total| total as i32)),
+        codec: Set(metadata.codec.clone()),
+        length: Set(metadata.length as i32),
+        label: Set(metadata.label.clone()),
+        sample_rate: Set(metadata.sample_rate.map(|rate| rate.to_string())),
+        bits_per_sample: Set(metadata.bit_depth.map(i32::from)),
+        // Loudness measured by scan_loudness is kept unless the file is tagged with ReplayGain
+        track_gain: tagged(metadata.track_gain),
+        track_peak: tagged(metadata.track_peak),
+        album_gain: tagged(metadata.album_gain),
+        album_peak: tagged(metadata.album_peak),
+        total_samples: Set(metadata.total_samples.map(|samples| samples as i64)),
+        encoder_delay: Set(metadata.encoder_delay.map(|delay| delay as i32)),
+        encoder_padding: Set(metadata.encoder_padding.map(|padding| padding as i32)),
+        duration_ms: Set(metadata.duration_ms.map(|duration| duration as i64)),
+        updated_at: Set(init_time),
+        album_id: Set(Some(album_id.to_owned())),
+        library_id: Set(Some(library_id.to_owned())),
+        ..Default::default()
+    };
+    match existing_id {
+        Some(id) => {
+            song.id = Set(id.to_owned());
+            Ok(song.update(db).await?.id)
+        }
+        None => {
+            song.id = Set(Uuid::new_v4().to_string());
+            song.created_at = Set(init_time);
+            song.liked = Set(false);
+            Ok(song.insert(db).await?.id)
+        }
+    }
+}
 
-    /*     .bind(&metadata.sample_rate)
-    .bind(&metadata.bits_per_sample) */
-    .bind(&init_time)
-    .bind(&init_time)
-    .bind(album_id)
-    .bind(false)
-    .execute(&mut **tx)
-    .await?)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_year() {
+        assert_eq!(parse_year("2019"), Some(2019));
+        assert_eq!(parse_year("2019-03-08"), Some(2019));
+        assert_eq!(parse_year("unknown"), None);
+    }
 }
@@ -0,0 +1,374 @@
+use chrono::{Duration, NaiveDate};
+use sea_orm::{
+    ConnectionTrait, DatabaseBackend, DatabaseConnection, FromQueryResult, Statement, Value,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::http::error::ApiError;
+
+#[derive(Debug, FromQueryResult, Serialize, Deserialize, ToSchema)]
+pub struct TopItem {
+    pub id: String,
+    pub name: String,
+    pub artist: Option<String>,
+    pub plays: i64,
+    pub listened: i64,
+}
+
+#[derive(Debug, FromQueryResult, Serialize, Deserialize, ToSchema)]
+pub struct ListeningTime {
+    pub period: String,
+    pub plays: i64,
+    pub listened: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LibraryGrowth {
+    pub period: String,
+    pub albums: i64,
+    pub songs: i64,
+    pub total_albums: i64,
+    pub total_songs: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct GrowthRow {
+    period: String,
+    albums: i64,
+    songs: i64,
+}
+
+#[derive(Debug, FromQueryResult, Serialize, Deserialize, ToSchema)]
+pub struct FormatBreakdown {
+    pub codec: Option<String>,
+    pub sample_rate: Option<String>,
+    pub bits_per_sample: Option<i32>,
+    pub songs: i64,
+    pub length: i64,
+}
+
+// Time window applied to the plays table. Both ends are optional and the to date is inclusive
+pub struct StatsWindow {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl StatsWindow {
+    // Returns the conditions and values needed to limit plays.played_at to the window
+    fn conditions(&self) -> (Vec<&'static str>, Vec<Value>) {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+        if let Some(from) = self.from {
+            conditions.push("plays.played_at >= ?");
+            values.push(from.and_hms_opt(0, 0, 0).unwrap().into());
+        }
+        if let Some(to) = self.to {
+            conditions.push("plays.played_at < ?");
+            values.push(
+                (to + Duration::days(1))
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+                    .into(),
+            );
+        }
+        (conditions, values)
+    }
+}
+
+// Maps the interval query param onto a strftime format. Defaults to day. Weeks are bucketed by period
+fn interval_format(interval: Option<&str>) -> &'static str {
+    match interval {
+        Some("month") => "%Y-%m",
+        Some("year") => "%Y",
+        _ => "%Y-%m-%d",
+    }
+}
+
+// Same buckets as interval_format written as a to_char format
+fn interval_format_postgres(interval: Option<&str>) -> &'static str {
+    match interval {
+        Some("month") => "YYYY-MM",
+        Some("year") => "YYYY",
+        _ => "YYYY-MM-DD",
+    }
+}
+
+// Returns the SQL bucketing the timestamp column by interval. Weeks start on Monday and are named after
+// that date on both backends so a week spanning New Year stays a single bucket
+fn period(backend: DatabaseBackend, interval: Option<&str>, column: &str) -> String {
+    match (backend, interval) {
+        (DatabaseBackend::Postgres, Some("week")) => {
+            format!("to_char(date_trunc('week', {column}), 'YYYY-MM-DD')")
+        }
+        (DatabaseBackend::Postgres, _) => {
+            format!(
+                "to_char({column}, '{}')",
+                interval_format_postgres(interval)
+            )
+        }
+        // Going back six days and then forward to the next Monday lands on the Monday of the week
+        (_, Some("week")) => format!("date({column}, '-6 days', 'weekday 1')"),
+        _ => format!("strftime('{}', {column})", interval_format(interval)),
+    }
+}
+
+// Builds the statement for the backend. Queries are written with ? placeholders which Postgres numbers as $1, $2..
+fn statement(backend: DatabaseBackend, sql: &str, values: Vec<Value>) -> Statement {
+    let sql = match backend {
+        DatabaseBackend::Postgres => {
+            let mut numbered = String::with_capacity(sql.len());
+            for (index, part) in sql.split('?').enumerate() {
+                if index > 0 {
+                    numbered.push_str(&format!("${index}"));
+                }
+                numbered.push_str(part);
+            }
+            numbered
+        }
+        _ => sql.to_owned(),
+    };
+    Statement::from_sql_and_values(backend, &sql, values)
+}
+
+fn where_clause(conditions: &[&str]) -> String {
+    match conditions.is_empty() {
+        true => String::new(),
+        false => format!("WHERE {}", conditions.join(" AND ")),
+    }
+}
+
+// Runs a top N query over the plays table. Select must provide id, name and artist columns
+async fn top_items(
+    db: &DatabaseConnection,
+    select: &str,
+    joins: &str,
+    group_by: &str,
+    window: &StatsWindow,
+    limit: Option<u64>,
+) -> Result<Vec<TopItem>, ApiError> {
+    let (conditions, mut values) = window.conditions();
+    values.push((limit.unwrap_or(50) as i64).into());
+    let sql = format!(
+        "SELECT {select}, COUNT(plays.id) AS plays, SUM(plays.duration) AS listened
+        FROM plays
+        INNER JOIN songs ON songs.id = plays.song_id
+        {joins}
+        {}
+        GROUP BY {group_by}
+        ORDER BY plays DESC, listened DESC
+        LIMIT ?",
+        where_clause(&conditions)
+    );
+    Ok(
+        TopItem::find_by_statement(statement(db.get_database_backend(), &sql, values))
+            .all(db)
+            .await?,
+    )
+}
+
+// Returns the most played songs inside the window
+pub async fn get_top_songs(
+    db: &DatabaseConnection,
+    window: &StatsWindow,
+    limit: Option<u64>,
+) -> Result<Vec<TopItem>, ApiError> {
+    top_items(
+        db,
+        "songs.id AS id, songs.title AS name, songs.artist AS artist",
+        "",
+        "songs.id",
+        window,
+        limit,
+    )
+    .await
+}
+
+// Returns the most played albums inside the window
+pub async fn get_top_albums(
+    db: &DatabaseConnection,
+    window: &StatsWindow,
+    limit: Option<u64>,
+) -> Result<Vec<TopItem>, ApiError> {
+    top_items(
+        db,
+        "albums.id AS id, albums.name AS name, albums.artist_name AS artist",
+        "INNER JOIN albums ON albums.id = songs.album_id",
+        "albums.id",
+        window,
+        limit,
+    )
+    .await
+}
+
+// Returns the most played album artists inside the window
+pub async fn get_top_artists(
+    db: &DatabaseConnection,
+    window: &StatsWindow,
+    limit: Option<u64>,
+) -> Result<Vec<TopItem>, ApiError> {
+    top_items(
+        db,
+        "artists.id AS id, artists.name AS name, CAST(NULL AS TEXT) AS artist",
+        "INNER JOIN albums ON albums.id = songs.album_id
+        INNER JOIN artists ON artists.id = albums.artist_id",
+        "artists.id",
+        window,
+        limit,
+    )
+    .await
+}
+
+// Returns the most played genres inside the window
+pub async fn get_top_genres(
+    db: &DatabaseConnection,
+    window: &StatsWindow,
+    limit: Option<u64>,
+) -> Result<Vec<TopItem>, ApiError> {
+    top_items(
+        db,
+        "genres.id AS id, genres.name AS name, CAST(NULL AS TEXT) AS artist",
+        "INNER JOIN song_genres ON song_genres.song_id = songs.id
+        INNER JOIN genres ON genres.id = song_genres.genre_id",
+        "genres.id",
+        window,
+        limit,
+    )
+    .await
+}
+
+// Returns play count and seconds listened bucketed by day, week, month or year
+pub async fn get_listening_time(
+    db: &DatabaseConnection,
+    window: &StatsWindow,
+    interval: Option<&str>,
+) -> Result<Vec<ListeningTime>, ApiError> {
+    let backend = db.get_database_backend();
+    let (conditions, values) = window.conditions();
+    let sql = format!(
+        "SELECT {} AS period, COUNT(plays.id) AS plays, SUM(plays.duration) AS listened
+        FROM plays
+        {}
+        GROUP BY period
+        ORDER BY period",
+        period(backend, interval, "plays.played_at"),
+        where_clause(&conditions)
+    );
+    Ok(
+        ListeningTime::find_by_statement(statement(backend, &sql, values))
+            .all(db)
+            .await?,
+    )
+}
+
+// Returns albums and songs added per bucket along with the running totals of the library
+pub async fn get_library_growth(
+    db: &DatabaseConnection,
+    interval: Option<&str>,
+) -> Result<Vec<LibraryGrowth>, ApiError> {
+    let backend = db.get_database_backend();
+    let created = period(backend, interval, "created_at");
+    let sql = format!(
+        "SELECT period, SUM(albums) AS albums, SUM(songs) AS songs FROM (
+            SELECT {created} AS period, 1 AS albums, 0 AS songs FROM albums
+            UNION ALL
+            SELECT {created} AS period, 0 AS albums, 1 AS songs FROM songs
+        ) AS added
+        GROUP BY period
+        ORDER BY period"
+    );
+    let rows = GrowthRow::find_by_statement(statement(backend, &sql, Vec::new()))
+        .all(db)
+        .await?;
+
+    let (mut total_albums, mut total_songs) = (0, 0);
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            total_albums += row.albums;
+            total_songs += row.songs;
+            LibraryGrowth {
+                period: row.period,
+                albums: row.albums,
+                songs: row.songs,
+                total_albums,
+                total_songs,
+            }
+        })
+        .collect())
+}
+
+// Returns song count and total length grouped by codec, sample rate and bit depth
+pub async fn get_format_breakdown(
+    db: &DatabaseConnection,
+) -> Result<Vec<FormatBreakdown>, ApiError> {
+    Ok(FormatBreakdown::find_by_statement(Statement::from_string(
+        db.get_database_backend(),
+        "SELECT codec, sample_rate, bits_per_sample, COUNT(id) AS songs, SUM(length) AS length
+        FROM songs
+        GROUP BY codec, sample_rate, bits_per_sample
+        ORDER BY songs DESC"
+            .to_owned(),
+    ))
+    .all(db)
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_format() {
+        assert_eq!(interval_format(Some("month")), "%Y-%m");
+        assert_eq!(interval_format(Some("year")), "%Y");
+        assert_eq!(interval_format(Some("day")), "%Y-%m-%d");
+        assert_eq!(interval_format(None), "%Y-%m-%d");
+        assert_eq!(
+            period(DatabaseBackend::Sqlite, Some("month"), "created_at"),
+            "strftime('%Y-%m', created_at)"
+        );
+        assert_eq!(
+            period(DatabaseBackend::Postgres, Some("week"), "created_at"),
+            "to_char(date_trunc('week', created_at), 'YYYY-MM-DD')"
+        );
+        assert_eq!(
+            period(DatabaseBackend::Sqlite, Some("week"), "created_at"),
+            "date(created_at, '-6 days', 'weekday 1')"
+        );
+    }
+
+    #[test]
+    fn test_statement_placeholders() {
+        let sql = "SELECT * FROM plays WHERE played_at >= ? LIMIT ?";
+        let values: Vec<Value> = vec![1.into(), 2.into()];
+        assert_eq!(
+            statement(DatabaseBackend::Postgres, sql, values.clone()).sql,
+            "SELECT * FROM plays WHERE played_at >= $1 LIMIT $2"
+        );
+        assert_eq!(statement(DatabaseBackend::Sqlite, sql, values).sql, sql);
+    }
+
+    #[test]
+    fn test_window_conditions() {
+        let window = StatsWindow {
+            from: None,
+            to: None,
+        };
+        let (conditions, values) = window.conditions();
+        assert!(conditions.is_empty());
+        assert!(values.is_empty());
+        assert_eq!(where_clause(&conditions), "");
+
+        let window = StatsWindow {
+            from: NaiveDate::from_ymd_opt(2023, 1, 1),
+            to: NaiveDate::from_ymd_opt(2023, 12, 31),
+        };
+        let (conditions, values) = window.conditions();
+        assert_eq!(values.len(), 2);
+        assert_eq!(
+            where_clause(&conditions),
+            "WHERE plays.played_at >= ? AND plays.played_at < ?"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+use anyhow::anyhow;
+use chrono::Utc;
+use hyper::StatusCode;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, Set,
+};
+use uuid::Uuid;
+
+use crate::services::{
+    events::{Event, EventBus, ScanCounters},
+    http::error::ApiError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Queued => "queued",
+            TaskStatus::Running => "running",
+            TaskStatus::Succeeded => "succeeded",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+// Counters updated by the scanner and metadata jobs while a task runs. Flushed to the tasks table periodically
+// Jobs check is_cancelled between items and stop early once the task is cancelled
+#[derive(Debug, Default)]
+pub struct TaskProgress {
+    events: EventBus,
+    cancelled: AtomicBool,
+    directories_walked: AtomicI32,
+    songs_added: AtomicI32,
+    songs_updated: AtomicI32,
+    songs_removed: AtomicI32,
+    artists_updated: AtomicI32,
+}
+
+impl TaskProgress {
+    pub fn new(events: EventBus) -> Self {
+        TaskProgress {
+            events,
+            ..Default::default()
+        }
+    }
+    // Publishes events such as added songs on the bus of the service running the task
+    pub fn publish(&self, event: Event) {
+        self.events.publish(event);
+    }
+    pub fn counters(&self) -> ScanCounters {
+        ScanCounters {
+            directories_walked: self.directories_walked.load(Ordering::Relaxed),
+            songs_added: self.songs_added.load(Ordering::Relaxed),
+            songs_updated: self.songs_updated.load(Ordering::Relaxed),
+            songs_removed: self.songs_removed.load(Ordering::Relaxed),
+            artists_updated: self.artists_updated.load(Ordering::Relaxed),
+        }
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+    // Returns an error once the task has been cancelled so jobs can bail out with ?
+    pub fn check_cancelled(&self) -> anyhow::Result<()> {
+        match self.is_cancelled() {
+            true => Err(anyhow!("Task cancelled")),
+            false => Ok(()),
+        }
+    }
+    pub fn directory_walked(&self) {
+        self.directories_walked.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn song_added(&self) {
+        self.songs_added.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn song_updated(&self) {
+        self.songs_updated.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn songs_removed(&self, count: u64) {
+        self.songs_removed
+            .fetch_add(count as i32, Ordering::Relaxed);
+    }
+    pub fn artist_updated(&self) {
+        self.artists_updated.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Records a queued task and returns its id
+pub async fn create_task(
+    db: &DatabaseConnection,
+    kind: &str,
+    library_id: Option<&str>,
+) -> Result<String, DbErr> {
+    let task = entity::task::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        kind: Set(kind.to_owned()),
+        library_id: Set(library_id.map(str::to_owned)),
+        status: Set(TaskStatus::Queued.as_str().to_owned()),
+        error: Set(None),
+        directories_walked: Set(0),
+        songs_added: Set(0),
+        songs_updated: Set(0),
+        songs_removed: Set(0),
+        artists_updated: Set(0),
+        created_at: Set(Utc::now().naive_local()),
+        started_at: Set(None),
+        finished_at: Set(None),
+    };
+    Ok(task.insert(db).await?.id)
+}
+
+// Id of a queued task with the same kind and library. Used to avoid queueing identical tasks
+pub async fn find_queued(
+    db: &DatabaseConnection,
+    kind: &str,
+    library_id: Option<&str>,
+) -> Result<Option<String>, DbErr> {
+    Ok(entity::task::Entity::find()
+        .filter(entity::task::Column::Kind.eq(kind))
+        .filter(match library_id {
+            Some(library_id) => entity::task::Column::LibraryId.eq(library_id),
+            None => entity::task::Column::LibraryId.is_null(),
+        })
+        .filter(entity::task::Column::Status.eq(TaskStatus::Queued.as_str()))
+        .one(db)
+        .await?
+        .map(|task| task.id))
+}
+
+// Tasks left queued or running by a previous run of Deaftone will never finish. Mark them failed on startup
+pub async fn fail_interrupted(db: &DatabaseConnection) -> Result<u64, DbErr> {
+    Ok(entity::task::Entity::update_many()
+        .col_expr(
+            entity::task::Column::Status,
+            Expr::value(TaskStatus::Failed.as_str()),
+        )
+        .col_expr(
+            entity::task::Column::Error,
+            Expr::value("Interrupted by shutdown"),
+        )
+        .col_expr(
+            entity::task::Column::FinishedAt,
+            Expr::value(Utc::now().naive_local()),
+        )
+        .filter(
+            entity::task::Column::Status
+                .is_in([TaskStatus::Queued.as_str(), TaskStatus::Running.as_str()]),
+        )
+        .exec(db)
+        .await?
+        .rows_affected)
+}
+
+// Marks a task that never reached the TaskService as failed
+pub async fn set_rejected(db: &DatabaseConnection, id: &str, error: String) -> Result<(), DbErr> {
+    entity::task::ActiveModel {
+        id: Set(id.to_owned()),
+        status: Set(TaskStatus::Failed.as_str().to_owned()),
+        error: Set(Some(error)),
+        finished_at: Set(Some(Utc::now().naive_local())),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_running(db: &DatabaseConnection, id: &str) -> Result<(), DbErr> {
+    entity::task::ActiveModel {
+        id: Set(id.to_owned()),
+        status: Set(TaskStatus::Running.as_str().to_owned()),
+        started_at: Set(Some(Utc::now().naive_local())),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn save_progress(
+    db: &DatabaseConnection,
+    id: &str,
+    progress: &TaskProgress,
+) -> Result<(), DbErr> {
+    let counters = progress.counters();
+    entity::task::ActiveModel {
+        id: Set(id.to_owned()),
+        directories_walked: Set(counters.directories_walked),
+        songs_added: Set(counters.songs_added),
+        songs_updated: Set(counters.songs_updated),
+        songs_removed: Set(counters.songs_removed),
+        artists_updated: Set(counters.artists_updated),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+    Ok(())
+}
+
+// Stores the final progress and marks the task succeeded, or failed with the error message
+pub async fn set_finished(
+    db: &DatabaseConnection,
+    id: &str,
+    progress: &TaskProgress,
+    error: Option<String>,
+) -> Result<(), DbErr> {
+    save_progress(db, id, progress).await?;
+    let status = match error {
+        Some(_) => TaskStatus::Failed,
+        None => TaskStatus::Succeeded,
+    };
+    entity::task::ActiveModel {
+        id: Set(id.to_owned()),
+        status: Set(status.as_str().to_owned()),
+        error: Set(error),
+        finished_at: Set(Some(Utc::now().naive_local())),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_cancelled(db: &DatabaseConnection, id: &str) -> Result<(), DbErr> {
+    entity::task::ActiveModel {
+        id: Set(id.to_owned()),
+        status: Set(TaskStatus::Cancelled.as_str().to_owned()),
+        finished_at: Set(Some(Utc::now().naive_local())),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+    Ok(())
+}
+
+// Most recent tasks first. Optionally only tasks with status
+pub async fn get_tasks(
+    db: &DatabaseConnection,
+    status: Option<String>,
+    size: Option<u64>,
+) -> Result<Vec<entity::task::Model>, ApiError> {
+    Ok(entity::task::Entity::find()
+        .apply_if(status, |query, status| {
+            query.filter(entity::task::Column::Status.eq(status))
+        })
+        .order_by_desc(entity::task::Column::CreatedAt)
+        .limit(size.unwrap_or(50))
+        .all(db)
+        .await?)
+}
+
+pub async fn get_task_by_id(
+    db: &DatabaseConnection,
+    task_id: &str,
+) -> Result<entity::task::Model, ApiError> {
+    match entity::task::Entity::find_by_id(task_id).one(db).await? {
+        Some(model) => Ok(model),
+        None => Err(ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow!("Unable to find task with id: {}", task_id),
+        )),
+    }
+}
@@ -1,56 +1,452 @@
-use std::time::Duration;
-use tokio::sync::mpsc::Receiver;
+use anyhow::{anyhow, Result};
+use futures::FutureExt;
+use sea_orm::{DatabaseConnection, DbErr, SqlErr};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    panic::AssertUnwindSafe,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        mpsc::{Receiver, Sender},
+        watch,
+    },
+    task::JoinSet,
+};
 
 use crate::database;
 
-use super::scanner::ScanType;
+use self::history::{TaskProgress, TaskStatus};
+
+use super::{
+    events::{Event, EventBus},
+    scanner::ScanType,
+    settings::RuntimeSettings,
+};
+
+pub mod history;
+
+// How often progress counters of the running task are written to the tasks table
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
 
 #[derive(Debug)]
 pub enum TaskType {
-    ScanLibrary(ScanType),
-    Shutdown,
+    // Scans every library or only the library with the id
+    ScanLibrary(ScanType, Option<String>),
     PopulateMetadata,
+    // Fingerprints songs of every library or only the library with the id
+    Fingerprint(Option<String>),
+    // Measures loudness of songs of every library or only the library with the id
+    AnalyzeLoudness(Option<String>),
+    DatabaseMaintenance,
+    EvictCache,
+}
+
+impl TaskType {
+    // Maps the task names used by /tasks and scheduled tasks onto a TaskType
+    pub fn from_name(name: &str, library: Option<String>) -> Option<TaskType> {
+        match name {
+            "scan_library_full" => Some(TaskType::ScanLibrary(ScanType::FullScan, library)),
+            "scan_library_partial" => Some(TaskType::ScanLibrary(ScanType::PartialScan, library)),
+            "scan_metadata" => Some(TaskType::PopulateMetadata),
+            "scan_fingerprints" => Some(TaskType::Fingerprint(library)),
+            "scan_loudness" => Some(TaskType::AnalyzeLoudness(library)),
+            "db_maintenance" => Some(TaskType::DatabaseMaintenance),
+            "evict_cache" => Some(TaskType::EvictCache),
+            _ => None,
+        }
+    }
+
+    // Inverse of from_name. Stored as the kind of the task in the tasks table
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskType::ScanLibrary(ScanType::FullScan, _) => "scan_library_full",
+            TaskType::ScanLibrary(ScanType::PartialScan, _) => "scan_library_partial",
+            TaskType::PopulateMetadata => "scan_metadata",
+            TaskType::Fingerprint(_) => "scan_fingerprints",
+            TaskType::AnalyzeLoudness(_) => "scan_loudness",
+            TaskType::DatabaseMaintenance => "db_maintenance",
+            TaskType::EvictCache => "evict_cache",
+        }
+    }
+
+    // Higher priority tasks are started first. Partial scans are cheap and keep the library up to date
+    fn priority(&self) -> u8 {
+        match self {
+            TaskType::ScanLibrary(ScanType::PartialScan, _) => 3,
+            TaskType::ScanLibrary(ScanType::FullScan, _) => 2,
+            TaskType::PopulateMetadata
+            | TaskType::Fingerprint(_)
+            | TaskType::AnalyzeLoudness(_) => 1,
+            TaskType::DatabaseMaintenance | TaskType::EvictCache => 0,
+        }
+    }
+
+    // Tasks of the same kind share a concurrency limit. Full and partial scans write the same tables so count as one kind
+    fn kind(&self) -> &'static str {
+        match self {
+            TaskType::ScanLibrary(_, _) => "scan",
+            task => task.name(),
+        }
+    }
+
+    fn concurrency_limit(&self) -> usize {
+        match self {
+            TaskType::ScanLibrary(_, _) => 1,
+            TaskType::PopulateMetadata => 1,
+            TaskType::Fingerprint(_) => 1,
+            TaskType::AnalyzeLoudness(_) => 1,
+            TaskType::DatabaseMaintenance => 1,
+            TaskType::EvictCache => 1,
+        }
+    }
+
+    pub fn library_id(&self) -> Option<&str> {
+        match self {
+            TaskType::ScanLibrary(_, library_id)
+            | TaskType::Fingerprint(library_id)
+            | TaskType::AnalyzeLoudness(library_id) => library_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+// A task sent to the TaskService. id refers to the row in the tasks table
+#[derive(Debug)]
+pub struct Task {
+    pub id: String,
+    pub task_type: TaskType,
+}
+
+pub enum TaskCommand {
+    Run(Task),
+    // Removes a queued task or asks a running task to stop
+    Cancel(String),
+    Shutdown,
+}
+
+// Records the task as queued and sends it to the TaskService. Returns the id of the task
+// If an identical task is still queued its id is returned instead of queueing it twice
+pub async fn enqueue(
+    db: &DatabaseConnection,
+    sender: &Sender<TaskCommand>,
+    task_type: TaskType,
+) -> Result<String> {
+    if let Some(id) = history::find_queued(db, task_type.name(), task_type.library_id()).await? {
+        tracing::info!("Task {:} is already queued", id);
+        return Ok(id);
+    }
+    let id = match history::create_task(db, task_type.name(), task_type.library_id()).await {
+        Ok(id) => id,
+        // A concurrent request queued the same task between the lookup and the insert
+        Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            let id = history::find_queued(db, task_type.name(), task_type.library_id())
+                .await?
+                .ok_or(err)?;
+            tracing::info!("Task {:} is already queued", id);
+            return Ok(id);
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if let Err(err) = sender
+        .send(TaskCommand::Run(Task {
+            id: id.clone(),
+            task_type,
+        }))
+        .await
+    {
+        history::set_rejected(db, &id, err.to_string()).await?;
+        return Err(anyhow!("Failed to send task to TaskService: {}", err));
+    }
+    Ok(id)
+}
+
+struct QueuedTask {
+    // Tasks with equal priority run in the order they were queued
+    sequence: u64,
+    task: Task,
+}
+
+impl PartialEq for QueuedTask {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for QueuedTask {}
+impl PartialOrd for QueuedTask {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for QueuedTask {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.task
+            .task_type
+            .priority()
+            .cmp(&other.task.task_type.priority())
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+struct RunningTask {
+    kind: &'static str,
+    progress: Arc<TaskProgress>,
 }
 
 pub struct TaskService {
-    pub task_queue: Vec<TaskType>,
-    receiver: Receiver<TaskType>,
+    queue: BinaryHeap<QueuedTask>,
+    running: HashMap<String, RunningTask>,
+    sequence: u64,
+    receiver: Receiver<TaskCommand>,
+    db: DatabaseConnection,
+    events: EventBus,
+    // Tasks start with the settings current when they are dispatched
+    settings: watch::Receiver<RuntimeSettings>,
 }
-// TaskServices listens for TaskType:: on the Receiver only runnig 1 task at a time
+// TaskService listens for TaskCommand on the Receiver. Queued tasks are started by priority as long as their kind has a free slot
 impl TaskService {
-    pub fn new(receiver: Receiver<TaskType>) -> TaskService {
+    pub fn new(
+        receiver: Receiver<TaskCommand>,
+        db: &DatabaseConnection,
+        events: EventBus,
+        settings: watch::Receiver<RuntimeSettings>,
+    ) -> TaskService {
         TaskService {
-            task_queue: Vec::new(),
+            queue: BinaryHeap::new(),
+            running: HashMap::new(),
+            sequence: 0,
             receiver,
+            db: db.clone(),
+            events,
+            settings,
         }
     }
     pub async fn run(&mut self) {
-        tracing::debug!("Connecting to DB");
-        let sqlite_pool = match database::connect_db_sqlx().await {
-            Ok(pool) => pool,
-            Err(_) => database::connect_db_sqlx().await.unwrap(),
-        };
-        tracing::debug!("Connected DB");
         tracing::info!("Started task service");
+        let db = self.db.clone();
+        let mut jobs: JoinSet<String> = JoinSet::new();
         loop {
-            if let Ok(task) = self.receiver.try_recv() {
-                tracing::info!("Running task: {:?}", task);
-                match task {
-                    TaskType::ScanLibrary(ScanType::FullScan) => {
-                        crate::services::scanner::start_scan(ScanType::FullScan, &sqlite_pool).await
+            self.dispatch(&mut jobs, &db);
+            tokio::select! {
+                command = self.receiver.recv() => match command {
+                    Some(TaskCommand::Run(task)) => {
+                        tracing::info!("Queued task {:}: {:?}", task.id, task.task_type);
+                        self.sequence += 1;
+                        self.queue.push(QueuedTask {
+                            sequence: self.sequence,
+                            task,
+                        });
                     }
-                    TaskType::ScanLibrary(ScanType::PartialScan) => {
-                        crate::services::scanner::start_scan(ScanType::PartialScan, &sqlite_pool)
-                            .await
+                    Some(TaskCommand::Cancel(id)) => self.cancel(&id, &db).await,
+                    Some(TaskCommand::Shutdown) | None => break,
+                },
+                Some(finished) = jobs.join_next(), if !jobs.is_empty() => match finished {
+                    Ok(id) => {
+                        self.running.remove(&id);
+                    }
+                    // Jobs catch their panics and are never aborted, so this only happens when the runtime shuts down
+                    Err(err) => tracing::error!("Task job failed: {:}", err),
+                },
+            }
+        }
+        // Ask running tasks to stop. Anything left is marked as interrupted on the next start
+        for running in self.running.values() {
+            running.progress.cancel();
+        }
+        while jobs.join_next().await.is_some() {}
+    }
+
+    // Starts queued tasks in priority order. Tasks whose kind is at its limit stay queued
+    fn dispatch(&mut self, jobs: &mut JoinSet<String>, db: &DatabaseConnection) {
+        let mut blocked = Vec::new();
+        while let Some(queued) = self.queue.pop() {
+            let task_type = &queued.task.task_type;
+            let running = self
+                .running
+                .values()
+                .filter(|running| running.kind == task_type.kind())
+                .count();
+            if running >= task_type.concurrency_limit() {
+                blocked.push(queued);
+                continue;
+            }
+            let task = queued.task;
+            tracing::info!("Running task {:}: {:?}", task.id, task.task_type);
+            let progress = Arc::new(TaskProgress::new(self.events.clone()));
+            self.running.insert(
+                task.id.clone(),
+                RunningTask {
+                    kind: task.task_type.kind(),
+                    progress: progress.clone(),
+                },
+            );
+            let db = db.clone();
+            let settings = self.settings.borrow().clone();
+            jobs.spawn(async move {
+                let id = task.id.clone();
+                // The id must come back even if run_task panics or the slot of the task is never freed
+                match AssertUnwindSafe(run_task(task, &db, &settings, &progress))
+                    .catch_unwind()
+                    .await
+                {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => tracing::error!("Failed to update task history: {:}", err),
+                    Err(_) => tracing::error!("Task {:} panicked", id),
+                }
+                id
+            });
+        }
+        self.queue.extend(blocked);
+    }
+
+    async fn cancel(&mut self, id: &str, db: &DatabaseConnection) {
+        if let Some(running) = self.running.get(id) {
+            tracing::info!("Cancelling running task {:}", id);
+            running.progress.cancel();
+            return;
+        }
+        let queued = self.queue.len();
+        self.queue.retain(|queued| queued.task.id != id);
+        if self.queue.len() < queued {
+            tracing::info!("Cancelled queued task {:}", id);
+            if let Err(err) = history::set_cancelled(db, id).await {
+                tracing::error!("Failed to update task history: {:}", err)
+            }
+        }
+    }
+}
+
+// Runs the task while periodically saving its progress. The outcome is stored in the tasks table
+async fn run_task(
+    task: Task,
+    db: &DatabaseConnection,
+    settings: &RuntimeSettings,
+    progress: &TaskProgress,
+) -> Result<(), DbErr> {
+    history::set_running(db, &task.id).await?;
+    // Only scans are announced on the event bus
+    let is_scan = matches!(task.task_type, TaskType::ScanLibrary(_, _));
+    if is_scan {
+        progress.publish(Event::ScanStarted {
+            task_id: task.id.clone(),
+            library_id: task.task_type.library_id().map(str::to_owned),
+        });
+    }
+    let result = {
+        // A panicking task should still be recorded as failed
+        let job = AssertUnwindSafe(execute(task.task_type, db, settings, progress)).catch_unwind();
+        tokio::pin!(job);
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        loop {
+            tokio::select! {
+                result = &mut job => break result.unwrap_or_else(|_| Err(anyhow!("Task panicked"))),
+                _ = ticker.tick() => {
+                    // The task keeps running when its progress can't be saved. The outcome is saved once it finishes
+                    if let Err(err) = history::save_progress(db, &task.id, progress).await {
+                        tracing::error!("Failed to save progress of task {:}: {:}", task.id, err);
                     }
-                    TaskType::PopulateMetadata => {
-                        crate::services::metadata::scrap_metadata(&sqlite_pool).await
+                    if is_scan {
+                        progress.publish(Event::ScanProgress {
+                            task_id: task.id.clone(),
+                            counters: progress.counters(),
+                        });
                     }
-                    TaskType::Shutdown => break,
                 }
             }
+        }
+    };
+    let status = if progress.is_cancelled() {
+        tracing::info!("Task {:} cancelled", task.id);
+        history::save_progress(db, &task.id, progress).await?;
+        history::set_cancelled(db, &task.id).await?;
+        TaskStatus::Cancelled
+    } else {
+        let error = result.err().map(|err| {
+            tracing::error!("Task {:} failed: {:}", task.id, err);
+            err.to_string()
+        });
+        let status = match error {
+            Some(_) => TaskStatus::Failed,
+            None => TaskStatus::Succeeded,
+        };
+        history::set_finished(db, &task.id, progress, error).await?;
+        status
+    };
+    if is_scan {
+        progress.publish(Event::ScanFinished {
+            task_id: task.id,
+            status: status.as_str().to_owned(),
+            counters: progress.counters(),
+        });
+    }
+    Ok(())
+}
+
+async fn execute(
+    task_type: TaskType,
+    db: &DatabaseConnection,
+    settings: &RuntimeSettings,
+    progress: &TaskProgress,
+) -> Result<()> {
+    match task_type {
+        TaskType::ScanLibrary(scan_type, library_id) => {
+            crate::services::scanner::start_scan(scan_type, library_id, db, progress).await
+        }
+        TaskType::PopulateMetadata => {
+            crate::services::metadata::scrap_metadata(db, settings, progress).await
+        }
+        TaskType::Fingerprint(library_id) => {
+            crate::services::fingerprint::fingerprint_songs(db, library_id.as_deref(), progress)
+                .await
+        }
+        TaskType::AnalyzeLoudness(library_id) => {
+            crate::services::loudness::analyze_songs(db, library_id.as_deref(), progress).await
+        }
+        TaskType::DatabaseMaintenance => database::maintenance(db).await,
+        TaskType::EvictCache => crate::services::cache::evict_cache().await,
+    }
+}
 
-            std::thread::sleep(Duration::from_millis(500));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(sequence: u64, task_type: TaskType) -> QueuedTask {
+        QueuedTask {
+            sequence,
+            task: Task {
+                id: sequence.to_string(),
+                task_type,
+            },
         }
     }
+
+    #[test]
+    fn test_queue_order() {
+        let mut queue = BinaryHeap::new();
+        queue.push(queued(1, TaskType::EvictCache));
+        queue.push(queued(2, TaskType::PopulateMetadata));
+        queue.push(queued(3, TaskType::ScanLibrary(ScanType::FullScan, None)));
+        queue.push(queued(4, TaskType::DatabaseMaintenance));
+        queue.push(queued(
+            5,
+            TaskType::ScanLibrary(ScanType::PartialScan, None),
+        ));
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop().map(|q| q.task.id)).collect();
+        assert_eq!(order, vec!["5", "3", "2", "1", "4"]);
+    }
+
+    #[test]
+    fn test_scans_share_kind() {
+        assert_eq!(
+            TaskType::ScanLibrary(ScanType::FullScan, None).kind(),
+            TaskType::ScanLibrary(ScanType::PartialScan, Some(String::from("id"))).kind()
+        );
+        assert_ne!(
+            TaskType::ScanLibrary(ScanType::FullScan, None).kind(),
+            TaskType::PopulateMetadata.kind()
+        );
+    }
 }
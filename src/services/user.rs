@@ -0,0 +1,141 @@
+use anyhow::anyhow;
+use chrono::Utc;
+use hyper::StatusCode;
+use pbkdf2::{
+    password_hash::{PasswordHasher, SaltString},
+    Pbkdf2,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+    SqlErr,
+};
+use uuid::Uuid;
+
+use super::http::error::ApiError;
+
+// PHC string with the crate's default PBKDF2-SHA256 parameters
+pub fn hash_password(password: &str) -> Result<String, ApiError> {
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes())
+        .map_err(|err| anyhow!("Failed to encode salt: {err}"))?;
+    Ok(Pbkdf2
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| anyhow!("Failed to hash password: {err}"))?
+        .to_string())
+}
+
+pub async fn get_users(db: &DatabaseConnection) -> Result<Vec<entity::user::Model>, ApiError> {
+    Ok(entity::user::Entity::find()
+        .order_by_asc(entity::user::Column::Username)
+        .all(db)
+        .await?)
+}
+
+pub async fn get_user_by_username(
+    db: &DatabaseConnection,
+    username: &str,
+) -> Result<entity::user::Model, ApiError> {
+    match entity::user::Entity::find()
+        .filter(entity::user::Column::Username.eq(username))
+        .one(db)
+        .await?
+    {
+        Some(user) => Ok(user),
+        None => Err(ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow!("Unable to find user: {}", username),
+        )),
+    }
+}
+
+pub async fn create_user(
+    db: &DatabaseConnection,
+    username: &str,
+    password: &str,
+    admin: bool,
+) -> Result<entity::user::Model, ApiError> {
+    let username = username.trim();
+    if username.is_empty() || password.is_empty() {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Username and password can't be empty"),
+        ));
+    }
+    if get_user_by_username(db, username).await.is_ok() {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            anyhow!("User {} already exists", username),
+        ));
+    }
+    let init_time = Utc::now().naive_local();
+    let user = entity::user::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        username: Set(username.to_owned()),
+        password_hash: Set(hash_password(password)?),
+        admin: Set(admin),
+        created_at: Set(init_time),
+        updated_at: Set(init_time),
+    };
+    match user.insert(db).await {
+        Ok(user) => Ok(user),
+        // Created by a concurrent request since the lookup
+        Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            Err(ApiError(
+                StatusCode::CONFLICT,
+                anyhow!("User {} already exists", username),
+            ))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn set_password(
+    db: &DatabaseConnection,
+    username: &str,
+    password: &str,
+) -> Result<entity::user::Model, ApiError> {
+    if password.is_empty() {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Password can't be empty"),
+        ));
+    }
+    let mut user: entity::user::ActiveModel =
+        get_user_by_username(db, username.trim()).await?.into();
+    user.password_hash = Set(hash_password(password)?);
+    user.updated_at = Set(Utc::now().naive_local());
+    Ok(user.update(db).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use pbkdf2::password_hash::{PasswordHash, PasswordVerifier};
+
+    use super::*;
+
+    #[test]
+    fn test_hash_password() {
+        let hash = hash_password("hunter2").unwrap_or_else(|_| panic!("hashing failed"));
+        assert!(hash.starts_with("$pbkdf2-sha256$"));
+        let parsed = PasswordHash::new(&hash).unwrap();
+        assert!(Pbkdf2.verify_password(b"hunter2", &parsed).is_ok());
+        assert!(Pbkdf2.verify_password(b"hunter3", &parsed).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_user_trims_username() {
+        let db = crate::test_util::new_seaorm_db().await.unwrap();
+        let user = create_user(&db, " alice ", "hunter2", false).await;
+        assert!(matches!(user, Ok(user) if user.username == "alice"));
+        let duplicate = create_user(&db, "alice  ", "hunter2", false).await;
+        assert!(matches!(duplicate, Err(ApiError(StatusCode::CONFLICT, _))));
+    }
+
+    #[tokio::test]
+    async fn test_set_password_trims_username() {
+        let db = crate::test_util::new_seaorm_db().await.unwrap();
+        let user = create_user(&db, "alice", "hunter2", false).await;
+        assert!(user.is_ok());
+        let user = set_password(&db, " alice", "hunter3").await;
+        assert!(matches!(user, Ok(user) if user.username == "alice"));
+    }
+}
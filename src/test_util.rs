@@ -1,65 +1,238 @@
-use std::{fs, time::Duration};
+use std::fs;
 
 use crate::{
-    services::{casting::device::DeviceService, http::handlers},
+    services::{casting::device::DeviceService, events::EventBus, http::handlers},
     *,
 };
-use axum::{routing::get, routing::post, Router};
+use axum::{extract::DefaultBodyLimit, routing::get, routing::post, Router};
 use migration::{DbErr, Migrator, MigratorTrait};
-use sea_orm::{ConnectOptions, ConnectionTrait, DatabaseBackend, ExecResult, Statement};
+use sea_orm::{
+    sea_query::{Alias, ColumnType, Expr},
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseBackend, EntityTrait, IdenStatic,
+    IntoActiveModel, Iterable, QuerySelect, TransactionTrait,
+};
+use sqlx::{postgres::PgConnectOptions, Connection};
+use std::str::FromStr;
 
 use tower_http::trace::TraceLayer;
 pub const ADDR: &str = "0.0.0.0:3030";
+// Admin token of the test router. Sent by tests as Authorization: Bearer ADMIN_TOKEN
+pub const ADMIN_TOKEN: &str = "test-admin-token";
 
 pub async fn app() -> Router {
     let database = new_seaorm_db().await.unwrap();
     seed_test_db(&database).await.unwrap();
-    let (tasks_send, _tasks_receiver) = tokio::sync::mpsc::channel::<services::task::TaskType>(10);
+    app_with_database(database).await
+}
+
+// Router over a database the test set up itself
+pub async fn app_with_database(database: DatabaseConnection) -> Router {
+    let (tasks_send, mut tasks_receiver) =
+        tokio::sync::mpsc::channel::<services::task::TaskCommand>(10);
+    // Tasks are accepted but never run so they stay queued
+    tokio::spawn(async move { while tasks_receiver.recv().await.is_some() {} });
     let services = DeaftoneService {
         device: DeviceService::new(database.clone()),
         task: tasks_send.clone(),
+        events: EventBus::new(),
+        settings: services::settings::SettingsService::load(&database)
+            .await
+            .unwrap(),
     };
     //scan.start_scan();
-    let state = AppState { database, services };
+    let state = AppState {
+        database,
+        services,
+        admin_token: Some(String::from(ADMIN_TOKEN)),
+    };
     Router::new()
         .route("/stream/:id", get(handlers::streams::stream_handler))
         .route(
             "/stream/transcode/:id",
             get(handlers::streams::transcode_stream_handler),
         )
+        .route(
+            "/stream/hls/:id/master.m3u8",
+            get(handlers::streams::hls_master_playlist),
+        )
+        .route(
+            "/stream/hls/:id/:profile/playlist.m3u8",
+            get(handlers::streams::hls_media_playlist),
+        )
+        .route(
+            "/stream/hls/:id/:profile/:segment",
+            get(handlers::streams::hls_segment),
+        )
+        .route("/songs/:id/download", get(handlers::streams::download_song))
+        .route(
+            "/albums/:id/download",
+            get(handlers::streams::download_album),
+        )
         .route("/albums/:id", get(handlers::albums::get_album))
         .route("/songs/:id", get(handlers::songs::get_song))
         .route("/songs/:id/like", post(handlers::songs::like_song))
+        .route("/songs/:id/scrobble", post(handlers::songs::scrobble_song))
+        .route("/songs/:id/lyrics", get(handlers::songs::get_song_lyrics))
         .route("/albums/:id/cover", get(handlers::albums::get_cover))
         .route("/albums", get(handlers::albums::get_albums))
         .route("/artists/:id", get(handlers::artists::get_artist))
         .route("/artists", get(handlers::artists::get_artists))
+        .route("/genres", get(handlers::genres::get_genres))
+        .route(
+            "/genres/:id/albums",
+            get(handlers::genres::get_genre_albums),
+        )
+        .route("/genres/:id/songs", get(handlers::genres::get_genre_songs))
+        .route(
+            "/libraries",
+            get(handlers::libraries::get_libraries).post(handlers::libraries::create_library),
+        )
+        .route(
+            "/libraries/:id",
+            get(handlers::libraries::get_library)
+                .put(handlers::libraries::update_library)
+                .delete(handlers::libraries::delete_library),
+        )
+        .route("/events", get(handlers::events::events))
+        .route("/settings", get(handlers::settings::get_settings))
+        .route(
+            "/settings/runtime",
+            get(handlers::settings::get_runtime_settings)
+                .put(handlers::settings::update_runtime_settings),
+        )
+        .route(
+            "/tasks",
+            get(handlers::tasks::get_tasks).post(handlers::tasks::handle_task),
+        )
+        .route(
+            "/tasks/:id",
+            get(handlers::tasks::get_task).delete(handlers::tasks::cancel_task),
+        )
         .route("/playlists/:id", get(handlers::playlist::get_playlist))
+        .route("/stats/top/artists", get(handlers::stats::get_top_artists))
+        .route("/stats/top/albums", get(handlers::stats::get_top_albums))
+        .route("/stats/top/songs", get(handlers::stats::get_top_songs))
+        .route("/stats/top/genres", get(handlers::stats::get_top_genres))
+        .route("/stats/listening", get(handlers::stats::get_listening_time))
+        .route("/stats/growth", get(handlers::stats::get_library_growth))
+        .route("/stats/formats", get(handlers::stats::get_format_breakdown))
+        .route("/admin/backup", get(handlers::admin::get_backup))
+        .route("/admin/export", get(handlers::admin::get_export))
+        .route("/admin/issues", get(handlers::admin::get_issues))
+        .route("/admin/duplicates", get(handlers::admin::get_duplicates))
+        .route(
+            "/admin/import",
+            post(handlers::admin::import_user_state)
+                .layer(DefaultBodyLimit::max(handlers::admin::IMPORT_BODY_LIMIT)),
+        )
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
 
+// Tests run against PostgreSQL when this points at a server, such as postgres://postgres@localhost/postgres
+// Every test database is created on that server. Otherwise tests use in-memory SQLite
+pub const TEST_DATABASE_URL: &str = "DEAFTONE_TEST_DATABASE_URL";
+
+fn test_postgres_url() -> Option<String> {
+    std::env::var(TEST_DATABASE_URL)
+        .ok()
+        .filter(|url| url.starts_with("postgres://") || url.starts_with("postgresql://"))
+}
+
+// Empty database opened with the same connection options as the server. Nothing is migrated
+pub async fn new_empty_db() -> Result<DatabaseConnection, anyhow::Error> {
+    match test_postgres_url() {
+        Some(url) => {
+            let name = format!("deaftone_test_{}", uuid::Uuid::new_v4().simple());
+            let mut admin = sqlx::PgConnection::connect(&url).await?;
+            sqlx::Executor::execute(&mut admin, format!("CREATE DATABASE {name}").as_str()).await?;
+            admin.close().await?;
+            let options = PgConnectOptions::from_str(&url)?.database(&name);
+            database::connect_postgres(options, 0, 5).await
+        }
+        None => {
+            let options = database::connect_options("sqlite::memory:")?;
+            database::connect_sqlite(options, 5, 100).await
+        }
+    }
+}
+
+// Migrated test database
 pub async fn new_seaorm_db() -> Result<DatabaseConnection, anyhow::Error> {
-    let mut opt: ConnectOptions = ConnectOptions::new(String::from("sqlite::memory:"));
-    opt.max_connections(100)
-        .min_connections(5)
-        .connect_timeout(Duration::from_secs(8))
-        .idle_timeout(Duration::from_secs(8))
-        .max_lifetime(Duration::from_secs(8))
-        .sqlx_logging(false);
+    let db = new_empty_db().await?;
+    Migrator::up(&db, None).await?;
+    Ok(db)
+}
+
+// Loads tests/test_seed.sql. The seed is a SQLite dump so PostgreSQL is seeded by copying every table
+// out of a seeded SQLite database
+pub async fn seed_test_db(db: &DatabaseConnection) -> Result<(), anyhow::Error> {
+    let seed: String = fs::read_to_string("tests/test_seed.sql")?;
+    if db.get_database_backend() == DatabaseBackend::Sqlite {
+        db.execute_unprepared(&seed).await?;
+        return Ok(());
+    }
+    let sqlite =
+        database::connect_sqlite(database::connect_options("sqlite::memory:")?, 1, 1).await?;
+    Migrator::up(&sqlite, None).await?;
+    sqlite.execute_unprepared(&seed).await?;
 
-    let pool: DatabaseConnection = sea_orm::Database::connect(opt).await?;
-    Migrator::up(&pool, None).await?;
-    Ok(pool)
+    let tx = db.begin().await?;
+    // The seed has albums of artists that were never scanned. Foreign keys aren't checked while copying
+    tx.execute_unprepared("SET LOCAL session_replication_role = replica")
+        .await?;
+    copy_table::<entity::setting::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::library::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::artist::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::album::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::song::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::directory::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::genre::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::style::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::album_genre::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::album_style::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::song_genre::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::song_style::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::artist_credit::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::task::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::playlist::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::playlist_song::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::play::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::user::Entity, _>(&sqlite, &tx).await?;
+    copy_table::<entity::cast_devices::Entity, _>(&sqlite, &tx).await?;
+    tx.commit().await?;
+    Ok(())
 }
-pub async fn seed_test_db(db: &DatabaseConnection) -> Result<ExecResult, DbErr> {
-    let seed: String = fs::read_to_string("tests/test_seed.sql")
-        .unwrap()
-        .parse()
-        .unwrap();
 
-    let exec_res: ExecResult = db
-        .execute(Statement::from_string(DatabaseBackend::Sqlite, seed))
+async fn copy_table<E, A>(from: &DatabaseConnection, to: &impl ConnectionTrait) -> Result<(), DbErr>
+where
+    E: EntityTrait,
+    E::Model: IntoActiveModel<A>,
+    A: ActiveModelTrait<Entity = E>,
+{
+    // Columns are cast to the entity types since the seed declares some columns with other types
+    let mut select = E::find().select_only();
+    for column in E::Column::iter() {
+        let sql_type = match column.def().get_column_type() {
+            ColumnType::Integer | ColumnType::BigInteger | ColumnType::Boolean => "INTEGER",
+            ColumnType::Float | ColumnType::Double => "REAL",
+            _ => "TEXT",
+        };
+        select.expr_as(
+            Expr::col(column).cast_as(Alias::new(sql_type)),
+            column.as_str(),
+        );
+    }
+    let rows = select.into_model::<E::Model>().all(from).await?;
+    for chunk in rows.chunks(200) {
+        E::insert_many(
+            chunk
+                .iter()
+                .cloned()
+                .map(IntoActiveModel::into_active_model),
+        )
+        .exec_without_returning(to)
         .await?;
-    Ok(exec_res)
+    }
+    Ok(())
 }
@@ -31,7 +31,15 @@ mod tests {
         let response: SuccessResponse<AlbumResponse> = from_slice(&body).unwrap();
         assert!(response.message.id == r#"46ffbb9a-8c98-45d6-a561-0cb80214a642"#);
         assert!(response.message.name == *"Ain't No Peace");
-        assert!(response.message.songs.len() == 7);
+        assert!(response.message.song_count == 7);
+        assert!(response.message.discs.len() == 1);
+        let tracks: Vec<i32> = response.message.discs[0]
+            .songs
+            .iter()
+            .map(|s| s.track.unwrap_or_default())
+            .collect();
+        assert!(tracks.len() == 7);
+        assert!(tracks.windows(2).all(|t| t[0] <= t[1]));
     }
 
     #[tokio::test]
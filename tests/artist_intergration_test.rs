@@ -32,6 +32,32 @@ mod tests {
         assert!(response.message.id == r#"7d110590-c4ed-4250-973b-f8fa5d60260e"#);
         assert!(response.message.name == *"Akon");
         assert!(response.message.albums.len() == 6);
+        // Akons own albums are not appearances
+        assert!(response.message.appearances.is_empty());
+    }
+    #[tokio::test]
+    async fn test_get_artist_appearances() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "http://{ADDR}/artists/f4051632-9e73-4942-809a-ef3a91ea8cbb"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<ArtistResponse> = from_slice(&body).unwrap();
+        assert_eq!(response.message.appearances.len(), 1);
+        assert_eq!(
+            response.message.appearances[0].id,
+            "46ffbb9a-8c98-45d6-a561-0cb80214a642"
+        );
     }
     #[tokio::test]
     async fn test_get_artist_not_found() {
@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, Router};
+    use deaftone::{
+        database,
+        test_util::{app, app_with_database, seed_test_db, ADDR, ADMIN_TOKEN, TEST_DATABASE_URL},
+    };
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use migration::{Migrator, MigratorTrait};
+    use sea_orm::{EntityTrait, PaginatorTrait};
+    use serde_json::{from_slice, json, Value};
+    use tower::ServiceExt;
+
+    const SONG: &str = "08696c90-b6b7-45c2-b4ef-a767250efe60";
+
+    async fn request(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<&Value>,
+    ) -> (StatusCode, Value) {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(format!("http://{ADDR}{uri}"))
+            .header("authorization", format!("Bearer {ADMIN_TOKEN}"));
+        if body.is_some() {
+            builder = builder.header("content-type", "application/json");
+        }
+        let resp = app
+            .clone()
+            .oneshot(
+                builder
+                    .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = resp.status();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        (status, from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        let original = app().await;
+        request(&original, "POST", &format!("/songs/{SONG}/like"), None).await;
+        request(&original, "POST", &format!("/songs/{SONG}/scrobble"), None).await;
+        let (status, mut export) = request(&original, "GET", "/admin/export", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(export["version"], 1);
+        assert_eq!(export["likes"].as_array().unwrap().len(), 1);
+        assert_eq!(export["plays"].as_array().unwrap().len(), 1);
+
+        let song = export["likes"][0].clone();
+        let missing = json!({"mb_track_id": null, "path": "/gone/song.flac"});
+        export["playlists"] = json!([{
+            "name": "Road trip",
+            "created_at": "2023-06-01T12:00:00",
+            "updated_at": "2023-06-01T12:00:00",
+            "songs": [song, missing]
+        }]);
+
+        // A fresh database with the same songs picks the state back up
+        let restored = app().await;
+        let (status, report) = request(&restored, "POST", "/admin/import", Some(&export)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["message"]["likes"], 1);
+        assert_eq!(report["message"]["playlists"], 1);
+        assert_eq!(report["message"]["playlist_songs"], 1);
+        assert_eq!(report["message"]["plays"], 1);
+        assert_eq!(report["message"]["missing"], json!([missing]));
+
+        let (_, song) = request(&restored, "GET", &format!("/songs/{SONG}"), None).await;
+        assert_eq!(song["message"]["liked"], true);
+
+        // Importing again doesn't duplicate playlists or plays
+        let (_, report) = request(&restored, "POST", "/admin/import", Some(&export)).await;
+        assert_eq!(report["message"]["playlists"], 0);
+        assert_eq!(report["message"]["plays"], 0);
+        let (_, export) = request(&restored, "GET", "/admin/export", None).await;
+        assert_eq!(export["playlists"].as_array().unwrap().len(), 1);
+        assert_eq!(export["plays"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_newer_version() {
+        let app = app().await;
+        let export = json!({
+            "version": 2,
+            "exported_at": "2023-06-01T12:00:00",
+            "likes": [],
+            "playlists": [],
+            "plays": []
+        });
+        let (status, _) = request(&app, "POST", "/admin/import", Some(&export)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_backup() {
+        if std::env::var(TEST_DATABASE_URL).is_ok_and(|url| url.starts_with("postgres")) {
+            let (status, _) = request(&app().await, "GET", "/admin/backup", None).await;
+            assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+            return;
+        }
+        // VACUUM INTO writes in memory copies of in memory databases so the backup is taken of a file
+        let path =
+            std::env::temp_dir().join(format!("deaftone-test-{}.sqlite", uuid::Uuid::new_v4()));
+        let database = database::connect_sqlite(
+            database::connect_options(&format!("sqlite://{}", path.display())).unwrap(),
+            1,
+            5,
+        )
+        .await
+        .unwrap();
+        Migrator::up(&database, None).await.unwrap();
+        seed_test_db(&database).await.unwrap();
+        deaftone::services::user::create_user(&database, "alice", "correct horse", true)
+            .await
+            .unwrap_or_else(|_| panic!("Failed to create user"));
+        let resp = app_with_database(database)
+            .await
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/admin/backup"))
+                    .header("authorization", format!("Bearer {ADMIN_TOKEN}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "application/vnd.sqlite3");
+        let len = resp.headers()["content-length"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.len().to_string(), len);
+        assert!(body.starts_with(b"SQLite format 3\0"));
+
+        let backup =
+            std::env::temp_dir().join(format!("deaftone-test-{}.sqlite", uuid::Uuid::new_v4()));
+        std::fs::write(&backup, &body).unwrap();
+        let restored = database::connect_sqlite(
+            database::connect_options(&format!("sqlite://{}", backup.display())).unwrap(),
+            1,
+            1,
+        )
+        .await
+        .unwrap();
+        let songs = entity::song::Entity::find().count(&restored).await.unwrap();
+        assert!(songs > 0);
+        // Password hashes aren't part of backups
+        let users = entity::user::Entity::find().count(&restored).await.unwrap();
+        assert_eq!(users, 0);
+        restored.close().await.unwrap();
+        for file in [path, backup] {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_requires_token() {
+        let app = app().await;
+        for authorization in [None, Some("Bearer wrong-admin-token")] {
+            let mut builder = Request::builder().uri(format!("http://{ADDR}/admin/export"));
+            if let Some(authorization) = authorization {
+                builder = builder.header("authorization", authorization);
+            }
+            let resp = app
+                .clone()
+                .oneshot(builder.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod tests {
+    use deaftone::database;
+    use sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+    #[tokio::test]
+    async fn test_connection_pragmas() {
+        let path = std::env::temp_dir().join(format!("deaftone-{}.sqlite", uuid::Uuid::new_v4()));
+        let options = database::connect_options(&format!("sqlite://{}", path.display())).unwrap();
+        let db = database::connect_sqlite(options, 1, 2).await.unwrap();
+
+        for (pragma, expected) in [
+            ("journal_mode", "wal"),
+            ("foreign_keys", "1"),
+            ("busy_timeout", "30000"),
+            ("temp_store", "2"),
+        ] {
+            let row = db
+                .query_one(Statement::from_string(
+                    DbBackend::Sqlite,
+                    format!("PRAGMA {pragma}"),
+                ))
+                .await
+                .unwrap()
+                .unwrap();
+            let value = match pragma {
+                "journal_mode" => row.try_get_by_index::<String>(0).unwrap(),
+                _ => row.try_get_by_index::<i64>(0).unwrap().to_string(),
+            };
+            assert_eq!(value, expected);
+        }
+        db.close().await.unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
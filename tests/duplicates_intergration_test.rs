@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, Router};
+    use deaftone::test_util::{app_with_database, new_seaorm_db, seed_test_db, ADDR, ADMIN_TOKEN};
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use sea_orm::{
+        ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+    };
+    use serde_json::{from_slice, Value};
+    use tower::ServiceExt;
+
+    const SONG: &str = "08696c90-b6b7-45c2-b4ef-a767250efe60";
+
+    async fn get(app: &Router, uri: &str) -> Value {
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}{uri}"))
+                    .header("authorization", format!("Bearer {ADMIN_TOKEN}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        from_slice(&body).unwrap()
+    }
+
+    // Lower quality copy of the song at path on album_id
+    async fn insert_copy(
+        db: &DatabaseConnection,
+        song: &entity::song::Model,
+        id: &str,
+        album_id: &str,
+    ) {
+        let mut copy = entity::song::ActiveModel::from(song.clone()).reset_all();
+        copy.id = Set(String::from(id));
+        copy.path = Set(format!("/copies/{id}.mp3"));
+        copy.album_id = Set(Some(String::from(album_id)));
+        copy.codec = Set(Some(String::from("MP3")));
+        copy.bits_per_sample = Set(None);
+        copy.bit_rate = Set(Some(320));
+        copy.insert(db).await.unwrap();
+    }
+
+    // Rips the album of SONG a second time as MP3 and adds an MP3 copy of SONG to the original album
+    async fn setup() -> (Router, String, usize) {
+        let db = new_seaorm_db().await.unwrap();
+        seed_test_db(&db).await.unwrap();
+        let song = entity::song::Entity::find_by_id(SONG)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        let album_id = song.album_id.clone().unwrap();
+        entity::song::Entity::update_many()
+            .col_expr(entity::song::Column::Codec, "FLAC".into())
+            .col_expr(entity::song::Column::BitsPerSample, 24.into())
+            .filter(entity::song::Column::AlbumId.eq(album_id.as_str()))
+            .exec(&db)
+            .await
+            .unwrap();
+        let songs = entity::song::Entity::find()
+            .filter(entity::song::Column::AlbumId.eq(album_id.as_str()))
+            .all(&db)
+            .await
+            .unwrap();
+
+        let album = entity::album::Entity::find_by_id(album_id.as_str())
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut copy = entity::album::ActiveModel::from(album).reset_all();
+        copy.id = Set(String::from("album-copy"));
+        copy.path = Set(String::from("/copies"));
+        // Some seeded albums belong to artists that aren't in the seed
+        copy.artist_id = Set(None);
+        copy.insert(&db).await.unwrap();
+        for song in &songs {
+            insert_copy(&db, song, &format!("copy-{}", song.id), "album-copy").await;
+        }
+        insert_copy(&db, &song, "same-album-copy", &album_id).await;
+
+        deaftone::services::duplicates::detect(&db).await.unwrap();
+        (app_with_database(db).await, album_id, songs.len())
+    }
+
+    #[tokio::test]
+    async fn test_get_duplicates() {
+        let (app, album_id, _) = setup().await;
+        let groups = get(&app, "/admin/duplicates").await;
+        let group = groups["message"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|group| group["songs"][0]["id"] == SONG)
+            .unwrap();
+        let songs = group["songs"].as_array().unwrap();
+        assert_eq!(songs.len(), 3);
+        assert_eq!(songs[0]["best"], true);
+        assert_eq!(songs[0]["album_id"], album_id.as_str());
+        assert_eq!(songs[0]["bit_depth"], 24);
+        assert!(songs[1..]
+            .iter()
+            .all(|song| song["best"] == false && song["codec"] == "MP3"));
+
+        let groups = get(&app, "/admin/duplicates?matched_by=isrc").await;
+        assert!(groups["message"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|group| group["matched_by"]
+                .as_array()
+                .unwrap()
+                .contains(&Value::from("isrc"))));
+    }
+
+    #[tokio::test]
+    async fn test_hide_duplicates() {
+        let (app, album_id, count) = setup().await;
+        let album = get(&app, &format!("/albums/{album_id}")).await;
+        assert_eq!(album["message"]["song_count"], count + 1);
+        let album = get(&app, &format!("/albums/{album_id}?hide_duplicates=true")).await;
+        assert_eq!(album["message"]["song_count"], count);
+
+        let listed = |albums: &Value| {
+            albums["message"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|album| album["id"] == "album-copy")
+        };
+        assert!(listed(&get(&app, "/albums?size=1000").await));
+        let albums = get(&app, "/albums?size=1000&hide_duplicates=true").await;
+        assert!(!listed(&albums));
+        assert!(albums["message"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|album| album["id"] == album_id.as_str()));
+    }
+}
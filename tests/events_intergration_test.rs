@@ -0,0 +1,26 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use deaftone::test_util::{app, ADDR};
+    use hyper::StatusCode;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_events_stream() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/events?types=scan_finished"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+    }
+}
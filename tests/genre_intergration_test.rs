@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use deaftone::{
+        services::http::SuccessResponse,
+        test_util::{app, ADDR},
+    };
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use serde_json::from_slice;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_get_genres() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/genres"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::genre::Model>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 2);
+        assert_eq!(response.message[0].name, "Hip Hop");
+        assert_eq!(response.message[1].name, "R&B");
+    }
+
+    #[tokio::test]
+    async fn test_get_genre_albums() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "http://{ADDR}/genres/2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11/albums"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::album::Model>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 1);
+        assert_eq!(
+            response.message[0].id,
+            "46ffbb9a-8c98-45d6-a561-0cb80214a642"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_genre_songs() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "http://{ADDR}/genres/b8e3f1a2-3c4d-4e5f-9a6b-7c8d9e0f1a2b/songs"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::song::Model>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_genre_not_found() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/genres/missing/songs"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,178 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, Router};
+    use deaftone::{
+        services::{scanner::ScanType, task::history::TaskProgress},
+        test_util::{app_with_database, new_seaorm_db, ADDR, ADMIN_TOKEN},
+    };
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use sea_orm::{ConnectionTrait, DbBackend};
+    use serde_json::{from_slice, json, Value};
+    use tower::ServiceExt;
+
+    async fn request(app: &Router, method: &str, uri: &str, body: Option<&Value>) -> Value {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(format!("http://{ADDR}{uri}"))
+            .header("authorization", format!("Bearer {ADMIN_TOKEN}"));
+        if body.is_some() {
+            builder = builder.header("content-type", "application/json");
+        }
+        let resp = app
+            .clone()
+            .oneshot(
+                builder
+                    .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(resp.status() == StatusCode::OK || resp.status() == StatusCode::CREATED);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_scan_records_issues() {
+        let root = std::env::temp_dir().join(format!("deaftone-issues-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("Akon/Konvicted")).unwrap();
+        std::fs::create_dir_all(root.join("Deleted/Album")).unwrap();
+        std::fs::write(root.join("Akon/Konvicted/01 Smack That.flac"), b"not flac").unwrap();
+
+        let db = new_seaorm_db().await.unwrap();
+        let app = app_with_database(db.clone()).await;
+        let library = request(
+            &app,
+            "POST",
+            "/libraries",
+            Some(&json!({"name": "Issues", "path": root.to_string_lossy()})),
+        )
+        .await;
+        let library_id = library["message"]["id"].as_str().unwrap().to_owned();
+        deaftone::services::scanner::start_scan(
+            ScanType::FullScan,
+            Some(library_id.clone()),
+            &db,
+            &TaskProgress::default(),
+        )
+        .await
+        .unwrap();
+
+        let issues = request(
+            &app,
+            "GET",
+            &format!("/admin/issues?library={library_id}"),
+            None,
+        )
+        .await;
+        assert_eq!(
+            issues["message"]["counts"],
+            json!([
+                {"kind": "orphaned_directory", "count": 2},
+                {"kind": "unreadable_file", "count": 1}
+            ])
+        );
+
+        let unreadable = request(
+            &app,
+            "GET",
+            &format!("/admin/issues?library={library_id}&kind=unreadable_file"),
+            None,
+        )
+        .await;
+        assert_eq!(unreadable["message"]["total"], 1);
+        let path = root.join("Akon/Konvicted/01 Smack That.flac");
+        assert_eq!(
+            unreadable["message"]["issues"][0]["path"],
+            path.to_string_lossy().as_ref()
+        );
+
+        // Scanning again replaces the issues instead of adding to them
+        deaftone::services::scanner::start_scan(
+            ScanType::FullScan,
+            Some(library_id.clone()),
+            &db,
+            &TaskProgress::default(),
+        )
+        .await
+        .unwrap();
+        let issues = request(&app, "GET", "/admin/issues", None).await;
+        assert_eq!(issues["message"]["total"], 3);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    // A failed statement aborts the transaction of a directory on PostgreSQL. The failed file is rolled
+    // back on its own and the other files of the directory are still stored
+    #[tokio::test]
+    async fn test_scan_skips_failed_file() {
+        let root = std::env::temp_dir().join(format!("deaftone-failed-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("Akon/Konvicted")).unwrap();
+        for name in [
+            "01 Broken.flac",
+            "02 Smack That.flac",
+            "03 Mama Africa.flac",
+        ] {
+            std::fs::write(root.join("Akon/Konvicted").join(name), b"not flac").unwrap();
+        }
+
+        let db = new_seaorm_db().await.unwrap();
+        let trigger = match db.get_database_backend() {
+            DbBackend::Postgres => {
+                "CREATE FUNCTION fail_broken() RETURNS trigger AS $$ BEGIN
+                    IF NEW.path LIKE '%Broken%' THEN RAISE EXCEPTION 'broken file'; END IF;
+                    RETURN NEW;
+                END $$ LANGUAGE plpgsql;
+                CREATE TRIGGER fail_broken BEFORE INSERT ON library_issues
+                    FOR EACH ROW EXECUTE FUNCTION fail_broken();"
+            }
+            _ => {
+                "CREATE TRIGGER fail_broken BEFORE INSERT ON library_issues
+                    WHEN NEW.path LIKE '%Broken%'
+                    BEGIN SELECT RAISE(ABORT, 'broken file'); END;"
+            }
+        };
+        db.execute_unprepared(trigger).await.unwrap();
+        let app = app_with_database(db.clone()).await;
+        let library = request(
+            &app,
+            "POST",
+            "/libraries",
+            Some(&json!({"name": "Failed", "path": root.to_string_lossy()})),
+        )
+        .await;
+        let library_id = library["message"]["id"].as_str().unwrap().to_owned();
+        deaftone::services::scanner::start_scan(
+            ScanType::FullScan,
+            Some(library_id.clone()),
+            &db,
+            &TaskProgress::default(),
+        )
+        .await
+        .unwrap();
+
+        let unreadable = request(
+            &app,
+            "GET",
+            &format!("/admin/issues?library={library_id}&kind=unreadable_file"),
+            None,
+        )
+        .await;
+        let mut paths: Vec<&str> = unreadable["message"]["issues"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|issue| issue["path"].as_str().unwrap())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            ["02 Smack That.flac", "03 Mama Africa.flac"]
+                .map(|name| root.join("Akon/Konvicted").join(name))
+                .iter()
+                .map(|path| path.to_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use deaftone::{
+        services::{http::SuccessResponse, scanner::ScanType, task::history::TaskProgress},
+        test_util::{app, app_with_database, new_seaorm_db, seed_test_db, ADDR},
+    };
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter};
+    use serde_json::{from_slice, json};
+    use tower::ServiceExt;
+
+    const MUSIC_LIBRARY: &str = "0f8a3c1e-2b4d-4e6f-8a9b-1c2d3e4f5a6b";
+    const AUDIOBOOKS_LIBRARY: &str = "9d8c7b6a-5f4e-4d3c-8b2a-1f0e9d8c7b6a";
+
+    #[tokio::test]
+    async fn test_get_libraries() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/libraries"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::library::Model>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 2);
+        assert_eq!(response.message[0].name, "Audiobooks");
+        assert_eq!(response.message[0].exclude.as_deref(), Some("**/*.cue"));
+    }
+
+    #[tokio::test]
+    async fn test_create_and_delete_library() {
+        let app = app().await;
+        let library = json!({
+            "name": "Classical",
+            "path": "/mnt/classical",
+            "include": ["**/*.flac"],
+            "exclude": ["**/Samples/**"]
+        });
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("http://{ADDR}/libraries"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(library.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<entity::library::Model> = from_slice(&body).unwrap();
+        assert_eq!(response.message.include.as_deref(), Some("**/*.flac"));
+        let library_id = response.message.id;
+
+        // Names are unique
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("http://{ADDR}/libraries"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(library.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!("http://{ADDR}/libraries/{library_id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/libraries/{library_id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_create_library_invalid_glob() {
+        let app = app().await;
+        let library = json!({
+            "name": "Broken",
+            "path": "/mnt/broken",
+            "include": ["***"]
+        });
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("http://{ADDR}/libraries"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(library.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_library_filter() {
+        let app = app().await;
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/albums?library={MUSIC_LIBRARY}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::album::Model>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 6);
+        assert!(response
+            .message
+            .iter()
+            .all(|a| a.library_id.as_deref() == Some(MUSIC_LIBRARY)));
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "http://{ADDR}/artists?library={MUSIC_LIBRARY}&page=0&size=50"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::artist::Model>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 1);
+        assert_eq!(response.message[0].name, "Akon");
+
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/genres?library={AUDIOBOOKS_LIBRARY}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::genre::Model>> = from_slice(&body).unwrap();
+        assert!(response.message.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_library_duplicate_name() {
+        let app = app().await;
+        let update = |name: &str| {
+            Request::builder()
+                .method("PUT")
+                .uri(format!("http://{ADDR}/libraries/{AUDIOBOOKS_LIBRARY}"))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({"name": name, "path": "/mnt/audiobooks"}).to_string(),
+                ))
+                .unwrap()
+        };
+        let resp = app.clone().oneshot(update(" Music ")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        // Keeping its own name is not a conflict
+        let resp = app.oneshot(update("Audiobooks")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    // Databases scanned before libraries existed have no library_id. Adding the library assigns the rows
+    // inside its root so a full scan updates them instead of adding every album again
+    #[tokio::test]
+    async fn test_full_scan_adopts_unassigned_rows() {
+        let root = std::env::temp_dir().join(format!("deaftone-adopt-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("A/Akon - Konvicted")).unwrap();
+        std::fs::write(
+            root.join("A/Akon - Konvicted/01 Smack That.flac"),
+            b"not flac",
+        )
+        .unwrap();
+        let root_path = root.to_string_lossy();
+
+        let db = new_seaorm_db().await.unwrap();
+        seed_test_db(&db).await.unwrap();
+        for table in ["songs", "albums", "directories"] {
+            db.execute_unprepared(&format!(
+                r"UPDATE {table} SET library_id = NULL,
+                    path = replace(replace(path, 'H:\aa', '{root_path}'), '\', '/')"
+            ))
+            .await
+            .unwrap();
+        }
+        db.execute_unprepared("DELETE FROM libraries")
+            .await
+            .unwrap();
+        let albums = entity::album::Entity::find().count(&db).await.unwrap();
+
+        let app = app_with_database(db.clone()).await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("http://{ADDR}/libraries"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({"name": "Music", "path": root_path}).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let library: SuccessResponse<entity::library::Model> = from_slice(&body).unwrap();
+        deaftone::services::scanner::start_scan(
+            ScanType::FullScan,
+            Some(library.message.id.clone()),
+            &db,
+            &TaskProgress::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            entity::album::Entity::find().count(&db).await.unwrap(),
+            albums
+        );
+        assert_eq!(
+            entity::album::Entity::find()
+                .filter(entity::album::Column::LibraryId.eq(&library.message.id))
+                .count(&db)
+                .await
+                .unwrap(),
+            albums
+        );
+        assert_eq!(
+            entity::song::Entity::find()
+                .filter(entity::song::Column::LibraryId.is_null())
+                .count(&db)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            entity::directory::Entity::find()
+                .filter(entity::directory::Column::LibraryId.is_null())
+                .count(&db)
+                .await
+                .unwrap(),
+            0
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
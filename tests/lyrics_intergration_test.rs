@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use deaftone::test_util::{app_with_database, new_seaorm_db, seed_test_db, ADDR};
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+    use serde_json::{from_slice, json, Value};
+    use tower::ServiceExt;
+
+    const SONG: &str = "08696c90-b6b7-45c2-b4ef-a767250efe60";
+
+    #[tokio::test]
+    async fn test_get_song_lyrics() {
+        let root = std::env::temp_dir().join(format!("deaftone-lyrics-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("02 Smack That.flac");
+        std::fs::write(
+            root.join("02 Smack That.lrc"),
+            "[offset:-250]\n[00:01.00]Shady\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("02 Smack That.es.txt"), "Sombra\n").unwrap();
+        std::fs::write(root.join("03 Other Song.lrc"), "[00:01.00]Other\n").unwrap();
+
+        let db = new_seaorm_db().await.unwrap();
+        seed_test_db(&db).await.unwrap();
+        entity::song::Entity::update_many()
+            .col_expr(
+                entity::song::Column::Path,
+                Expr::value(path.to_string_lossy().to_string()),
+            )
+            .filter(entity::song::Column::Id.eq(SONG))
+            .exec(&db)
+            .await
+            .unwrap();
+        let embedded = vec![String::from("I see you winding\nand grinding")];
+        let paths: Vec<std::path::PathBuf> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        deaftone::services::lyrics::scan_song_lyrics(
+            &db,
+            SONG,
+            &path.to_string_lossy(),
+            &[embedded.clone(), embedded].concat(),
+            &deaftone::services::lyrics::sidecar_candidates(&paths),
+        )
+        .await
+        .unwrap();
+        let app = app_with_database(db).await;
+
+        let get = |uri: String| {
+            let app = app.clone();
+            async move {
+                let resp = app
+                    .oneshot(
+                        Request::builder()
+                            .uri(format!("http://{ADDR}{uri}"))
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = resp.status();
+                let body = resp.into_body().collect().await.unwrap().to_bytes();
+                (status, from_slice::<Value>(&body).unwrap())
+            }
+        };
+
+        let (status, lyrics) = get(format!("/songs/{SONG}/lyrics")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            lyrics["message"],
+            json!([
+                {
+                    "source": "sidecar",
+                    "language": null,
+                    "synced": true,
+                    "offset": -250,
+                    "lines": [{"start": 1250, "text": "Shady"}]
+                },
+                {
+                    "source": "embedded",
+                    "language": null,
+                    "synced": false,
+                    "offset": 0,
+                    "lines": [
+                        {"start": null, "text": "I see you winding"},
+                        {"start": null, "text": "and grinding"}
+                    ]
+                },
+                {
+                    "source": "sidecar",
+                    "language": "es",
+                    "synced": false,
+                    "offset": 0,
+                    "lines": [{"start": null, "text": "Sombra"}]
+                }
+            ])
+        );
+
+        let (_, spanish) = get(format!("/songs/{SONG}/lyrics?language=ES")).await;
+        assert_eq!(spanish["message"].as_array().unwrap().len(), 1);
+        assert_eq!(spanish["message"][0]["lines"][0]["text"], "Sombra");
+
+        let (status, _) = get(String::from("/songs/missing/lyrics")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use deaftone::test_util::new_empty_db;
+    use migration::{Migrator, MigratorTrait};
+    use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
+
+    async fn tables(db: &DatabaseConnection) -> Vec<String> {
+        let backend = db.get_database_backend();
+        let sql = match backend {
+            DbBackend::Postgres => "SELECT table_name AS name FROM information_schema.tables WHERE table_schema = current_schema() AND table_name != 'seaql_migrations' ORDER BY name",
+            _ => "SELECT name FROM sqlite_master WHERE type = 'table' AND name != 'seaql_migrations' ORDER BY name",
+        };
+        db.query_all(Statement::from_string(backend, sql))
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.try_get::<String>("", "name").unwrap())
+            .collect()
+    }
+
+    async fn columns(db: &DatabaseConnection, table: &str) -> Vec<String> {
+        let backend = db.get_database_backend();
+        let sql = match backend {
+            DbBackend::Postgres => format!("SELECT column_name AS name FROM information_schema.columns WHERE table_schema = current_schema() AND table_name = '{table}'"),
+            _ => format!("SELECT name FROM pragma_table_info('{table}')"),
+        };
+        db.query_all(Statement::from_string(backend, sql))
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.try_get::<String>("", "name").unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_migrations_are_reversible() {
+        let db = new_empty_db().await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let migrated = tables(&db).await;
+        assert!(migrated.contains(&String::from("songs")));
+        assert!(columns(&db, "albums")
+            .await
+            .contains(&String::from("compilation")));
+
+        Migrator::down(&db, None).await.unwrap();
+        assert!(tables(&db).await.is_empty());
+
+        Migrator::up(&db, None).await.unwrap();
+        assert_eq!(tables(&db).await, migrated);
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_upgrades_existing_tables() {
+        let db = new_empty_db().await.unwrap();
+        // Songs table of an old database without the liked column
+        db.execute_unprepared(
+            "CREATE TABLE songs (id text NOT NULL PRIMARY KEY, path text NOT NULL UNIQUE, title text NOT NULL);
+            INSERT INTO songs (id, path, title) VALUES ('song', '/music/song.flac', 'Song');",
+        )
+        .await
+        .unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        let songs = columns(&db, "songs").await;
+        assert!(songs.contains(&String::from("liked")));
+        assert!(songs.contains(&String::from("library_id")));
+        let row = db
+            .query_one(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT liked, length FROM songs WHERE id = 'song'",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!row.try_get::<bool>("", "liked").unwrap());
+        assert_eq!(row.try_get::<i32>("", "length").unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_adds_missing_baseline_columns() {
+        let db = new_empty_db().await.unwrap();
+        // Database that already recorded the baseline migration but was created without the liked column
+        Migrator::up(&db, Some(1)).await.unwrap();
+        db.execute_unprepared(
+            "ALTER TABLE songs DROP COLUMN liked;
+            INSERT INTO songs (id, path, title, artist, album_name, length, created_at, updated_at)
+            VALUES ('song', '/music/song.flac', 'Song', 'Artist', 'Album', 60, '2020-01-01 00:00:00', '2020-01-01 00:00:00');",
+        )
+        .await
+        .unwrap();
+        assert!(!columns(&db, "songs").await.contains(&String::from("liked")));
+
+        Migrator::up(&db, None).await.unwrap();
+        assert!(columns(&db, "songs").await.contains(&String::from("liked")));
+        let row = db
+            .query_one(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT liked FROM songs WHERE id = 'song'",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!row.try_get::<bool>("", "liked").unwrap());
+    }
+}
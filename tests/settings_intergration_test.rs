@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use deaftone::test_util::{app, ADDR};
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use serde_json::{from_slice, json, Value};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_get_settings() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/settings"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: Value = from_slice(&body).unwrap();
+        assert_eq!(response["message"]["listen_addr"], "0.0.0.0:3030");
+        assert_eq!(response["message"]["request_timeout"], "10s");
+        assert_eq!(response["message"]["db_max_connections"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_update_runtime_settings() {
+        let app = app().await;
+        let settings = json!({
+            "scan_schedule": "6h",
+            "transcoding_profiles": [
+                {"name": "opus_96", "codec": "libopus", "bitrate": "96k", "format": "ogg"}
+            ],
+            "metadata_providers": ["musicbrainz"]
+        });
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("http://{ADDR}/settings/runtime"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(settings.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/settings/runtime"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: Value = from_slice(&body).unwrap();
+        assert_eq!(response["message"], settings);
+
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("http://{ADDR}/settings/runtime"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({
+                            "scan_schedule": "every day",
+                            "transcoding_profiles": [],
+                            "metadata_providers": []
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
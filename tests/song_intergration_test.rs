@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use deaftone::test_util::{app_with_database, new_seaorm_db, seed_test_db, ADDR};
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+    use serde_json::{from_slice, json, Value};
+    use tower::ServiceExt;
+
+    const SONG: &str = "08696c90-b6b7-45c2-b4ef-a767250efe60";
+
+    #[tokio::test]
+    async fn test_get_song_gapless() {
+        let db = new_seaorm_db().await.unwrap();
+        seed_test_db(&db).await.unwrap();
+        entity::song::Entity::update_many()
+            .col_expr(
+                entity::song::Column::TotalSamples,
+                Expr::value(9_151_059i64),
+            )
+            .col_expr(entity::song::Column::SampleRate, Expr::value("44100"))
+            .col_expr(entity::song::Column::EncoderDelay, Expr::value(0))
+            .col_expr(entity::song::Column::EncoderPadding, Expr::value(0))
+            .col_expr(entity::song::Column::DurationMs, Expr::value(207_507i64))
+            .filter(entity::song::Column::Id.eq(SONG))
+            .exec(&db)
+            .await
+            .unwrap();
+        let app = app_with_database(db).await;
+
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/songs/{SONG}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let song = &from_slice::<Value>(&body).unwrap()["message"];
+        assert_eq!(song["total_samples"], json!(9_151_059));
+        assert_eq!(song["sample_rate"], json!(44_100));
+        assert_eq!(song["encoder_delay"], json!(0));
+        assert_eq!(song["encoder_padding"], json!(0));
+        assert_eq!(song["duration_ms"], json!(207_507));
+    }
+}
@@ -0,0 +1,278 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use chrono::NaiveDateTime;
+    use deaftone::{
+        services::{
+            http::SuccessResponse,
+            stats::{FormatBreakdown, LibraryGrowth, ListeningTime, TopItem},
+        },
+        test_util::{app, app_with_database, new_seaorm_db, seed_test_db, ADDR},
+    };
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+    use serde_json::from_slice;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_scrobble_and_top_songs() {
+        let app = app().await;
+        for _ in 0..2 {
+            let resp = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri(format!(
+                            "http://{ADDR}/songs/08696c90-b6b7-45c2-b4ef-a767250efe60/scrobble"
+                        ))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!(
+                        "http://{ADDR}/songs/7db7d5cf-b1bc-4308-94c1-18784dfc2184/scrobble?duration=30"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/stats/top/songs"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<TopItem>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 2);
+        assert_eq!(
+            response.message[0].id,
+            "08696c90-b6b7-45c2-b4ef-a767250efe60"
+        );
+        assert_eq!(response.message[0].plays, 2);
+        assert_eq!(response.message[0].listened, 560);
+        assert_eq!(response.message[1].listened, 30);
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/stats/top/albums"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<TopItem>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 1);
+        assert_eq!(response.message[0].plays, 3);
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/stats/listening?interval=week"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<ListeningTime>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 1);
+        assert_eq!(response.message[0].listened, 590);
+    }
+
+    #[tokio::test]
+    async fn test_top_songs_outside_window() {
+        let app = app().await;
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!(
+                        "http://{ADDR}/songs/08696c90-b6b7-45c2-b4ef-a767250efe60/scrobble"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "http://{ADDR}/stats/top/songs?from=2020-01-01&to=2020-12-31"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<TopItem>> = from_slice(&body).unwrap();
+        assert!(response.message.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_library_growth() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/stats/growth?interval=month"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<LibraryGrowth>> = from_slice(&body).unwrap();
+        let last = response.message.last().unwrap();
+        assert_eq!(
+            last.total_albums,
+            response.message.iter().map(|g| g.albums).sum::<i64>()
+        );
+        assert!(last.total_songs > 0);
+    }
+
+    #[tokio::test]
+    async fn test_format_breakdown() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/stats/formats"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<FormatBreakdown>> = from_slice(&body).unwrap();
+        assert!(response
+            .message
+            .iter()
+            .any(|f| f.sample_rate.as_deref() == Some("44100") && f.bits_per_sample == Some(16)));
+    }
+
+    #[tokio::test]
+    async fn test_listening_weeks_across_new_year() {
+        let db = new_seaorm_db().await.unwrap();
+        seed_test_db(&db).await.unwrap();
+        entity::play::Entity::delete_many().exec(&db).await.unwrap();
+        // Sunday, Monday and Wednesday around New Year 2025 and the next Sunday and Monday
+        for played_at in [
+            "2024-12-29 12:00:00",
+            "2024-12-30 12:00:00",
+            "2025-01-01 12:00:00",
+            "2025-01-05 23:00:00",
+            "2025-01-06 00:30:00",
+        ] {
+            entity::play::ActiveModel {
+                id: Set(uuid::Uuid::new_v4().to_string()),
+                song_id: Set(Some(String::from("08696c90-b6b7-45c2-b4ef-a767250efe60"))),
+                played_at: Set(
+                    NaiveDateTime::parse_from_str(played_at, "%Y-%m-%d %H:%M:%S").unwrap(),
+                ),
+                duration: Set(60),
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        let resp = app_with_database(db)
+            .await
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/stats/listening?interval=week"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<ListeningTime>> = from_slice(&body).unwrap();
+        let weeks: Vec<(&str, i64)> = response
+            .message
+            .iter()
+            .map(|week| (week.period.as_str(), week.plays))
+            .collect();
+        assert_eq!(
+            weeks,
+            vec![("2024-12-23", 1), ("2024-12-30", 3), ("2025-01-06", 1)]
+        );
+    }
+
+    // Removing a song from the library keeps its plays in the listening history
+    #[tokio::test]
+    async fn test_plays_outlive_removed_songs() {
+        const SONG: &str = "08696c90-b6b7-45c2-b4ef-a767250efe60";
+        let db = new_seaorm_db().await.unwrap();
+        seed_test_db(&db).await.unwrap();
+        entity::play::Entity::delete_many().exec(&db).await.unwrap();
+        let app = app_with_database(db.clone()).await;
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("http://{ADDR}/songs/{SONG}/scrobble?duration=30"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        entity::song::Entity::delete_by_id(SONG)
+            .exec(&db)
+            .await
+            .unwrap();
+
+        let plays = entity::play::Entity::find().all(&db).await.unwrap();
+        assert_eq!(plays.len(), 1);
+        assert_eq!(plays[0].song_id, None);
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/stats/listening?interval=day"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<ListeningTime>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 1);
+        assert_eq!(response.message[0].plays, 1);
+        assert_eq!(response.message[0].listened, 30);
+    }
+}
@@ -0,0 +1,287 @@
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Method, Request},
+        Router,
+    };
+    use deaftone::test_util::{app_with_database, new_seaorm_db, seed_test_db, ADDR};
+    use http_body_util::BodyExt;
+    use hyper::{HeaderMap, StatusCode};
+    use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+    use tower::ServiceExt;
+
+    const SONG: &str = "08696c90-b6b7-45c2-b4ef-a767250efe60";
+
+    async fn request(
+        app: &Router,
+        method: Method,
+        uri: String,
+        headers: &[(header::HeaderName, &str)],
+    ) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(format!("http://{ADDR}{uri}"));
+        for (name, value) in headers {
+            builder = builder.header(name, *value);
+        }
+        let resp = app
+            .clone()
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        (status, headers, body.to_vec())
+    }
+
+    // Points every song of the album of SONG at a file in a temp directory
+    async fn app_with_files() -> (Router, String, Vec<entity::song::Model>, std::path::PathBuf) {
+        let root = std::env::temp_dir().join(format!("deaftone-stream-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let db = new_seaorm_db().await.unwrap();
+        seed_test_db(&db).await.unwrap();
+        let album_id = entity::song::Entity::find_by_id(SONG)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap()
+            .album_id
+            .unwrap();
+        let songs = entity::song::Entity::find()
+            .filter(entity::song::Column::AlbumId.eq(&album_id))
+            .all(&db)
+            .await
+            .unwrap();
+        for (i, song) in songs.iter().enumerate() {
+            let path = root.join(format!("{:02} Song.flac", i + 1));
+            std::fs::write(&path, format!("fLaC song number {i}")).unwrap();
+            entity::song::Entity::update_many()
+                .col_expr(
+                    entity::song::Column::Path,
+                    Expr::value(path.to_string_lossy().to_string()),
+                )
+                .filter(entity::song::Column::Id.eq(&song.id))
+                .exec(&db)
+                .await
+                .unwrap();
+        }
+        let songs = entity::song::Entity::find()
+            .filter(entity::song::Column::AlbumId.eq(&album_id))
+            .all(&db)
+            .await
+            .unwrap();
+        (app_with_database(db).await, album_id, songs, root)
+    }
+
+    #[tokio::test]
+    async fn test_stream_song() {
+        let (app, _, songs, root) = app_with_files().await;
+        let song = songs.iter().find(|song| song.id == SONG).unwrap();
+        let content = std::fs::read(&song.path).unwrap();
+
+        let (status, headers, body) =
+            request(&app, Method::GET, format!("/stream/{SONG}"), &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, content);
+        assert_eq!(headers[header::ACCEPT_RANGES], "bytes");
+        let etag = headers[header::ETAG].to_str().unwrap().to_string();
+
+        let (status, headers, body) = request(
+            &app,
+            Method::GET,
+            format!("/stream/{SONG}"),
+            &[(header::RANGE, "bytes=2-5")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body, &content[2..6]);
+        assert_eq!(
+            headers[header::CONTENT_RANGE],
+            format!("bytes 2-5/{}", content.len())
+        );
+
+        let (status, _, _) = request(
+            &app,
+            Method::GET,
+            format!("/stream/{SONG}"),
+            &[(header::RANGE, "bytes=1000-")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+
+        let (status, headers, body) = request(
+            &app,
+            Method::GET,
+            format!("/stream/{SONG}"),
+            &[(header::IF_NONE_MATCH, &format!("\"other\", W/{etag}"))],
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert_eq!(headers[header::ETAG], etag.as_str());
+        assert!(body.is_empty());
+
+        let (status, headers, body) =
+            request(&app, Method::HEAD, format!("/stream/{SONG}"), &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            headers[header::CONTENT_LENGTH],
+            content.len().to_string().as_str()
+        );
+        assert!(body.is_empty());
+
+        std::fs::remove_file(&song.path).unwrap();
+        let (status, _, _) = request(&app, Method::GET, format!("/stream/{SONG}"), &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_publishes_now_playing() {
+        let (app, _, _, root) = app_with_files().await;
+        let events = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/events?types=now_playing"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let mut events = events.into_body();
+
+        // Seeking and HEAD requests don't start playback
+        request(
+            &app,
+            Method::GET,
+            format!("/stream/{SONG}"),
+            &[(header::RANGE, "bytes=2-5")],
+        )
+        .await;
+        request(&app, Method::HEAD, format!("/stream/{SONG}"), &[]).await;
+        request(&app, Method::GET, format!("/stream/{SONG}"), &[]).await;
+
+        let frame = tokio::time::timeout(std::time::Duration::from_secs(5), events.frame())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let event = String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap();
+        assert!(event.starts_with("event: now_playing\n"));
+        assert!(event.contains(SONG));
+        let next =
+            tokio::time::timeout(std::time::Duration::from_millis(200), events.frame()).await;
+        assert!(next.is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_song() {
+        let (app, _, songs, root) = app_with_files().await;
+        let song = songs.iter().find(|song| song.id == SONG).unwrap();
+
+        let (status, headers, body) =
+            request(&app, Method::GET, format!("/songs/{SONG}/download"), &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, std::fs::read(&song.path).unwrap());
+        let disposition = headers[header::CONTENT_DISPOSITION].to_str().unwrap();
+        assert!(disposition.starts_with("attachment; filename=\""));
+        assert!(disposition.contains(&format!("{}.flac\"", song.title)));
+
+        let (status, _, _) = request(&app, Method::GET, "/songs/0/download".into(), &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_album() {
+        let (app, album_id, songs, root) = app_with_files().await;
+
+        let (status, headers, body) = request(
+            &app,
+            Method::GET,
+            format!("/albums/{album_id}/download"),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::CONTENT_TYPE], "application/zip");
+        assert_eq!(
+            headers[header::CONTENT_LENGTH],
+            body.len().to_string().as_str()
+        );
+        assert!(headers[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .ends_with(".zip"));
+        // Entry count in the end of central directory record
+        let end = &body[body.len() - 22..];
+        assert_eq!(&end[..4], &[0x50, 0x4b, 0x05, 0x06]);
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]) as usize, songs.len());
+        for song in &songs {
+            let content = std::fs::read(&song.path).unwrap();
+            assert!(body.windows(content.len()).any(|window| window == content));
+        }
+
+        std::fs::remove_file(&songs[0].path).unwrap();
+        let (status, _, _) = request(
+            &app,
+            Method::GET,
+            format!("/albums/{album_id}/download"),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, _) = request(&app, Method::GET, "/albums/0/download".into(), &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hls_playlists() {
+        let (app, _, songs, root) = app_with_files().await;
+        let song = songs.iter().find(|song| song.id == SONG).unwrap();
+
+        let (status, headers, body) = request(
+            &app,
+            Method::GET,
+            format!("/stream/hls/{SONG}/master.m3u8"),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            headers[header::CONTENT_TYPE],
+            "application/vnd.apple.mpegurl"
+        );
+        let master = String::from_utf8(body).unwrap();
+        assert!(master.contains("#EXT-X-STREAM-INF:BANDWIDTH=128000"));
+        assert!(master.contains("\nmp3_128/playlist.m3u8\n"));
+
+        let (status, _, body) = request(
+            &app,
+            Method::GET,
+            format!("/stream/hls/{SONG}/mp3_128/playlist.m3u8"),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let playlist = String::from_utf8(body).unwrap();
+        let segments = (song.length.max(1) as usize + 9) / 10;
+        assert_eq!(playlist.matches("#EXTINF:").count(), segments);
+        assert!(playlist.ends_with("#EXT-X-ENDLIST\n"));
+
+        for uri in [
+            format!("/stream/hls/{SONG}/flac/playlist.m3u8"),
+            format!("/stream/hls/{SONG}/mp3_128/{segments}.ts"),
+            format!("/stream/hls/{SONG}/mp3_128/first.ts"),
+            String::from("/stream/hls/0/master.m3u8"),
+        ] {
+            let (status, _, _) = request(&app, Method::GET, uri, &[]).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use deaftone::{
+        services::{
+            http::SuccessResponse,
+            task::{self, TaskType},
+        },
+        test_util::{app, new_seaorm_db, ADDR},
+    };
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use serde_json::{from_slice, Value};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_get_tasks() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/tasks"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::task::Model>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 3);
+        assert_eq!(response.message[0].kind, "scan_library_partial");
+        assert_eq!(response.message[0].status, "queued");
+    }
+
+    #[tokio::test]
+    async fn test_get_tasks_by_status() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/tasks?status=failed"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<Vec<entity::task::Model>> = from_slice(&body).unwrap();
+        assert_eq!(response.message.len(), 1);
+        assert_eq!(response.message[0].kind, "scan_metadata");
+        assert_eq!(
+            response.message[0].error.as_deref(),
+            Some("error sending request for url")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_task() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "http://{ADDR}/tasks/3b5e7a90-1c2d-4e3f-9a8b-7c6d5e4f3a21"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<entity::task::Model> = from_slice(&body).unwrap();
+        assert_eq!(response.message.status, "succeeded");
+        assert_eq!(response.message.directories_walked, 12);
+        assert_eq!(response.message.songs_added, 140);
+        assert!(response.message.finished_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_get_task_not_found() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/tasks/missing"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_queue_task() {
+        let app = app().await;
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("http://{ADDR}/tasks?task=db_maintenance"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: Value = from_slice(&body).unwrap();
+        assert_eq!(response["status"], "sent");
+        let id = response["id"].as_str().unwrap();
+
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("http://{ADDR}/tasks/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: SuccessResponse<entity::task::Model> = from_slice(&body).unwrap();
+        assert_eq!(response.message.kind, "db_maintenance");
+        assert_eq!(response.message.status, "queued");
+        assert!(response.message.started_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_queue_invalid_task() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("http://{ADDR}/tasks?task=scan_everything"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_queue_duplicate_task() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("http://{ADDR}/tasks?task=scan_library_partial"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let response: Value = from_slice(&body).unwrap();
+        // The seeded partial scan is still queued
+        assert_eq!(response["id"], "c1d2e3f4-5a6b-4c7d-8e9f-0a1b2c3d4e5f");
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_concurrently() {
+        let db = new_seaorm_db().await.unwrap();
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+        let ids = futures::future::join_all(
+            (0..8).map(|_| task::enqueue(&db, &sender, TaskType::DatabaseMaintenance)),
+        )
+        .await;
+        let ids: Vec<String> = ids.into_iter().map(Result::unwrap).collect();
+        assert!(ids.iter().all(|id| id == &ids[0]));
+        drop(sender);
+        let mut sent = 0;
+        while receiver.recv().await.is_some() {
+            sent += 1;
+        }
+        assert_eq!(sent, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_task() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!(
+                        "http://{ADDR}/tasks/c1d2e3f4-5a6b-4c7d-8e9f-0a1b2c3d4e5f"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_finished_task() {
+        let app = app().await;
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!(
+                        "http://{ADDR}/tasks/3b5e7a90-1c2d-4e3f-9a8b-7c6d5e4f3a21"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+}
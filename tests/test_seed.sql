@@ -76,6 +76,7 @@ CREATE TABLE IF NOT EXISTS "songs" (
 	"channels"	text,
 	"track"	integer,
 	"disk"	integer,
+	"disc_total"	integer,
 	"codec"	text,
 	"length"	integer NOT NULL,
 	"label"	text,
@@ -85,6 +86,17 @@ CREATE TABLE IF NOT EXISTS "songs" (
 	"updated_at"	text NOT NULL,
 	"album_id"	text,
 	"liked"	boolean NOT NULL,
+	"library_id"	text,
+	"mb_recording_id"	text,
+	"duplicate_of"	text,
+	"track_gain"	real,
+	"track_peak"	real,
+	"album_gain"	real,
+	"album_peak"	real,
+	"total_samples"	bigint,
+	"encoder_delay"	integer,
+	"encoder_padding"	integer,
+	"duration_ms"	bigint,
 	FOREIGN KEY("album_id") REFERENCES "albums"("id") ON DELETE SET NULL ON UPDATE CASCADE,
 	PRIMARY KEY("id")
 );
@@ -121,6 +133,9 @@ CREATE TABLE IF NOT EXISTS "albums" (
 	"created_at"	text NOT NULL,
 	"updated_at"	text NOT NULL,
 	"artist_id"	text,
+	"compilation"	boolean NOT NULL DEFAULT 0,
+	"library_id"	text,
+	"duplicate_of"	text,
 	FOREIGN KEY("artist_id") REFERENCES "artists"("id") ON DELETE SET NULL ON UPDATE CASCADE,
 	PRIMARY KEY("id")
 );
@@ -154,6 +169,7 @@ CREATE TABLE IF NOT EXISTS "directories" (
 	"mtime"	text NOT NULL,
 	"created_at"	text NOT NULL,
 	"updated_at"	text NOT NULL,
+	"library_id"	text,
 	PRIMARY KEY("id")
 );
 DROP TABLE IF EXISTS "playlists";
@@ -2460,6 +2476,28 @@ INSERT INTO "directories" ("id","path","mtime","created_at","updated_at") VALUES
  ('12e2a6bd-9f63-470d-8045-20c9602fa26b','H:\aa\Taylor Swift\Taylor Swift - Taylor Swift (2014) GE1257C','2021-04-10 16:36:21.514526400','2023-02-06 21:04:18.125170300','2023-02-06 21:04:18.125170300'),
  ('6510791d-bd47-483a-8816-9bdf1927e4b1','H:\aa\Taylor Swift\Taylor Swift - Taylor Swift (2014) GE1257C\Artwork','2021-04-10 16:36:21.528528','2023-02-06 21:04:18.141867400','2023-02-06 21:04:18.141867400'),
  ('de1394e2-df71-4932-8ff5-89471170605b','H:\aa\test','2022-08-31 17:30:46.454411200','2023-02-06 21:04:18.142637','2023-02-06 21:04:18.142637');
+INSERT INTO "genres" ("id","name","created_at","updated_at") VALUES ('2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11','Hip Hop','2023-02-06 21:04:16.830331400','2023-02-06 21:04:16.830331400'),
+ ('b8e3f1a2-3c4d-4e5f-9a6b-7c8d9e0f1a2b','R&B','2023-02-06 21:04:16.830331400','2023-02-06 21:04:16.830331400');
+INSERT INTO "album_genres" ("album_id","genre_id") VALUES ('46ffbb9a-8c98-45d6-a561-0cb80214a642','2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11'),
+ ('46ffbb9a-8c98-45d6-a561-0cb80214a642','b8e3f1a2-3c4d-4e5f-9a6b-7c8d9e0f1a2b');
+INSERT INTO "song_genres" ("song_id","genre_id") VALUES ('94d63f8c-d473-409b-b560-1dc11e3c69ef','2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11'),
+ ('8672430a-9c65-4b8b-94f0-0bce547d1801','2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11'),
+ ('f3998a61-f1b6-48ad-965d-ff4f37b50f57','2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11'),
+ ('6674b3df-4b88-4501-af58-2f4e8ecc8d67','2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11'),
+ ('53062946-b90d-4449-8559-1ae31112065c','2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11'),
+ ('7e68a90f-3f2a-4a13-998e-dadf7427465a','2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11'),
+ ('21a1aa16-138d-48b8-93d7-5c746d8b64fd','2f6c5d0e-6d8f-4a43-a1a4-0c8a1f7e5b11'),
+ ('94d63f8c-d473-409b-b560-1dc11e3c69ef','b8e3f1a2-3c4d-4e5f-9a6b-7c8d9e0f1a2b'),
+ ('8672430a-9c65-4b8b-94f0-0bce547d1801','b8e3f1a2-3c4d-4e5f-9a6b-7c8d9e0f1a2b');
+INSERT INTO "artist_credits" ("id","artist_id","song_id","album_id","role","position") VALUES ('5b0e8f3c-6a1d-4c2e-9f7a-3d4b5c6e7f80','7d110590-c4ed-4250-973b-f8fa5d60260e','94d63f8c-d473-409b-b560-1dc11e3c69ef',NULL,'main',0),
+ ('c1d2e3f4-a5b6-4c7d-8e9f-0a1b2c3d4e5f','f4051632-9e73-4942-809a-ef3a91ea8cbb','94d63f8c-d473-409b-b560-1dc11e3c69ef',NULL,'featured',1);
+INSERT INTO "libraries" ("id","name","path","scan_schedule","include","exclude","created_at","updated_at") VALUES ('0f8a3c1e-2b4d-4e6f-8a9b-1c2d3e4f5a6b','Music','H:\aa',NULL,NULL,NULL,'2023-02-06 21:04:16.812978900','2023-02-06 21:04:16.812978900'),
+ ('9d8c7b6a-5f4e-4d3c-8b2a-1f0e9d8c7b6a','Audiobooks','H:\audiobooks','24h',NULL,'**/*.cue','2023-02-06 21:04:16.812978900','2023-02-06 21:04:16.812978900');
+UPDATE "albums" SET "library_id" = '0f8a3c1e-2b4d-4e6f-8a9b-1c2d3e4f5a6b' WHERE "artist_id" = '7d110590-c4ed-4250-973b-f8fa5d60260e';
+UPDATE "songs" SET "library_id" = '0f8a3c1e-2b4d-4e6f-8a9b-1c2d3e4f5a6b' WHERE "album_id" IN (SELECT "id" FROM "albums" WHERE "library_id" IS NOT NULL);
+INSERT INTO "tasks" ("id","kind","library_id","status","error","directories_walked","songs_added","songs_updated","songs_removed","artists_updated","created_at","started_at","finished_at") VALUES ('3b5e7a90-1c2d-4e3f-9a8b-7c6d5e4f3a21','scan_library_full','0f8a3c1e-2b4d-4e6f-8a9b-1c2d3e4f5a6b','succeeded',NULL,12,140,0,0,0,'2023-02-06 21:04:16.812978900','2023-02-06 21:04:17.000000000','2023-02-06 21:06:02.000000000'),
+ ('a4c6e8f0-2b3d-4f5a-8c7e-9d0f1a2b3c4d','scan_metadata',NULL,'failed','error sending request for url',0,0,0,0,3,'2023-02-07 10:00:00.000000000','2023-02-07 10:00:01.000000000','2023-02-07 10:00:30.000000000'),
+ ('c1d2e3f4-5a6b-4c7d-8e9f-0a1b2c3d4e5f','scan_library_partial',NULL,'queued',NULL,0,0,0,0,0,'2023-02-08 09:00:00.000000000',NULL,NULL);
 DROP INDEX IF EXISTS "idx-albums-id";
 CREATE INDEX IF NOT EXISTS "idx-albums-id" ON "albums" (
 	"id"